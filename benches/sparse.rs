@@ -0,0 +1,37 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use z_slime::world::{Cell, GridUpdate, World};
+
+/// A mostly-empty grid: a handful of hot spots on an otherwise empty 300x300 world.
+fn sparse_world(grid_update: GridUpdate) -> World {
+    let mut world = World::new();
+    world.params.grid_update = grid_update;
+    for (x, y) in [(40, 40), (150, 80), (220, 260), (90, 200)] {
+        world.set_tile(x, y, Cell::Heat(255.0, 255.0, 255.0));
+    }
+    world
+}
+
+fn bench_sparse_grid(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_tiles_sparse_grid");
+    for (name, grid_update) in [
+        ("dense", GridUpdate::Dense),
+        (
+            "sparse",
+            GridUpdate::Sparse {
+                max_active_fraction: 0.25,
+            },
+        ),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || sparse_world(grid_update),
+                |world| world.update_tiles(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sparse_grid);
+criterion_main!(benches);
@@ -0,0 +1,98 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use z_slime::world::{Cell, World, MAX_HEAT};
+
+const SEED: u64 = 7;
+
+/// A `size` x `size` world with roughly `active_fraction` of its cells hot.
+fn heated_world(size: usize, active_fraction: f64) -> World {
+    let mut world = World::with_size(size, size, SEED);
+    let mut rng = StdRng::seed_from_u64(SEED);
+    for y in 0..size {
+        for x in 0..size {
+            if rng.gen_bool(active_fraction) {
+                let mut heat = || rng.gen_range(0.0..=MAX_HEAT);
+                world.set_tile(x, y, Cell::Heat(heat(), heat(), heat()));
+            }
+        }
+    }
+    world
+}
+
+/// The default world with `count` agents scattered well inside the grid.
+fn populated_world(count: usize, sensing: bool) -> World {
+    let mut world = World::new_seeded(SEED);
+    world.params.sensing = sensing;
+    let mut rng = StdRng::seed_from_u64(SEED);
+    for _ in 0..count {
+        world.spawn_agent(rng.gen_range(10.0..250.0), rng.gen_range(10.0..250.0));
+    }
+    world
+}
+
+fn bench_update_tiles(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_tiles");
+    for size in [100, 300, 600] {
+        group.throughput(Throughput::Elements((size * size) as u64));
+        for active_fraction in [0.01, 0.1, 0.5, 1.0] {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{size}x{size}"), active_fraction),
+                &active_fraction,
+                |b, &active_fraction| {
+                    b.iter_batched_ref(
+                        || heated_world(size, active_fraction),
+                        |world| world.update_tiles(),
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_update_agents(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_agents");
+    for count in [100, 1_000, 10_000] {
+        group.throughput(Throughput::Elements(count as u64));
+        for (name, sensing) in [("bounce", false), ("sensing", true)] {
+            group.bench_with_input(BenchmarkId::new(name, count), &count, |b, &count| {
+                b.iter_batched_ref(
+                    || populated_world(count, sensing),
+                    |world| world.update_agents(),
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Whole updates of the default grid, agents and diffusion together.
+fn bench_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update");
+    for count in [1_000, 10_000] {
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, &count| {
+            b.iter_batched_ref(
+                || {
+                    let mut world = populated_world(count, true);
+                    world.advance(20);
+                    world
+                },
+                |world| world.update(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_update_tiles,
+    bench_update_agents,
+    bench_update
+);
+criterion_main!(benches);
@@ -1,42 +1,881 @@
-
+use crate::world::Cell;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Range each velocity component of a bouncing agent is drawn from.
+pub const VELOCITY_RANGE: (f32, f32) = (0.0, 1.0);
+
+/// How `World::mouse_action` sets up the motion of newly spawned agents.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpawnMode {
+    /// Random velocity, bouncing off the world edges.
+    Bounce,
+    /// Circle the grid center, optionally spiralling inwards.
+    ///
+    /// `angular_speed` is the angle in radians swept around the center per
+    /// update; `inward_rate` is how many cells the orbit radius shrinks per update.
+    Orbit {
+        angular_speed: f32,
+        inward_rate: f32,
+    },
+}
+
+/// Whether agents move through continuous space or hop between cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MovementMode {
+    /// Move by the velocity each update, landing anywhere between cells.
+    Continuous,
+    /// Move exactly one cell per update, to whichever of the eight
+    /// neighbouring cells lies closest to the heading. Sensing still turns
+    /// the heading smoothly, so small turns add up until the agent changes
+    /// cell direction. Positions stay on whole cells.
+    Lattice,
+}
+
+/// How the sensing step turns an agent toward the sensor it picked.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TurnMode {
+    /// Turn straight onto the chosen sensor's direction, giving the sharp
+    /// kinks of classic Physarum models.
+    Snap,
+    /// Turn toward it by at most `rate` radians per update, giving smooth curves.
+    Gradual { rate: f32 },
+}
+
+impl TurnMode {
+    /// The part of a turn of `angle` radians taken this update.
+    pub fn limit(&self, angle: f32) -> f32 {
+        match *self {
+            TurnMode::Snap => angle,
+            TurnMode::Gradual { rate } => angle.clamp(-rate, rate),
+        }
+    }
+}
+
+/// How agents look ahead for trails to steer by.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SensingStrategy {
+    /// Three point sensors, straight ahead and `SensorConfig::angle` to
+    /// either side, turning toward whichever reads the most.
+    ThreePoint,
+    /// `samples` points spread evenly over an arc `half_angle` radians to
+    /// either side of straight ahead, turning toward the centroid of what
+    /// they read. Catches trails that fall between three point sensors and
+    /// steers more smoothly.
+    Cone { half_angle: f32, samples: usize },
+}
+
+/// Forward sensors agents steer with: by default three of them (left,
+/// center, right).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorConfig {
+    /// Three point sensors or a cone of samples.
+    pub strategy: SensingStrategy,
+    /// How many cells ahead of the agent the sensors sit.
+    pub distance: f32,
+    /// Angle in radians between the center sensor and each side sensor,
+    /// for `SensingStrategy::ThreePoint`.
+    pub angle: f32,
+    /// How agents turn toward a brighter side sensor.
+    pub turn: TurnMode,
+    /// How much channels outside an agent's own color count when sensing:
+    /// `0.0` follows only its own color's trail, `1.0` senses every channel
+    /// equally and so follows any trail.
+    pub cross_channel_weight: f32,
+    /// Sense from a copy of the trail map averaged over `downsample` x
+    /// `downsample` blocks instead of the full grid. Larger factors make
+    /// sensing cheaper on big worlds, but agents can no longer tell apart
+    /// trails closer together than a block, so `1` (full resolution) is the
+    /// default.
+    pub downsample: usize,
+    /// How strongly a world's attractor field pulls on the sensors, next to
+    /// the trails: at `1.0`, a field value of `1.0` reads like a full-strength
+    /// trail cell in the agent's own color.
+    pub attractor_weight: f32,
+    /// Sign and strength of the pull trails exert on the sensors: positive
+    /// values draw agents onto trails, negative ones push them away so they
+    /// spread out and carve corridors between each other.
+    pub chemotaxis: f32,
+}
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        Self {
+            strategy: SensingStrategy::ThreePoint,
+            distance: 9.0,
+            angle: 0.4,
+            turn: TurnMode::Snap,
+            cross_channel_weight: 0.0,
+            downsample: 1,
+            attractor_weight: 1.0,
+            chemotaxis: 1.0,
+        }
+    }
+}
+
+/// How an agent's velocity responds to steering.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Motion {
+    /// Fraction of the current velocity kept when steering asks for a new
+    /// one: `0.0` takes the new velocity at once, values toward `1.0`
+    /// accelerate toward it more and more slowly.
+    pub inertia: f32,
+    /// Upper bound on speed in cells per update; `None` leaves it unbounded.
+    pub max_speed: Option<f32>,
+    /// Factor the speed is scaled by on each wall bounce: `1.0` bounces
+    /// elastically, less damps agents, more speeds them up to `max_speed`.
+    pub restitution: f32,
+    /// Lower bound on speed in cells per update, so no agent stalls in place
+    /// baking a single bright spot. Applied every update.
+    pub min_speed: f32,
+    /// Scale each step along the grid's shorter axis down by its aspect
+    /// ratio, so agents look equally fast in every direction once a
+    /// non-square grid is stretched over a square view. Off keeps speeds in
+    /// grid cells. Continuous movement only.
+    pub aspect_locked: bool,
+    /// Cells moved per update for each unit of velocity. Continuous
+    /// movement only.
+    pub speed: f32,
+}
+
+impl Motion {
+    /// What steps along x and y are scaled by on a `width` x `height` grid:
+    /// both `1.0` unless `aspect_locked`, when the shorter axis gets its
+    /// length over the longer one's.
+    pub fn axis_scale(&self, width: usize, height: usize) -> (f32, f32) {
+        let longer = width.max(height).max(1) as f32;
+        if self.aspect_locked {
+            (width as f32 / longer, height as f32 / longer)
+        } else {
+            (1.0, 1.0)
+        }
+    }
+}
+
+impl Default for Motion {
+    /// Instant steering and elastic bounces with no upper speed limit.
+    fn default() -> Self {
+        Self {
+            inertia: 0.0,
+            max_speed: None,
+            restitution: 1.0,
+            min_speed: 0.05,
+            aspect_locked: false,
+            speed: 1.0,
+        }
+    }
+}
 
-const AGENT_SPEED: f32 = 1.0;
+/// A static scalar field sampled at full grid resolution, row-major.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldView<'a> {
+    pub values: &'a [f32],
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FieldView<'_> {
+    fn sample(&self, x: f32, y: f32) -> f32 {
+        if x < 0.0 || y < 0.0 || x as usize >= self.width || y as usize >= self.height {
+            return 0.0;
+        }
+        self.values[x as usize + y as usize * self.width]
+    }
+}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sensor {
+    Left,
+    Center,
+    Right,
+}
 
-#[derive(Clone)]
+/// The sensor with the highest reading. Equal readings are settled by a
+/// fixed order, center, then left, then right, never by chance, so seeded
+/// runs steer the same way every time.
+fn strongest_sensor(left: f32, center: f32, right: f32) -> Sensor {
+    if center >= left && center >= right {
+        Sensor::Center
+    } else if left >= right {
+        Sensor::Left
+    } else {
+        Sensor::Right
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Orbit {
+    center: (f32, f32),
+    angular_speed: f32,
+    inward_rate: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Agent {
+    /// Handed out by the world the agent joins, in spawn order and never
+    /// reused; `0` until then.
+    id: u64,
     pub x: f32,
     pub y: f32,
     pub rgb: (u8, u8, u8),
     velocity: (f32, f32),
+    orbit: Option<Orbit>,
+    /// Position before the latest update, for drawing between updates.
+    prev: (f32, f32),
+    /// Steps taken since the agent last left heat behind.
+    #[serde(skip)]
+    since_deposit: u32,
+    /// The latest positions kept by `record_position`, oldest first.
+    #[serde(skip)]
+    trajectory: VecDeque<(f32, f32)>,
 }
 
 impl Agent {
-    pub fn new(x: f32, y: f32, rgb: (u8, u8, u8)) -> Self {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(crate) fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+
+    pub fn new(x: f32, y: f32, rgb: (u8, u8, u8), rng: &mut impl Rng) -> Self {
+        Self {
+            id: 0,
+            x,
+            y,
+            rgb,
+            velocity: (
+                rng.gen_range(VELOCITY_RANGE.0..VELOCITY_RANGE.1),
+                rng.gen_range(VELOCITY_RANGE.0..VELOCITY_RANGE.1),
+            ),
+            orbit: None,
+            prev: (x, y),
+            since_deposit: 0,
+            trajectory: VecDeque::new(),
+        }
+    }
+
+    /// Create an agent moving at full speed in the direction `heading`,
+    /// in radians from the +x axis.
+    pub fn with_heading(x: f32, y: f32, rgb: (u8, u8, u8), heading: f32) -> Self {
+        let (sin, cos) = heading.sin_cos();
         Self {
+            id: 0,
             x,
             y,
             rgb,
-            velocity: (random_float(0.0, 1.0), random_float(0.0, 1.0))
+            velocity: (cos * VELOCITY_RANGE.1, sin * VELOCITY_RANGE.1),
+            orbit: None,
+            prev: (x, y),
+            since_deposit: 0,
+            trajectory: VecDeque::new(),
         }
     }
 
-    pub fn update(&mut self, world_height: usize, world_width: usize) {
-        self.x = (self.x as f32 + self.velocity.0)*AGENT_SPEED;
-        self.y = (self.y as f32 + self.velocity.1)*AGENT_SPEED;
+    /// Create an agent circling `center` with a tangential velocity.
+    pub fn orbiting(
+        x: f32,
+        y: f32,
+        rgb: (u8, u8, u8),
+        center: (f32, f32),
+        angular_speed: f32,
+        inward_rate: f32,
+    ) -> Self {
+        let mut agent = Self {
+            id: 0,
+            x,
+            y,
+            rgb,
+            velocity: (0.0, 0.0),
+            orbit: Some(Orbit {
+                center,
+                angular_speed,
+                inward_rate,
+            }),
+            prev: (x, y),
+            since_deposit: 0,
+            trajectory: VecDeque::new(),
+        };
+        agent.steer_orbit(&Motion::default());
+        agent
+    }
+
+    pub fn update(&mut self, world_height: usize, world_width: usize, motion: &Motion) {
+        self.steer_orbit(motion);
+        self.clamp_speed(motion);
+
+        let (width, height) = (world_width as f32, world_height as f32);
+        let inside = (0.0..width).contains(&self.x) && (0.0..height).contains(&self.y);
+        let (sx, sy) = motion.axis_scale(world_width, world_height);
+        self.x += self.velocity.0 * sx * motion.speed;
+        self.y += self.velocity.1 * sy * motion.speed;
 
-        if self.x >= (world_width as f32) || self.x <= 0.0 {
-            self.velocity.0 = self.velocity.0*-1.0;
+        // Only a component still heading out turns around, so an agent a
+        // weak rebound leaves outside doesn't flip back and forth there.
+        let mut bounced = false;
+        if (self.x < 0.0 && self.velocity.0 < 0.0) || (self.x >= width && self.velocity.0 > 0.0) {
+            self.velocity.0 = -self.velocity.0;
+            bounced = true;
         }
-        if self.y >= (world_height as f32) || self.y <= 0.0 {
-            self.velocity.1 = self.velocity.1*-1.0;
+        if (self.y < 0.0 && self.velocity.1 < 0.0) || (self.y >= height && self.velocity.1 > 0.0) {
+            self.velocity.1 = -self.velocity.1;
+            bounced = true;
+        }
+        // Stepping off the grid lands on its edge. Agents put down further
+        // out are left for the world to cull.
+        if inside {
+            self.x = self.x.clamp(0.0, width.next_down().max(0.0));
+            self.y = self.y.clamp(0.0, height.next_down().max(0.0));
+        }
+        if bounced {
+            self.rebound(motion);
         }
     }
-}
 
-fn random_float(min: f32, max: f32) -> f32 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..max)
+    /// Count a step toward the next deposit: true on every `interval`th
+    /// call, starting with the first, so an interval of 1 deposits always.
+    pub fn deposit_due(&mut self, interval: u32) -> bool {
+        let due = self.since_deposit == 0;
+        self.since_deposit = (self.since_deposit + 1) % interval.max(1);
+        due
+    }
+
+    /// Add the current position to the trajectory, keeping only the latest
+    /// `max_points`. A limit of `0` clears it and records nothing.
+    pub fn record_position(&mut self, max_points: usize) {
+        while self.trajectory.len() >= max_points.max(1) {
+            self.trajectory.pop_front();
+        }
+        if max_points > 0 {
+            self.trajectory.push_back((self.x, self.y));
+        }
+    }
+
+    /// The recorded positions, oldest first.
+    pub fn trajectory(&self) -> &VecDeque<(f32, f32)> {
+        &self.trajectory
+    }
+
+    /// Note the current position as where the next update starts from.
+    pub fn remember_position(&mut self) {
+        self.prev = (self.x, self.y);
+    }
+
+    /// Where the agent is drawn `alpha` of the way from its position before
+    /// the latest update (`0.0`) to its current one (`1.0`).
+    pub fn position_at(&self, alpha: f32) -> (f32, f32) {
+        (
+            self.prev.0 + (self.x - self.prev.0) * alpha,
+            self.prev.1 + (self.y - self.prev.1) * alpha,
+        )
+    }
+
+    /// Hop to the neighbouring cell nearest the heading, bouncing off the
+    /// grid edges, for `MovementMode::Lattice`.
+    pub fn step_lattice(&mut self, world_height: usize, world_width: usize, motion: &Motion) {
+        self.steer_orbit(motion);
+
+        let (vx, vy) = self.velocity;
+        if vx == 0.0 && vy == 0.0 {
+            return;
+        }
+        let octant = (vy.atan2(vx) / std::f32::consts::FRAC_PI_4).round();
+        let (sin, cos) = (octant * std::f32::consts::FRAC_PI_4).sin_cos();
+        let (mut dx, mut dy) = (cos.round(), sin.round());
+
+        let (x, y) = (self.x.round(), self.y.round());
+        let mut bounced = false;
+        if x + dx < 0.0 || x + dx >= world_width as f32 {
+            dx = -dx;
+            self.velocity.0 = -self.velocity.0;
+            bounced = true;
+        }
+        if y + dy < 0.0 || y + dy >= world_height as f32 {
+            dy = -dy;
+            self.velocity.1 = -self.velocity.1;
+            bounced = true;
+        }
+        if bounced {
+            self.rebound(motion);
+        }
+        (self.x, self.y) = (x + dx, y + dy);
+    }
+
+    /// Bounce off a wall: step back to `prev` and mirror the velocity about
+    /// the wall's unit `normal`.
+    pub fn reflect(&mut self, prev: (f32, f32), normal: (f32, f32), motion: &Motion) {
+        (self.x, self.y) = prev;
+        let dot = self.velocity.0 * normal.0 + self.velocity.1 * normal.1;
+        self.velocity.0 -= 2.0 * dot * normal.0;
+        self.velocity.1 -= 2.0 * dot * normal.1;
+        self.rebound(motion);
+    }
+
+    /// Scale the speed by `motion.restitution` after a bounce, up to
+    /// `motion.max_speed`.
+    fn rebound(&mut self, motion: &Motion) {
+        self.velocity.0 *= motion.restitution;
+        self.velocity.1 *= motion.restitution;
+        self.clamp_speed(motion);
+    }
+
+    /// Sample the sensors and return the angle to turn by this update, as
+    /// limited by the turn mode. With three point sensors that is toward the
+    /// brighter side sensor, or `0.0` when the center one reads the most;
+    /// ties are broken as described on `strongest_sensor`. A cone turns
+    /// toward the centroid of its samples, each weighted by how much more it
+    /// reads than the weakest one, and doesn't turn when they all read the
+    /// same.
+    ///
+    /// `tiles` is `width` x `height` cells, already reduced by
+    /// `config.downsample` when that is above `1`. An `attractor` adds to
+    /// each sensor's reading by `config.attractor_weight`.
+    pub fn sense(
+        &self,
+        tiles: &[Cell],
+        width: usize,
+        height: usize,
+        config: &SensorConfig,
+        attractor: Option<FieldView>,
+    ) -> f32 {
+        let heading = self.velocity.1.atan2(self.velocity.0);
+        let weights = self.channel_weights(config.cross_channel_weight);
+        let block = config.downsample.max(1) as f32;
+        let sample = |angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            let (x, y) = (
+                self.x + cos * config.distance,
+                self.y + sin * config.distance,
+            );
+            let pull = attractor.map_or(0.0, |field| {
+                field.sample(x, y) * 255.0 * weights.iter().sum::<f32>() * config.attractor_weight
+            });
+            let (x, y) = (x / block, y / block);
+            if x < 0.0 || y < 0.0 || x as usize >= width || y as usize >= height {
+                return pull;
+            }
+            pull + config.chemotaxis
+                * match tiles[x as usize + y as usize * width] {
+                    Cell::Empty => 0.0,
+                    Cell::Heat(r, g, b) => r * weights[0] + g * weights[1] + b * weights[2],
+                }
+        };
+
+        let target = match config.strategy {
+            SensingStrategy::ThreePoint => {
+                let left = sample(heading + config.angle);
+                let center = sample(heading);
+                let right = sample(heading - config.angle);
+                match strongest_sensor(left, center, right) {
+                    Sensor::Left => config.angle,
+                    Sensor::Center => 0.0,
+                    Sensor::Right => -config.angle,
+                }
+            }
+            SensingStrategy::Cone {
+                half_angle,
+                samples,
+            } => {
+                let offsets = (0..samples).map(|i| match samples {
+                    1 => 0.0,
+                    _ => half_angle * (2.0 * i as f32 / (samples - 1) as f32 - 1.0),
+                });
+                let readings: Vec<(f32, f32)> = offsets
+                    .map(|offset| (offset, sample(heading + offset)))
+                    .collect();
+                let weakest = readings
+                    .iter()
+                    .map(|&(_, reading)| reading)
+                    .fold(f32::INFINITY, f32::min);
+                let (mut sin, mut cos) = (0.0, 0.0);
+                for &(offset, reading) in &readings {
+                    let weight = reading - weakest;
+                    sin += weight * offset.sin();
+                    cos += weight * offset.cos();
+                }
+                if sin == 0.0 && cos == 0.0 {
+                    0.0
+                } else {
+                    sin.atan2(cos)
+                }
+            }
+        };
+        config.turn.limit(target)
+    }
+
+    /// How strongly each channel counts toward what this agent senses: its
+    /// own color scaled so the strongest channel is `1.0`, with the rest of
+    /// each channel filled in by `cross_channel_weight`.
+    fn channel_weights(&self, cross_channel_weight: f32) -> [f32; 3] {
+        let own = [self.rgb.0, self.rgb.1, self.rgb.2];
+        let max = own.into_iter().max().unwrap_or(0).max(1) as f32;
+        own.map(|c| {
+            let own = c as f32 / max;
+            own + cross_channel_weight * (1.0 - own)
+        })
+    }
+
+    /// Steer toward the direction of travel rotated by `angle` radians at
+    /// the same speed.
+    pub fn turn(&mut self, angle: f32, motion: &Motion) {
+        let (sin, cos) = angle.sin_cos();
+        let (vx, vy) = self.velocity;
+        self.steer((vx * cos - vy * sin, vx * sin + vy * cos), motion);
+    }
+
+    /// Accelerate toward the `desired` velocity as far as `motion.inertia`
+    /// allows, then clamp to `motion.max_speed`.
+    fn steer(&mut self, desired: (f32, f32), motion: &Motion) {
+        let keep = motion.inertia.clamp(0.0, 1.0);
+        let (vx, vy) = self.velocity;
+        self.velocity = (
+            desired.0 + (vx - desired.0) * keep,
+            desired.1 + (vy - desired.1) * keep,
+        );
+        self.clamp_speed(motion);
+    }
+
+    /// Scale the velocity into `motion.min_speed..=motion.max_speed`. A
+    /// standing agent has no direction to keep, so it sets off along +x.
+    fn clamp_speed(&mut self, motion: &Motion) {
+        let (vx, vy) = self.velocity;
+        let speed = (vx * vx + vy * vy).sqrt();
+        let target = match motion.max_speed {
+            Some(max_speed) if speed > max_speed => max_speed,
+            _ if speed < motion.min_speed => motion.min_speed,
+            _ => return,
+        };
+        self.velocity = if speed > 0.0 {
+            (vx * target / speed, vy * target / speed)
+        } else {
+            (target, 0.0)
+        };
+    }
+
+    /// A child placed within a cell of this agent, sharing its color and
+    /// heading off at up to `max_turn` radians from the parent's direction.
+    /// It gets its own id when it joins the world.
+    pub fn offspring(&self, max_turn: f32, rng: &mut impl Rng) -> Self {
+        let mut child = self.clone();
+        child.id = 0;
+        child.trajectory.clear();
+        child.x += rng.gen_range(-1.0..=1.0);
+        child.y += rng.gen_range(-1.0..=1.0);
+        let (sin, cos) = rng.gen_range(-max_turn..=max_turn).sin_cos();
+        let (vx, vy) = child.velocity;
+        child.velocity = (vx * cos - vy * sin, vx * sin + vy * cos);
+        child
+    }
+
+    /// Point the velocity at the next position along the orbit: the current
+    /// offset from the center rotated by `angular_speed` and shortened by
+    /// `inward_rate`.
+    fn steer_orbit(&mut self, motion: &Motion) {
+        let Some(orbit) = self.orbit else {
+            return;
+        };
+
+        let (dx, dy) = (self.x - orbit.center.0, self.y - orbit.center.1);
+        let radius = (dx * dx + dy * dy).sqrt();
+        if radius <= f32::EPSILON {
+            self.steer((0.0, 0.0), motion);
+            return;
+        }
+
+        let shrink = (radius - orbit.inward_rate).max(0.0) / radius;
+        let (sin, cos) = orbit.angular_speed.sin_cos();
+        let next = (
+            (dx * cos - dy * sin) * shrink,
+            (dx * sin + dy * cos) * shrink,
+        );
+        self.steer((next.0 - dx, next.1 - dy), motion);
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: usize = 100;
+    const H: usize = 100;
+
+    /// Red heat below the row `y = 50`, blue heat above it.
+    fn two_species_tiles() -> Vec<Cell> {
+        (0..W * H)
+            .map(|i| match i / W {
+                y if y >= 53 => Cell::Heat(255.0, 0.0, 0.0),
+                y if y <= 47 => Cell::Heat(0.0, 0.0, 255.0),
+                _ => Cell::Empty,
+            })
+            .collect()
+    }
+
+    fn heading_east(rgb: (u8, u8, u8)) -> Agent {
+        Agent {
+            id: 0,
+            x: 50.0,
+            y: 50.0,
+            rgb,
+            velocity: (1.0, 0.0),
+            orbit: None,
+            prev: (50.0, 50.0),
+            since_deposit: 0,
+            trajectory: VecDeque::new(),
+        }
+    }
+
+    #[test]
+    fn species_follow_only_their_own_trail() {
+        let tiles = two_species_tiles();
+        let config = SensorConfig::default();
+
+        let red = heading_east((255, 0, 0)).sense(&tiles, W, H, &config, None);
+        let blue = heading_east((0, 0, 255)).sense(&tiles, W, H, &config, None);
+
+        // Positive turns head toward +y, where the red trail is.
+        assert!(red > 0.0);
+        assert!(blue < 0.0);
+    }
+
+    #[test]
+    fn full_cross_sensing_ignores_color() {
+        let tiles = two_species_tiles();
+        let config = SensorConfig {
+            cross_channel_weight: 1.0,
+            ..SensorConfig::default()
+        };
+
+        let red = heading_east((255, 0, 0)).sense(&tiles, W, H, &config, None);
+        let blue = heading_east((0, 0, 255)).sense(&tiles, W, H, &config, None);
+
+        assert_eq!(red, blue);
+    }
+
+    #[test]
+    fn negative_chemotaxis_steers_away_from_trails() {
+        // Trail under the left and center sensors, none under the right.
+        let tiles: Vec<Cell> = (0..W * H)
+            .map(|i| match i / W {
+                y if y >= 49 => Cell::Heat(255.0, 0.0, 0.0),
+                _ => Cell::Empty,
+            })
+            .collect();
+        let attract = SensorConfig::default();
+        let repel = SensorConfig {
+            chemotaxis: -1.0,
+            ..attract
+        };
+        let red = heading_east((255, 0, 0));
+
+        assert_eq!(red.sense(&tiles, W, H, &attract, None), 0.0);
+        assert_eq!(red.sense(&tiles, W, H, &repel, None), -repel.angle);
+    }
+
+    #[test]
+    fn cones_see_trails_between_point_sensors() {
+        // A single hot cell 0.2 radians left of straight ahead, between the
+        // center and left point sensors.
+        let mut tiles = vec![Cell::Empty; W * H];
+        tiles[58 + 51 * W] = Cell::Heat(255.0, 0.0, 0.0);
+        let points = SensorConfig::default();
+        let cone = SensorConfig {
+            strategy: SensingStrategy::Cone {
+                half_angle: 0.4,
+                samples: 9,
+            },
+            ..points
+        };
+        let red = heading_east((255, 0, 0));
+
+        assert_eq!(red.sense(&tiles, W, H, &points, None), 0.0);
+        let turn = red.sense(&tiles, W, H, &cone, None);
+        assert!((turn - 0.2).abs() < 1e-5, "{turn}");
+        let empty = vec![Cell::Empty; W * H];
+        assert_eq!(red.sense(&empty, W, H, &cone, None), 0.0);
+    }
+
+    #[test]
+    fn gradual_turns_are_rate_limited() {
+        let tiles = two_species_tiles();
+        let snap = SensorConfig::default();
+        let gradual = SensorConfig {
+            turn: TurnMode::Gradual { rate: 0.1 },
+            ..snap
+        };
+        let red = heading_east((255, 0, 0));
+
+        assert_eq!(red.sense(&tiles, W, H, &snap, None), snap.angle);
+        assert_eq!(red.sense(&tiles, W, H, &gradual, None), 0.1);
+    }
+
+    #[test]
+    fn inertia_eases_into_turns_and_max_speed_caps_them() {
+        let quarter_turn = std::f32::consts::FRAC_PI_2;
+
+        let mut instant = heading_east((255, 0, 0));
+        instant.turn(quarter_turn, &Motion::default());
+        assert!((instant.velocity.0).abs() < 1e-6 && (instant.velocity.1 - 1.0).abs() < 1e-6);
+
+        let heavy = Motion {
+            inertia: 0.75,
+            max_speed: Some(0.5),
+            ..Motion::default()
+        };
+        let mut eased = heading_east((255, 0, 0));
+        eased.turn(quarter_turn, &heavy);
+        let (vx, vy) = eased.velocity;
+        // A quarter of the way from east to south, then slowed to 0.5.
+        assert!((vy / vx - 1.0 / 3.0).abs() < 1e-5);
+        assert!(((vx * vx + vy * vy).sqrt() - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn slow_agents_speed_up_to_min_speed() {
+        let motion = Motion {
+            min_speed: 0.5,
+            ..Motion::default()
+        };
+        let mut slow = heading_east((1, 1, 1));
+        slow.velocity = (0.0, 0.1);
+        slow.update(H, W, &motion);
+        assert_eq!(slow.velocity, (0.0, 0.5));
+
+        let mut stopped = heading_east((1, 1, 1));
+        stopped.velocity = (0.0, 0.0);
+        stopped.update(H, W, &motion);
+        assert_eq!((stopped.x, stopped.velocity), (50.5, (0.5, 0.0)));
+    }
+
+    #[test]
+    fn aspect_locked_steps_shrink_along_the_short_axis() {
+        // A 2:1 grid: steps down its 100 rows are half their grid length,
+        // so crossing it takes as long as crossing its 200 columns.
+        let (width, height) = (200, 100);
+        let step = |aspect_locked| {
+            let motion = Motion {
+                aspect_locked,
+                ..Motion::default()
+            };
+            let mut agent = heading_east((1, 1, 1));
+            agent.velocity = (0.6, 0.8);
+            agent.update(height, width, &motion);
+            (agent.x - 50.0, agent.y - 50.0)
+        };
+
+        let (dx, dy) = step(false);
+        assert!((dx - 0.6).abs() < 1e-5 && (dy - 0.8).abs() < 1e-5);
+        let (dx, dy) = step(true);
+        assert!((dx - 0.6).abs() < 1e-5 && (dy - 0.4).abs() < 1e-5);
+        // In screen space, with both axes stretched to the same length,
+        // the locked step keeps the velocity's direction.
+        let screen = (dx / width as f32, dy / height as f32);
+        assert!((screen.1 / screen.0 - 0.8 / 0.6).abs() < 1e-4);
+    }
+
+    #[test]
+    fn lattice_agents_hop_whole_cells() {
+        let mut agent = Agent::with_heading(10.3, 20.7, (255, 0, 0), 0.3);
+        let motion = Motion::default();
+        for _ in 0..40 {
+            let before = (agent.x, agent.y);
+            agent.turn(0.1, &motion);
+            agent.step_lattice(H, W, &motion);
+
+            assert_eq!((agent.x.fract(), agent.y.fract()), (0.0, 0.0));
+            let hop = (agent.x - before.0.round())
+                .abs()
+                .max((agent.y - before.1.round()).abs());
+            assert_eq!(hop, 1.0);
+        }
+    }
+
+    #[test]
+    fn restitution_scales_bounces_up_to_max_speed() {
+        let bounce = |restitution, max_speed| {
+            let mut agent = heading_east((255, 0, 0));
+            agent.x = W as f32 - 0.5;
+            let motion = Motion {
+                max_speed,
+                restitution,
+                ..Motion::default()
+            };
+            agent.update(H, W, &motion);
+            agent.velocity
+        };
+
+        assert_eq!(bounce(1.0, None), (-1.0, 0.0));
+        assert_eq!(bounce(0.5, None), (-0.5, 0.0));
+        assert_eq!(bounce(2.0, Some(1.5)), (-1.5, 0.0));
+    }
+
+    #[test]
+    fn weak_bounces_bring_agents_past_the_edge_back_inside() {
+        let mut agent = heading_east((255, 0, 0));
+        agent.velocity = (-1.0, 0.0);
+        agent.x = -0.8;
+        let motion = Motion {
+            restitution: 0.5,
+            ..Motion::default()
+        };
+
+        agent.update(H, W, &motion);
+        assert_eq!(agent.velocity, (0.5, 0.0));
+        for _ in 0..4 {
+            agent.update(H, W, &motion);
+        }
+        assert!(agent.x > 0.0 && agent.x < W as f32);
+        assert_eq!(agent.velocity, (0.5, 0.0));
+
+        // Bouncing off from inside keeps the agent on the grid.
+        agent.x = 0.2;
+        agent.velocity = (-1.0, 0.0);
+        agent.update(H, W, &motion);
+        assert_eq!((agent.x, agent.velocity), (0.0, (0.5, 0.0)));
+    }
+
+    #[test]
+    fn agents_turn_up_the_attractor_field() {
+        let tiles = vec![Cell::Empty; W * H];
+        let values: Vec<f32> = (0..W * H).map(|i| (i / W) as f32 / H as f32).collect();
+        let field = FieldView {
+            values: &values,
+            width: W,
+            height: H,
+        };
+        let config = SensorConfig::default();
+        let agent = heading_east((255, 0, 0));
+
+        assert_eq!(agent.sense(&tiles, W, H, &config, None), 0.0);
+        assert_eq!(
+            agent.sense(&tiles, W, H, &config, Some(field)),
+            config.angle
+        );
+    }
+
+    #[test]
+    fn sensor_ties_break_center_then_left() {
+        // The same trail on both sides of an empty lane.
+        let tiles: Vec<Cell> = (0..W * H)
+            .map(|i| match i / W {
+                y if y >= 53 || y <= 47 => Cell::Heat(255.0, 0.0, 0.0),
+                _ => Cell::Empty,
+            })
+            .collect();
+        let config = SensorConfig::default();
+        let agent = heading_east((255, 0, 0));
+
+        assert_eq!(agent.sense(&tiles, W, H, &config, None), config.angle);
+        let empty = vec![Cell::Empty; W * H];
+        assert_eq!(agent.sense(&empty, W, H, &config, None), 0.0);
+        assert_eq!(strongest_sensor(2.0, 1.0, 2.0), Sensor::Left);
+        assert_eq!(strongest_sensor(1.0, 2.0, 2.0), Sensor::Center);
+    }
+}
@@ -0,0 +1,271 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use z_slime::demo::Demo;
+use z_slime::params::Preset;
+use z_slime::species::SpeciesMix;
+
+use crate::sweep::Sweep;
+
+pub const USAGE: &str = "\
+usage: z_slime [options]
+
+  --seed <n>             seed the simulation's random number generator
+  --fps <n>              simulation steps per second, fractions allowed
+                         (default: 20)
+  --agents <n>           scatter n agents over the grid at startup
+  --burn-in <n>          diffuse the field for n updates before agents move
+  --sharp-fronts <floor> let no cell lose more than 1 - floor of its heat
+                         to one diffusion pass
+  --species <mix>        spawn species by share, as #rrggbb=share pairs
+                         separated by commas; whole shares are agent counts
+                         unless --agents is given; keys 1-9 pick one for
+                         clicks
+  --preset <name>        start from a preset: classic-slime, cobweb, coral,
+                         dense or sparse
+  --attractor <png>      steer sensing agents toward the bright parts of a
+                         grayscale image the size of the grid
+  --decay-map <png>      fade trails by how dark a grayscale image the size
+                         of the grid is: white keeps all heat, black none
+  --walls <png>          wall off the dark cells of a grayscale image the
+                         size of the grid
+  --demo <name>          set up a built-in scenario: maze, agents finding
+                         the way between two food sources (a generated maze,
+                         or the --walls image); sets its own parameters, so
+                         it can't take a --preset
+  --config <file>        read key bindings and parameters from a TOML file
+                         (default: z_slime.toml, if present)
+  --record-input <file>  write every input to a replayable script on exit
+  --replay <file>        replay a recorded input script with its seed
+  --autosave <file>      save the whole simulation to a file on exit
+  --no-focus-pause       keep simulating while the window is unfocused
+  --threaded             step and draw the simulation on its own thread,
+                         apart from the window
+  --sweep <name>=<values>
+                         render a contact sheet of headless runs instead of
+                         opening a window, over start:end:count or a,b,c
+                         values of a parameter; give it twice for a grid
+  --tiled <name>=<values>
+                         run one world per value of a parameter, side by
+                         side; clicks go to the world under the cursor and
+                         keys to the last one clicked
+  --steps <n>            steps per sweep run (default: 300)
+  --output <png>         where to write the sweep's contact sheet
+                         (default: z_slime-sweep.png)
+
+screenshots: F12 saves the window and F11 just the grid to a timestamped
+PNG, written with the png crate like the other images; P is taken by the
+preset cycle, so rebind screenshot or screenshot-grid under [keybinds] in
+the config file to move them";
+
+/// Command line options for the windowed app.
+#[derive(Debug, Default)]
+pub struct Options {
+    pub seed: Option<u64>,
+    pub fps: Option<f64>,
+    pub agents: Option<usize>,
+    pub preset: Option<Preset>,
+    pub attractor: Option<PathBuf>,
+    pub decay_map: Option<PathBuf>,
+    pub walls: Option<PathBuf>,
+    pub demo: Option<Demo>,
+    pub config: Option<PathBuf>,
+    pub record_input: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub autosave: Option<PathBuf>,
+    pub no_focus_pause: bool,
+    pub threaded: bool,
+    pub burn_in: Option<u32>,
+    pub sharp_fronts: Option<f32>,
+    pub species: Option<SpeciesMix>,
+    pub sweeps: Vec<Sweep>,
+    pub tiled: Option<Sweep>,
+    pub steps: Option<u64>,
+    pub output: Option<PathBuf>,
+}
+
+impl Options {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed = Some(seed.parse().map_err(|_| format!("bad seed {seed:?}"))?);
+                }
+                "--fps" => {
+                    let fps = value()?;
+                    let fps = fps.parse().map_err(|_| format!("bad fps {fps:?}"))?;
+                    time_step(fps)?;
+                    options.fps = Some(fps);
+                }
+                "--agents" => {
+                    let agents = value()?;
+                    let count = agents
+                        .parse()
+                        .map_err(|_| format!("bad agent count {agents:?}"))?;
+                    options.agents = Some(count);
+                }
+                "--preset" => options.preset = Some(value()?.parse()?),
+                "--attractor" => options.attractor = Some(value()?.into()),
+                "--decay-map" => options.decay_map = Some(value()?.into()),
+                "--walls" => options.walls = Some(value()?.into()),
+                "--demo" => options.demo = Some(value()?.parse()?),
+                "--config" => options.config = Some(value()?.into()),
+                "--record-input" => options.record_input = Some(value()?.into()),
+                "--replay" => options.replay = Some(value()?.into()),
+                "--autosave" => options.autosave = Some(value()?.into()),
+                "--no-focus-pause" => options.no_focus_pause = true,
+                "--threaded" => options.threaded = true,
+                "--burn-in" => {
+                    let steps = value()?;
+                    let steps = steps
+                        .parse()
+                        .map_err(|_| format!("bad burn-in {steps:?}"))?;
+                    options.burn_in = Some(steps);
+                }
+                "--sharp-fronts" => {
+                    let floor = value()?;
+                    let floor = floor
+                        .parse()
+                        .ok()
+                        .filter(|floor| (0.0..=1.0).contains(floor))
+                        .ok_or_else(|| format!("bad sharp-fronts floor {floor:?}, need 0 to 1"))?;
+                    options.sharp_fronts = Some(floor);
+                }
+                "--species" => options.species = Some(value()?.parse()?),
+                "--sweep" => {
+                    if options.sweeps.len() == 2 {
+                        return Err("--sweep can be given at most twice".into());
+                    }
+                    options.sweeps.push(value()?.parse()?);
+                }
+                "--tiled" => options.tiled = Some(value()?.parse()?),
+                "--steps" => {
+                    let steps = value()?;
+                    let steps = steps
+                        .parse()
+                        .map_err(|_| format!("bad step count {steps:?}"))?;
+                    options.steps = Some(steps);
+                }
+                "--output" => options.output = Some(value()?.into()),
+                _ => return Err(format!("unknown argument {arg:?}")),
+            }
+        }
+        if options.tiled.is_some() && (options.record_input.is_some() || options.replay.is_some()) {
+            return Err("--tiled worlds can't record or replay input".into());
+        }
+        if options.tiled.is_some() && options.autosave.is_some() {
+            return Err("--tiled worlds can't autosave to one file".into());
+        }
+        if options.demo.is_some() && options.preset.is_some() {
+            return Err("a --demo sets its own parameters, so it can't take a --preset".into());
+        }
+        if options.demo.is_some() && !options.sweeps.is_empty() {
+            return Err("--sweep runs can't start from a --demo".into());
+        }
+        if options.tiled.is_some() && options.threaded {
+            return Err("--tiled worlds can't run --threaded".into());
+        }
+        Ok(options)
+    }
+}
+
+/// The time between updates at `fps` steps per second, which must be a
+/// positive, finite rate.
+pub fn time_step(fps: f64) -> Result<Duration, String> {
+    if !(fps.is_finite() && fps > 0.0) {
+        return Err(format!("fps must be above 0, not {fps}"));
+    }
+    Duration::try_from_secs_f64(1.0 / fps).map_err(|_| format!("fps {fps} is too low"))
+}
+
+/// When the next step falls due, every `time_step` of wall-clock time, so
+/// fractional rates step as often as they say rather than at a whole rate.
+pub struct StepClock {
+    time_step: Duration,
+    next: Instant,
+}
+
+impl StepClock {
+    /// A clock whose first step is due at `start`.
+    pub fn new(time_step: Duration, start: Instant) -> Self {
+        Self {
+            time_step,
+            next: start,
+        }
+    }
+
+    /// Whether a step is due at `now`, moving on to the one after if so.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        if now < self.next {
+            return false;
+        }
+        // Fall behind by at most one step rather than rushing to catch up.
+        self.next = (self.next + self.time_step).max(now);
+        true
+    }
+
+    /// When the next step falls due.
+    pub fn next(&self) -> Instant {
+        self.next
+    }
+
+    /// How far `now` is from the last step to the next, from 0 to 1.
+    pub fn progress(&self, now: Instant) -> f64 {
+        let left = self.next.saturating_duration_since(now);
+        (1.0 - left.as_secs_f64() / self.time_step.as_secs_f64()).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractional_fps_keep_their_fraction() {
+        assert_eq!(time_step(7.5), Ok(Duration::from_nanos(133_333_333)));
+        assert_eq!(time_step(20.0), Ok(Duration::from_millis(50)));
+        assert!(time_step(0.0).is_err());
+        assert!(time_step(-5.0).is_err());
+        assert!(time_step(f64::NAN).is_err());
+
+        let args = ["--fps", "7.5"].map(String::from).into_iter();
+        assert_eq!(Options::parse(args).unwrap().fps, Some(7.5));
+        let args = ["--fps", "0"].map(String::from).into_iter();
+        assert!(Options::parse(args).is_err());
+    }
+
+    #[test]
+    fn demos_refuse_a_preset() {
+        let args = ["--demo", "maze", "--preset", "coral"]
+            .map(String::from)
+            .into_iter();
+        assert!(Options::parse(args).is_err());
+        let args = ["--demo", "maze"].map(String::from).into_iter();
+        assert_eq!(Options::parse(args).unwrap().demo, Some(Demo::Maze));
+    }
+
+    #[test]
+    fn fractional_fps_step_that_often_each_second() {
+        let start = Instant::now();
+        for fps in [7.5, 0.4, 0.25, 60.0] {
+            let mut clock = StepClock::new(time_step(fps).unwrap(), start);
+            // Polled every millisecond over 20 seconds, as a busy loop would.
+            let steps = (0..20_000)
+                .filter(|&ms| clock.tick(start + Duration::from_millis(ms)))
+                .count();
+            assert_eq!(steps as f64 / 20.0, fps, "{fps} fps");
+        }
+
+        // A late poll falls one step behind, not a whole second's worth.
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut clock = StepClock::new(time_step(7.5).unwrap(), start);
+        assert!(clock.tick(at(1000)));
+        assert!(clock.tick(at(1100)));
+        assert!(!clock.tick(at(1120)));
+        assert!(clock.tick(at(1134)));
+        assert!((clock.progress(at(1200)) - 0.5).abs() < 0.01);
+    }
+}
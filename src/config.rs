@@ -0,0 +1,454 @@
+//! The optional TOML config file.
+//!
+//! ```toml
+//! [keybinds]
+//! toggle-orbit = "F2"
+//! quit = "Q"
+//!
+//! [params]
+//! sensing = false
+//! diffusion_rate = [1.0, 0.5, 0.5]
+//!
+//! [params.sensors]
+//! distance = 12.0
+//!
+//! [render]
+//! palette = ["#000000", "#ffffff", "#e04040"]
+//! contours = [50, 100, 150, 200]
+//! gamma = 0.5
+//!
+//! [spawn]
+//! colors = ["#203080", "#40c0c0", "#f0e060"]
+//! species = { "#e04040" = 70, "#4040e0" = 30 }
+//! ```
+//!
+//! Key names are winit's `VirtualKeyCode` names. `[params]` holds any of
+//! `SimParams`' fields, as written by `save_params`; missing ones keep their
+//! defaults. A `palette` of `#rrggbb` colors starts the app drawing in just
+//! those colors, and `contours` sets the intensities, from 0 to 255, that
+//! contour lines are drawn at. `gamma` raises drawn brightness to that
+//! power, or `log_base` compresses it logarithmically in that base, to
+//! bring out faint trails; give at most one. Spawn `colors` are `"random"`,
+//! `"fixed"` (the color picked with the cycle-color key) or a list of
+//! `#rrggbb` stops that each new agent's color is drawn from a gradient
+//! through. `species` maps species colors to their shares of new agents,
+//! and takes over from `colors` when given.
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::{Map, Number, Value};
+use toml_edit::{Array, Document, Item, Table};
+use z_slime::params::SimParams;
+use z_slime::species::SpeciesMix;
+use z_slime::world::{ColorStrategy, ToneCurve};
+
+use crate::input::InputMap;
+
+/// Everything a config file can set.
+pub struct Config {
+    pub input_map: InputMap,
+    /// Starting parameters, if the file has a `[params]` table.
+    pub params: Option<SimParams>,
+    /// Colors to quantize drawing to, from `render.palette`.
+    pub palette: Option<Palette>,
+    /// Intensities to draw contour lines at, from `render.contours`.
+    pub contour_levels: Option<Vec<f32>>,
+    /// How drawn brightness is curved, from `render.gamma` or
+    /// `render.log_base`.
+    pub tone_curve: Option<ToneCurve>,
+    /// How new agents get their colors, from `spawn.colors`.
+    pub color_strategy: Option<ColorStrategy>,
+    /// Species shares of new agents, from `spawn.species`.
+    pub species: Option<SpeciesMix>,
+}
+
+pub type Palette = Vec<(u8, u8, u8)>;
+
+/// Read the config at `path`.
+pub fn load(path: &Path) -> Result<Config, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    parse(&text).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+/// Write `params` to `path` as a config file `load` reads back.
+pub fn save_params(params: &SimParams, path: &Path) -> Result<(), String> {
+    let value = serde_json::to_value(params).map_err(|err| err.to_string())?;
+    let Item::Table(table) = to_toml(&value) else {
+        return Err("parameters didn't serialize to a table".into());
+    };
+    let mut document = Document::new();
+    document.insert("params", Item::Table(table));
+    fs::write(path, document.to_string()).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+fn parse(text: &str) -> Result<Config, String> {
+    let document: Document = text.parse().map_err(|err| format!("{err}"))?;
+    let params = match document.get("params") {
+        Some(params) => Some(
+            serde_json::from_value(from_toml(params)).map_err(|err| format!("params: {err}"))?,
+        ),
+        None => None,
+    };
+    Ok(Config {
+        input_map: parse_input_map(&document)?,
+        params,
+        palette: parse_palette(&document)?,
+        contour_levels: parse_contour_levels(&document)?,
+        tone_curve: parse_tone_curve(&document)?,
+        color_strategy: parse_color_strategy(&document)?,
+        species: parse_species(&document)?,
+    })
+}
+
+fn parse_species(document: &Document) -> Result<Option<SpeciesMix>, String> {
+    let Some(species) = document.get("spawn").and_then(|spawn| spawn.get("species")) else {
+        return Ok(None);
+    };
+    let table = species
+        .as_table_like()
+        .ok_or("spawn.species must map \"#rrggbb\" colors to shares")?;
+    let species = table
+        .iter()
+        .map(|(color, share)| {
+            let rgb = parse_hex_color(color)
+                .ok_or_else(|| format!("spawn.species: bad color {color:?}"))?;
+            let share = share
+                .as_float()
+                .or_else(|| share.as_integer().map(|share| share as f64))
+                .ok_or_else(|| format!("spawn.species: bad share for {color}"))?;
+            Ok((rgb, share))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    SpeciesMix::new(species)
+        .map(Some)
+        .map_err(|err| format!("spawn.species: {err}"))
+}
+
+fn parse_color_strategy(document: &Document) -> Result<Option<ColorStrategy>, String> {
+    let Some(colors) = document.get("spawn").and_then(|spawn| spawn.get("colors")) else {
+        return Ok(None);
+    };
+    let strategy = match colors.as_str() {
+        Some("random") => ColorStrategy::Random,
+        Some("fixed") => ColorStrategy::Fixed,
+        _ => {
+            let stops = colors.as_array().ok_or(
+                "spawn.colors must be \"random\", \"fixed\" or an array of \"#rrggbb\" strings",
+            )?;
+            let stops = stops
+                .iter()
+                .map(|color| {
+                    color
+                        .as_str()
+                        .and_then(parse_hex_color)
+                        .ok_or_else(|| format!("spawn.colors: bad color {color}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if stops.is_empty() {
+                return Err("spawn.colors is empty".into());
+            }
+            ColorStrategy::PaletteGradient(stops)
+        }
+    };
+    Ok(Some(strategy))
+}
+
+fn parse_contour_levels(document: &Document) -> Result<Option<Vec<f32>>, String> {
+    let Some(levels) = document
+        .get("render")
+        .and_then(|render| render.get("contours"))
+    else {
+        return Ok(None);
+    };
+    let levels = levels
+        .as_array()
+        .ok_or("render.contours must be an array of numbers")?;
+    levels
+        .iter()
+        .map(|level| {
+            level
+                .as_float()
+                .or_else(|| level.as_integer().map(|level| level as f64))
+                .map(|level| level as f32)
+                .ok_or_else(|| format!("render.contours: bad level {level}"))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+fn parse_tone_curve(document: &Document) -> Result<Option<ToneCurve>, String> {
+    let number = |key: &str| {
+        let Some(value) = document.get("render").and_then(|render| render.get(key)) else {
+            return Ok(None);
+        };
+        value
+            .as_float()
+            .or_else(|| value.as_integer().map(|value| value as f64))
+            .map(|value| Some(value as f32))
+            .ok_or_else(|| format!("render.{key} must be a number"))
+    };
+    match (number("gamma")?, number("log_base")?) {
+        (Some(_), Some(_)) => Err("give render.gamma or render.log_base, not both".into()),
+        (Some(gamma), None) if gamma > 0.0 => Ok(Some(ToneCurve::Gamma(gamma))),
+        (Some(gamma), None) => Err(format!("render.gamma must be above 0, not {gamma}")),
+        (None, Some(base)) if base > 1.0 => Ok(Some(ToneCurve::Log(base))),
+        (None, Some(base)) => Err(format!("render.log_base must be above 1, not {base}")),
+        (None, None) => Ok(None),
+    }
+}
+
+fn parse_palette(document: &Document) -> Result<Option<Palette>, String> {
+    let Some(palette) = document
+        .get("render")
+        .and_then(|render| render.get("palette"))
+    else {
+        return Ok(None);
+    };
+    let colors = palette
+        .as_array()
+        .ok_or("render.palette must be an array of \"#rrggbb\" strings")?;
+    let palette = colors
+        .iter()
+        .map(|color| {
+            color
+                .as_str()
+                .and_then(parse_hex_color)
+                .ok_or_else(|| format!("render.palette: bad color {color}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if palette.is_empty() {
+        return Err("render.palette is empty".into());
+    }
+    Ok(Some(palette))
+}
+
+fn parse_hex_color(text: &str) -> Option<(u8, u8, u8)> {
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+fn parse_input_map(document: &Document) -> Result<InputMap, String> {
+    let Some(keybinds) = document.get("keybinds") else {
+        return Ok(InputMap::default());
+    };
+    let table = keybinds.as_table_like().ok_or("keybinds must be a table")?;
+    let mut overrides = Vec::new();
+    for (action, key) in table.iter() {
+        let key = key
+            .as_str()
+            .ok_or_else(|| format!("keybinds.{action} must be a key name string"))?;
+        overrides.push((action, key));
+    }
+    InputMap::with_overrides(overrides)
+}
+
+/// Convert serde's view of a value into TOML. Objects become tables, or
+/// inline tables inside arrays; nulls (unset options) are left out.
+fn to_toml(value: &Value) -> Item {
+    match value {
+        Value::Object(map) => {
+            let mut table = Table::new();
+            for (key, value) in map {
+                if !value.is_null() {
+                    table.insert(key, to_toml(value));
+                }
+            }
+            Item::Table(table)
+        }
+        other => to_toml_value(other).map_or(Item::None, Item::Value),
+    }
+}
+
+fn to_toml_value(value: &Value) -> Option<toml_edit::Value> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => shortest_float(n.as_f64()?).into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(values) => {
+            let mut array = Array::new();
+            for value in values.iter().filter_map(to_toml_value) {
+                array.push(value);
+            }
+            array.into()
+        }
+        Value::Object(map) => {
+            let mut table = toml_edit::InlineTable::new();
+            for (key, value) in map {
+                if let Some(value) = to_toml_value(value) {
+                    table.insert(key, value);
+                }
+            }
+            table.into()
+        }
+    })
+}
+
+/// `x`, or its shortest `f32` spelling when it came from one, so `0.4f32`
+/// is written as `0.4` rather than `0.4000000059604645`.
+fn shortest_float(x: f64) -> f64 {
+    let single = x as f32;
+    if single as f64 == x {
+        single.to_string().parse().unwrap_or(x)
+    } else {
+        x
+    }
+}
+
+fn from_toml(item: &Item) -> Value {
+    match item {
+        Item::None => Value::Null,
+        Item::Value(value) => from_toml_value(value),
+        Item::Table(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, item)| (key.to_string(), from_toml(item)))
+                .collect(),
+        ),
+        Item::ArrayOfTables(tables) => Value::Array(
+            tables
+                .iter()
+                .map(|table| from_toml(&Item::Table(table.clone())))
+                .collect(),
+        ),
+    }
+}
+
+fn from_toml_value(value: &toml_edit::Value) -> Value {
+    match value {
+        toml_edit::Value::String(s) => Value::String(s.value().clone()),
+        toml_edit::Value::Integer(i) => Value::Number((*i.value()).into()),
+        toml_edit::Value::Float(f) => {
+            Number::from_f64(*f.value()).map_or(Value::Null, Value::Number)
+        }
+        toml_edit::Value::Boolean(b) => Value::Bool(*b.value()),
+        toml_edit::Value::Datetime(d) => Value::String(d.value().to_string()),
+        toml_edit::Value::Array(array) => Value::Array(array.iter().map(from_toml_value).collect()),
+        toml_edit::Value::InlineTable(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_string(), from_toml_value(value)))
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use z_slime::agent::TurnMode;
+    use z_slime::params::Preset;
+    use z_slime::world::GridUpdate;
+
+    #[test]
+    fn keybinds_must_be_key_name_strings() {
+        assert!(parse("[keybinds]\ntoggle-orbit = \"F2\"\n").is_ok());
+        assert!(parse("").is_ok());
+        assert!(parse("[keybinds]\nquit = 3\n").is_err());
+        assert!(parse("keybinds = [").is_err());
+    }
+
+    #[test]
+    fn palettes_are_lists_of_hex_colors() {
+        let config = parse("[render]\npalette = [\"#000000\", \"#FF8010\"]\n").unwrap();
+        assert_eq!(config.palette, Some(vec![(0, 0, 0), (255, 128, 16)]));
+        assert_eq!(parse("").unwrap().palette, None);
+        assert!(parse("[render]\npalette = [\"red\"]\n").is_err());
+
+        let config = parse("[render]\ncontours = [64, 127.5]\n").unwrap();
+        assert_eq!(config.contour_levels, Some(vec![64.0, 127.5]));
+        assert!(parse("[render]\ncontours = [\"high\"]\n").is_err());
+    }
+
+    #[test]
+    fn spawn_colors_pick_a_strategy() {
+        let strategy = |text: &str| parse(text).map(|config| config.color_strategy);
+        assert_eq!(
+            strategy("[spawn]\ncolors = \"fixed\"\n"),
+            Ok(Some(ColorStrategy::Fixed))
+        );
+        assert_eq!(
+            strategy("[spawn]\ncolors = [\"#000000\", \"#ff8010\"]\n"),
+            Ok(Some(ColorStrategy::PaletteGradient(vec![
+                (0, 0, 0),
+                (255, 128, 16)
+            ])))
+        );
+        assert_eq!(strategy(""), Ok(None));
+        assert!(strategy("[spawn]\ncolors = \"rainbow\"\n").is_err());
+        assert!(strategy("[spawn]\ncolors = []\n").is_err());
+        assert!(parse("[render]\npalette = []\n").is_err());
+    }
+
+    #[test]
+    fn render_gamma_or_log_base_curves_brightness() {
+        let curve = |text: &str| parse(text).map(|config| config.tone_curve);
+        assert_eq!(
+            curve("[render]\ngamma = 0.5\n"),
+            Ok(Some(ToneCurve::Gamma(0.5)))
+        );
+        assert_eq!(
+            curve("[render]\nlog_base = 100\n"),
+            Ok(Some(ToneCurve::Log(100.0)))
+        );
+        assert_eq!(curve(""), Ok(None));
+        assert!(curve("[render]\ngamma = 0\n").is_err());
+        assert!(curve("[render]\nlog_base = 1\n").is_err());
+        assert!(curve("[render]\ngamma = 0.5\nlog_base = 10\n").is_err());
+    }
+
+    #[test]
+    fn spawn_species_map_colors_to_shares() {
+        let species = |text: &str| parse(text).map(|config| config.species);
+        let mix = species("[spawn]\nspecies = { \"#ff0000\" = 70, \"#0000ff\" = 30.0 }\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(mix.color(1), Some((0, 0, 255)));
+        assert_eq!(mix.split(10), [7, 3]);
+        assert!(species("[spawn]\nspecies = { \"red\" = 1 }\n").is_err());
+        assert!(species("[spawn]\nspecies = { \"#ff0000\" = 0 }\n").is_err());
+        assert!(species("[spawn]\nspecies = {}\n").is_err());
+    }
+
+    #[test]
+    fn saved_params_load_back_unchanged() {
+        let mut params = Preset::Cobweb.params();
+        params.mass_target = Some(1234.5);
+        params.grid_update = GridUpdate::Sparse {
+            max_active_fraction: 0.25,
+        };
+        params.sensors.turn = TurnMode::Gradual { rate: 0.1 };
+        let path = std::env::temp_dir().join(format!("z_slime_params_{}.toml", std::process::id()));
+
+        save_params(&params, &path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let loaded = load(&path).unwrap().params;
+        fs::remove_file(&path).unwrap();
+
+        assert!(text.contains("angle = 0.2\n"), "{text}");
+        assert_eq!(loaded, Some(params));
+    }
+
+    #[test]
+    fn missing_params_keep_their_defaults() {
+        let params = parse("[params]\nsensing = false\n")
+            .unwrap()
+            .params
+            .unwrap();
+        assert_eq!(
+            params,
+            SimParams {
+                sensing: false,
+                ..SimParams::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Built-in scenarios that set a world up to show off one behaviour.
+//!
+//! `Demo::Maze` walls off a maze, puts a food source at each end and tunes
+//! sensing agents to follow the food's scent, so the trails that survive
+//! evaporation are the ones joining the two.
+
+use std::fmt;
+use std::str::FromStr;
+
+use log::info;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::agent::{SensorConfig, TurnMode};
+use crate::grid::Grid;
+use crate::params::SimParams;
+use crate::world::World;
+
+/// Width in cells of a generated maze's corridors.
+const CORRIDOR: usize = 12;
+/// Thickness in cells of a generated maze's walls.
+const WALL: usize = 3;
+/// Chance that a wall between two neighbouring corridors is knocked
+/// through after the maze is carved, giving it loops and so more than one
+/// way between the ends.
+const LOOP_CHANCE: f64 = 0.08;
+/// Color the food sources are drawn in and feed the field with.
+const FOOD_COLOR: (u8, u8, u8) = (250, 170, 20);
+/// Agents a demo starts with when `--agents` isn't given.
+pub const DEMO_AGENTS: usize = 4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Demo {
+    /// Agents finding the way between two food sources through a maze.
+    Maze,
+}
+
+impl Demo {
+    pub const ALL: [Demo; 1] = [Demo::Maze];
+
+    fn name(self) -> &'static str {
+        match self {
+            Demo::Maze => "maze",
+        }
+    }
+
+    /// Set `world` up for this demo, replacing its parameters. A maze uses
+    /// the world's walls if it has any and generates one from its seed
+    /// otherwise.
+    pub fn set_up(self, world: &mut World) -> Result<(), String> {
+        match self {
+            Demo::Maze => {
+                let params = maze_params();
+                world.defaults = params.clone();
+                world.params = params;
+                if world.walls().is_none() {
+                    let walls = maze_walls(world.width(), world.height(), world.seed());
+                    world.set_walls(Some(walls))?;
+                }
+                let walls = Grid::from_vec(
+                    world.width(),
+                    world.height(),
+                    world.walls().unwrap_or_default().to_vec(),
+                );
+                let (width, height) = (world.width(), world.height());
+                let start = nearest_open(&walls, (0, 0)).ok_or("the maze has no open cells")?;
+                let end = nearest_open(&walls, (width, height)).unwrap_or(start);
+                world.add_source(start.0, start.1, FOOD_COLOR);
+                world.add_source(end.0, end.1, FOOD_COLOR);
+                info!("Maze demo: food at {start:?} and {end:?}");
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Demo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Demo {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        Demo::ALL
+            .into_iter()
+            .find(|demo| demo.name() == name)
+            .ok_or_else(|| format!("unknown demo {name:?}"))
+    }
+}
+
+/// Short-sighted sensing agents that fit the corridors, on a field that
+/// fades unless it is fed, so only trails kept up by the food stay.
+pub fn maze_params() -> SimParams {
+    let defaults = SimParams::default();
+    SimParams {
+        sensing: true,
+        sensors: SensorConfig {
+            distance: 5.0,
+            angle: 0.6,
+            turn: TurnMode::Gradual { rate: 0.3 },
+            ..defaults.sensors
+        },
+        diffusion_rate: [0.6; 3],
+        evaporation: 0.97,
+        ..defaults
+    }
+}
+
+/// A `width` x `height` wall map of a maze carved by a randomized
+/// depth-first search from `seed`, with a few loops knocked through. Every
+/// open cell is reachable from every other.
+pub fn maze_walls(width: usize, height: usize, seed: u64) -> Vec<bool> {
+    let pitch = CORRIDOR + WALL;
+    let columns = width.saturating_sub(WALL) / pitch;
+    let rows = height.saturating_sub(WALL) / pitch;
+    let mut walls = Grid::new(width, height, true);
+    if columns == 0 || rows == 0 {
+        return walls.into_vec();
+    }
+
+    // Open the corridor cells from `from` to `to` in maze coordinates,
+    // either one cell or two neighbours and the wall between them.
+    let mut carve = |from: (usize, usize), to: (usize, usize)| {
+        let left = WALL + from.0.min(to.0) * pitch;
+        let top = WALL + from.1.min(to.1) * pitch;
+        let span_x = CORRIDOR + from.0.abs_diff(to.0) * pitch;
+        let span_y = CORRIDOR + from.1.abs_diff(to.1) * pitch;
+        for y in top..top + span_y {
+            for x in left..left + span_x {
+                let idx = walls.index(x, y);
+                walls[idx] = false;
+            }
+        }
+    };
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut visited = Grid::new(columns, rows, false);
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+    carve((0, 0), (0, 0));
+    while let Some(&(x, y)) = stack.last() {
+        let unvisited: Vec<_> = (0..4)
+            .filter_map(|direction| {
+                let (dx, dy) = [(1, 0), (-1, 0), (0, 1), (0, -1)][direction];
+                let next = visited.offset(x, y, dx, dy)?;
+                (!visited[visited.index(next.0, next.1)]).then_some(next)
+            })
+            .collect();
+        let Some(&next) = unvisited.choose(&mut rng) else {
+            stack.pop();
+            continue;
+        };
+        let idx = visited.index(next.0, next.1);
+        visited[idx] = true;
+        carve((x, y), next);
+        stack.push(next);
+    }
+
+    for y in 0..rows {
+        for x in 0..columns {
+            if x + 1 < columns && rng.gen_bool(LOOP_CHANCE) {
+                carve((x, y), (x + 1, y));
+            }
+            if y + 1 < rows && rng.gen_bool(LOOP_CHANCE) {
+                carve((x, y), (x, y + 1));
+            }
+        }
+    }
+    walls.into_vec()
+}
+
+/// The open cell closest to `corner`, if there is one.
+fn nearest_open(walls: &Grid<bool>, corner: (usize, usize)) -> Option<(usize, usize)> {
+    (0..walls.len())
+        .filter(|&idx| !walls[idx])
+        .map(|idx| walls.coords(idx))
+        .min_by_key(|&(x, y)| x.abs_diff(corner.0).pow(2) + y.abs_diff(corner.1).pow(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_mazes_join_their_ends() {
+        let (width, height) = (120, 90);
+        let walls = Grid::from_vec(width, height, maze_walls(width, height, 7));
+        assert_eq!(
+            walls,
+            Grid::from_vec(width, height, maze_walls(width, height, 7))
+        );
+        assert!(walls.iter().filter(|&&wall| wall).count() > walls.len() / 4);
+
+        // Every open cell is reachable from the food at the top left.
+        let start = nearest_open(&walls, (0, 0)).unwrap();
+        let mut reached = Grid::new(width, height, false);
+        let mut queue = vec![start];
+        reached[walls.index(start.0, start.1)] = true;
+        while let Some((x, y)) = queue.pop() {
+            for dir in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let Some(next) = walls.offset(x, y, dir.0, dir.1) else {
+                    continue;
+                };
+                let idx = walls.index(next.0, next.1);
+                if !walls[idx] && !reached[idx] {
+                    reached[idx] = true;
+                    queue.push(next);
+                }
+            }
+        }
+        assert!((0..walls.len()).all(|idx| walls[idx] || reached[idx]));
+        let end = nearest_open(&walls, (width, height)).unwrap();
+        assert!(end.0 > width / 2 && end.1 > height / 2);
+    }
+
+    #[test]
+    fn the_maze_demo_feeds_both_ends() {
+        let mut world = World::with_size(80, 80, 3);
+        Demo::Maze.set_up(&mut world).unwrap();
+        assert!(world.params.sensing);
+        assert_eq!(world.source_cells().len(), 2);
+        assert!(world.walls().is_some_and(|walls| walls.contains(&true)));
+        assert_eq!("maze".parse(), Ok(Demo::Maze));
+        assert!("labyrinth".parse::<Demo>().is_err());
+    }
+
+    #[test]
+    fn maze_agents_join_the_food_sources() {
+        let run = |agents| {
+            let mut world = World::with_size(63, 63, 3);
+            Demo::Maze.set_up(&mut world).unwrap();
+            world.spawn_random(agents);
+            world.advance(200);
+            world.network_metrics(&world.source_cells(), 32.0)
+        };
+        // The food's scent alone fades long before it crosses the maze.
+        assert_eq!(run(0).connectivity, 0.0);
+        assert_eq!(run(1500).connectivity, 1.0);
+    }
+}
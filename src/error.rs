@@ -0,0 +1,34 @@
+//! Errors the windowed app can't recover from.
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ZSlimeError {
+    /// No GPU adapter could drive the window, not even wgpu's software
+    /// fallback. Holds the last error.
+    GpuInit(pixels::Error),
+}
+
+impl fmt::Display for ZSlimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZSlimeError::GpuInit(err) => write!(
+                f,
+                "couldn't set up rendering ({err}). No usable GPU adapter was found, \
+                 including wgpu's software fallback. On a machine without a GPU, install \
+                 a software driver such as Mesa's llvmpipe and try WGPU_BACKEND=gl, or run \
+                 the simulation headless through the z_slime library \
+                 (World::update and World::render_to_buffer)"
+            ),
+        }
+    }
+}
+
+impl Error for ZSlimeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ZSlimeError::GpuInit(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Handing finished frames from a simulation thread to a render thread.
+//!
+//! The pair shares one slot between two buffers of their own. The writer
+//! draws into its back buffer and publishes it by swapping it into the
+//! slot; the reader swaps a freshly published frame out of the slot into
+//! its front buffer. Only whole buffers change hands, under the slot's
+//! lock, so the reader never sees a frame the writer is still drawing, and
+//! neither side waits on the other for longer than a swap.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+struct Slot {
+    frame: Vec<u8>,
+    /// Whether `frame` was published since the reader last took one.
+    fresh: bool,
+}
+
+type Shared = Arc<Mutex<Slot>>;
+
+/// A writer and reader of frames `len` bytes long, starting out zeroed.
+pub fn frame_exchange(len: usize) -> (FrameWriter, FrameReader) {
+    let slot = Arc::new(Mutex::new(Slot {
+        frame: vec![0; len],
+        fresh: false,
+    }));
+    let writer = FrameWriter {
+        back: vec![0; len],
+        slot: slot.clone(),
+    };
+    let reader = FrameReader {
+        front: vec![0; len],
+        slot,
+    };
+    (writer, reader)
+}
+
+/// A panic on the other side mid-swap can't leave a buffer half written,
+/// so a poisoned slot is still good to use.
+fn lock(slot: &Shared) -> MutexGuard<'_, Slot> {
+    slot.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The simulation side of a `frame_exchange`.
+pub struct FrameWriter {
+    back: Vec<u8>,
+    slot: Shared,
+}
+
+impl FrameWriter {
+    /// The buffer the next frame is drawn into. After a `publish` it holds
+    /// some older frame, so it has to be drawn over in full.
+    pub fn back_mut(&mut self) -> &mut [u8] {
+        &mut self.back
+    }
+
+    /// Make the frames drawn from now on `len` bytes long, as when the
+    /// window changes size. Frames published before keep their length.
+    pub fn resize(&mut self, len: usize) {
+        self.back.resize(len, 0);
+    }
+
+    /// Hand the back buffer over as the latest frame. A frame published
+    /// before the reader took it is dropped.
+    pub fn publish(&mut self) {
+        let mut slot = lock(&self.slot);
+        std::mem::swap(&mut slot.frame, &mut self.back);
+        slot.fresh = true;
+    }
+}
+
+/// The render side of a `frame_exchange`.
+pub struct FrameReader {
+    front: Vec<u8>,
+    slot: Shared,
+}
+
+impl FrameReader {
+    /// The frame published since the last call, if there is one.
+    pub fn latest(&mut self) -> Option<&[u8]> {
+        let mut slot = lock(&self.slot);
+        if !slot.fresh {
+            return None;
+        }
+        std::mem::swap(&mut slot.frame, &mut self.front);
+        slot.fresh = false;
+        Some(&self.front)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readers_get_each_published_frame_once() {
+        let (mut writer, mut reader) = frame_exchange(4);
+        assert_eq!(reader.latest(), None);
+
+        writer.back_mut().fill(1);
+        writer.publish();
+        writer.back_mut().fill(2);
+        assert_eq!(reader.latest(), Some(&[1; 4][..]));
+        assert_eq!(reader.latest(), None);
+
+        // Only the newest of two unread frames comes through.
+        writer.publish();
+        writer.back_mut().fill(3);
+        writer.publish();
+        assert_eq!(reader.latest(), Some(&[3; 4][..]));
+    }
+
+    #[test]
+    fn resized_writers_publish_frames_of_the_new_length() {
+        let (mut writer, mut reader) = frame_exchange(4);
+        writer.resize(8);
+        writer.back_mut().fill(5);
+        writer.publish();
+        assert_eq!(reader.latest(), Some(&[5; 8][..]));
+    }
+
+    #[test]
+    fn frames_cross_threads_whole() {
+        let (mut writer, mut reader) = frame_exchange(1024);
+        let sim = std::thread::spawn(move || {
+            for value in 1..=200 {
+                writer.back_mut().fill(value);
+                writer.publish();
+            }
+        });
+        let mut last = 0;
+        while !sim.is_finished() || last != 200 {
+            if let Some(frame) = reader.latest() {
+                assert!(frame.iter().all(|&byte| byte == frame[0]), "torn frame");
+                assert!(frame[0] > last);
+                last = frame[0];
+            }
+        }
+        sim.join().unwrap();
+    }
+}
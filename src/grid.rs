@@ -0,0 +1,255 @@
+//! A row-major 2D grid, and the index and neighbourhood math every
+//! per-cell pass needs.
+
+use std::ops::{Deref, DerefMut};
+
+/// Offsets to a cell's eight neighbours, in row-major order.
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// `width` x `height` values stored row by row. Dereferences to the flat
+/// slice for whole-grid passes and plain index lookups.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    data: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    /// A `width` x `height` grid with every cell set to `fill`.
+    pub fn new(width: usize, height: usize, fill: T) -> Self {
+        let len = width.checked_mul(height).expect("overflow");
+        Self {
+            width,
+            height,
+            data: vec![fill; len],
+        }
+    }
+
+    /// Cut or pad the grid with `fill` to `width` x `height`, keeping each
+    /// remaining cell at the same coordinates.
+    pub fn resize(&mut self, width: usize, height: usize, fill: T) {
+        self.data = crop(&self.data, (self.width, self.height), (width, height), fill);
+        self.width = width;
+        self.height = height;
+    }
+}
+
+impl<T> Grid<T> {
+    /// A grid over `data`, which must hold exactly `width * height` values.
+    pub fn from_vec(width: usize, height: usize, data: Vec<T>) -> Self {
+        assert_eq!(
+            Some(data.len()),
+            width.checked_mul(height),
+            "a {width}x{height} grid needs {width} * {height} values"
+        );
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Whether `(x, y)` is a cell of the grid.
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// The flat index of cell `(x, y)`. Panics outside the grid, rather than
+    /// quietly landing on some other row.
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        assert!(
+            self.contains(x, y),
+            "({x}, {y}) is outside the {}x{} grid",
+            self.width,
+            self.height
+        );
+        x + y * self.width
+    }
+
+    /// The coordinates of flat index `idx`.
+    pub fn coords(&self, idx: usize) -> (usize, usize) {
+        (idx % self.width, idx / self.width)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        self.contains(x, y).then(|| &self.data[x + y * self.width])
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        match self.contains(x, y) {
+            true => Some(&mut self.data[x + y * self.width]),
+            false => None,
+        }
+    }
+
+    /// The cell containing the point `(x, y)`, if it is on the grid.
+    pub fn at_point(&self, x: f32, y: f32) -> Option<&T> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        self.get(x as usize, y as usize)
+    }
+
+    /// `(x, y)` moved by `(dx, dy)`, if that stays on the grid.
+    pub fn offset(&self, x: usize, y: usize, dx: isize, dy: isize) -> Option<(usize, usize)> {
+        let (x, y) = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+        self.contains(x, y).then_some((x, y))
+    }
+
+    /// `(x, y)` moved by `(dx, dy)`, wrapping around the edges as on a
+    /// torus.
+    pub fn offset_wrapped(&self, x: usize, y: usize, dx: isize, dy: isize) -> (usize, usize) {
+        let wrap =
+            |v: usize, d: isize, len: usize| (v as isize + d).rem_euclid(len as isize) as usize;
+        (wrap(x, dx, self.width), wrap(y, dy, self.height))
+    }
+
+    /// The neighbours of `(x, y)` on the grid, of the eight around it, with
+    /// their offsets from it.
+    pub fn neighbours(
+        &self,
+        x: usize,
+        y: usize,
+    ) -> impl Iterator<Item = ((usize, usize), (isize, isize))> + '_ {
+        NEIGHBOURS
+            .into_iter()
+            .filter_map(move |(dx, dy)| Some((self.offset(x, y, dx, dy)?, (dx, dy))))
+    }
+
+    /// All eight neighbours of `(x, y)`, wrapping around the edges, with
+    /// their offsets from it. On grids narrower or shorter than three cells
+    /// some of them are the same cell.
+    pub fn neighbours_wrapped(
+        &self,
+        x: usize,
+        y: usize,
+    ) -> impl Iterator<Item = ((usize, usize), (isize, isize))> + '_ {
+        NEIGHBOURS
+            .into_iter()
+            .map(move |(dx, dy)| (self.offset_wrapped(x, y, dx, dy), (dx, dy)))
+    }
+
+    /// The cells of the 3x3 window centered on `(x, y)` that are on the
+    /// grid, the center included, in row-major order.
+    pub fn window(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (-1..=1).flat_map(move |dy| (-1..=1).filter_map(move |dx| self.offset(x, y, dx, dy)))
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+}
+
+impl<T> Deref for Grid<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.data
+    }
+}
+
+impl<T> DerefMut for Grid<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+}
+
+/// The `old` sized row-major `values` cut or padded with `fill` to `new`,
+/// keeping each remaining cell at the same coordinates.
+pub fn crop<T: Clone>(values: &[T], old: (usize, usize), new: (usize, usize), fill: T) -> Vec<T> {
+    let mut out = vec![fill; new.0 * new.1];
+    let keep = old.0.min(new.0);
+    for y in 0..old.1.min(new.1) {
+        out[y * new.0..y * new.0 + keep].clone_from_slice(&values[y * old.0..y * old.0 + keep]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbours_stop_at_the_edges() {
+        let grid = Grid::new(4, 3, 0);
+        let cells = |x, y| {
+            grid.neighbours(x, y)
+                .map(|(cell, _)| cell)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(cells(1, 1).len(), 8);
+        assert_eq!(cells(0, 0), [(1, 0), (0, 1), (1, 1)]);
+        assert_eq!(cells(3, 2), [(2, 1), (3, 1), (2, 2)]);
+        let offsets: Vec<_> = grid.neighbours(3, 1).map(|(_, offset)| offset).collect();
+        assert_eq!(offsets, [(-1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)]);
+
+        assert_eq!(grid.window(0, 0).count(), 4);
+        assert_eq!(grid.window(2, 1).count(), 9);
+        assert_eq!(grid.window(2, 1).nth(4), Some((2, 1)));
+    }
+
+    #[test]
+    fn wrapped_neighbours_cross_to_the_far_side() {
+        let grid = Grid::new(4, 3, 0);
+        let cells: Vec<_> = grid
+            .neighbours_wrapped(0, 0)
+            .map(|(cell, _)| cell)
+            .collect();
+        assert_eq!(
+            cells,
+            [
+                (3, 2),
+                (0, 2),
+                (1, 2),
+                (3, 0),
+                (1, 0),
+                (3, 1),
+                (0, 1),
+                (1, 1)
+            ]
+        );
+        assert_eq!(grid.offset_wrapped(3, 2, 1, 1), (0, 0));
+        assert_eq!(grid.offset_wrapped(1, 1, -5, 7), (0, 2));
+    }
+
+    #[test]
+    fn lookups_stay_inside_the_grid() {
+        let mut grid = Grid::from_vec(3, 2, (0..6).collect());
+        assert_eq!(grid.index(2, 1), 5);
+        assert_eq!(grid.coords(5), (2, 1));
+        assert_eq!(grid.get(2, 1), Some(&5));
+        assert_eq!(grid.get(3, 0), None);
+        assert_eq!(grid.at_point(1.9, 0.5), Some(&1));
+        assert_eq!(grid.at_point(-0.5, 0.5), None);
+        *grid.get_mut(0, 1).unwrap() = 9;
+        assert_eq!(grid[3], 9);
+
+        grid.resize(2, 3, -1);
+        assert_eq!(grid.into_vec(), [0, 1, 9, 4, -1, -1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside")]
+    fn indexing_past_the_edge_panics() {
+        Grid::new(3, 2, 0).index(3, 0);
+    }
+}
@@ -0,0 +1,117 @@
+//! PNG image files, read and written with the `png` crate rather than the
+//! heavier `image` crate, as the app only ever reads and writes PNGs.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+
+/// A grayscale image with values from `0.0` (black) to `1.0` (white),
+/// row-major.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub values: Vec<f32>,
+}
+
+/// Load any PNG as grayscale, averaging the color channels and ignoring alpha.
+pub fn load_grayscale(path: impl AsRef<Path>) -> io::Result<GrayImage> {
+    let invalid = |err: png::DecodingError| io::Error::new(io::ErrorKind::InvalidData, err);
+
+    let mut decoder = Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(invalid)?;
+
+    let (samples, colors) = match info.color_type {
+        ColorType::Grayscale => (1, 1),
+        ColorType::GrayscaleAlpha => (2, 1),
+        ColorType::Rgb | ColorType::Indexed => (3, 3),
+        ColorType::Rgba => (4, 3),
+    };
+    let (width, height) = (info.width as usize, info.height as usize);
+    let mut values = Vec::with_capacity(width * height);
+    for row in buf[..info.buffer_size()].chunks_exact(info.line_size) {
+        for pixel in row[..width * samples].chunks_exact(samples) {
+            let sum: u32 = pixel[..colors].iter().map(|&c| c as u32).sum();
+            values.push(sum as f32 / (colors as f32 * 255.0));
+        }
+    }
+    Ok(GrayImage {
+        width,
+        height,
+        values,
+    })
+}
+
+/// Write `rgba`, `width` x `height` row-major RGBA pixels, as a PNG.
+pub fn save_rgba(path: impl AsRef<Path>, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    let mut encoder = Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// The `width` x `height` pixels at `(x, y)` of `rgba`, an RGBA image
+/// `image_width` pixels wide, row by row. The region must lie inside it.
+pub fn crop_rgba(
+    rgba: &[u8],
+    image_width: usize,
+    (x, y): (usize, usize),
+    (width, height): (usize, usize),
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(width * height * 4);
+    for row in y..y + height {
+        let start = (x + row * image_width) * 4;
+        out.extend_from_slice(&rgba[start..start + width * 4]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_pngs_load_as_channel_means() {
+        let path = std::env::temp_dir().join(format!("z_slime_gray_{}.png", std::process::id()));
+        let mut encoder = png::Encoder::new(File::create(&path).unwrap(), 2, 1);
+        encoder.set_color(ColorType::Rgb);
+        let mut writer = encoder.write_header().unwrap();
+        writer
+            .write_image_data(&[255, 255, 255, 255, 0, 0])
+            .unwrap();
+        writer.finish().unwrap();
+
+        let image = load_grayscale(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.values, [1.0, 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn saved_rgba_loads_back() {
+        let path = std::env::temp_dir().join(format!("z_slime_rgba_{}.png", std::process::id()));
+        save_rgba(&path, 1, 2, &[0, 0, 0, 255, 255, 255, 255, 255]).unwrap();
+        let image = load_grayscale(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((image.width, image.height), (1, 2));
+        assert_eq!(image.values, [0.0, 1.0]);
+    }
+
+    #[test]
+    fn crops_keep_the_region_rows() {
+        let rgba: Vec<u8> = (0..3 * 2 * 4).collect();
+        assert_eq!(
+            crop_rgba(&rgba, 3, (1, 0), (2, 2)),
+            [4, 5, 6, 7, 8, 9, 10, 11, 16, 17, 18, 19, 20, 21, 22, 23]
+        );
+        assert_eq!(crop_rgba(&rgba, 3, (0, 0), (3, 2)), rgba);
+    }
+}
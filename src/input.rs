@@ -0,0 +1,406 @@
+//! Keyboard and mouse handling: turns `WinitInputHelper` state into
+//! `Action`s for `main` to carry out.
+
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+/// Something the user asked for with a key press or the mouse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    ToggleOrbit,
+    CycleColor,
+    ToggleRandomColor,
+    ToggleFade,
+    ToggleAgeColors,
+    ToggleAutoExposure,
+    ToggleDither,
+    ToggleAgentsOnly,
+    TogglePalette,
+    ToggleContours,
+    /// Show or hide the list of current parameter values.
+    ToggleHud,
+    /// Empty the grid and remove every agent.
+    Reset,
+    ResetParams,
+    CyclePreset,
+    ToggleShape,
+    ToggleRawView,
+    ToggleProbe,
+    ToggleFloodFill,
+    CycleMarkers,
+    /// Step between drawing trails in color, in gray and along a gradient.
+    CycleRenderMode,
+    ToggleInterpolation,
+    ToggleSensing,
+    ToggleTurnMode,
+    FlipChemotaxis,
+    GrowAgents,
+    ShrinkAgents,
+    Faster,
+    Slower,
+    LongerTrails,
+    ShorterTrails,
+    StrongerDeposits,
+    WeakerDeposits,
+    /// Spawn species `index`, counting from `0`, of the species mix.
+    SelectSpecies {
+        index: usize,
+    },
+    /// Stop or resume updating the worlds.
+    TogglePause,
+    /// Update the worlds exactly once while paused.
+    Step,
+    /// Write the current parameters to a timestamped config file.
+    SaveParams,
+    /// Write agent trajectories to a timestamped SVG file.
+    ExportSvg,
+    /// Write the window's frame to a timestamped PNG file.
+    Screenshot,
+    /// Write just the grid's part of the frame, or in tiled mode the active
+    /// tile's, to a timestamped PNG file.
+    ScreenshotGrid,
+    /// Write the grid and agents to the state file.
+    SaveState,
+    /// Go back to what the state file holds.
+    LoadState,
+    /// A click released at window position `(x, y)`: spawns an agent, or
+    /// floods the region there while the flood fill tool is on.
+    Spawn {
+        x: f32,
+        y: f32,
+    },
+    /// The left button held at window position `(x, y)`, each frame it is
+    /// down: scatters agents around it.
+    Brush {
+        x: f32,
+        y: f32,
+    },
+    /// A shift-click released at window position `(x, y)`: pins a heat
+    /// source there.
+    PlaceSource {
+        x: f32,
+        y: f32,
+    },
+    /// A ctrl-click released at window position `(x, y)`: takes the color
+    /// of the heat there for new agents.
+    PickColor {
+        x: f32,
+        y: f32,
+    },
+    /// The mouse moved to window position `(x, y)`.
+    Hover {
+        x: f32,
+        y: f32,
+    },
+}
+
+/// Every action a key can be bound to, with its name in config files.
+const BINDABLE: [(&str, Action); 50] = [
+    ("quit", Action::Quit),
+    ("toggle-orbit", Action::ToggleOrbit),
+    ("cycle-color", Action::CycleColor),
+    ("toggle-random-color", Action::ToggleRandomColor),
+    ("toggle-fade", Action::ToggleFade),
+    ("toggle-age-colors", Action::ToggleAgeColors),
+    ("toggle-auto-exposure", Action::ToggleAutoExposure),
+    ("toggle-dither", Action::ToggleDither),
+    ("toggle-agents-only", Action::ToggleAgentsOnly),
+    ("toggle-palette", Action::TogglePalette),
+    ("toggle-contours", Action::ToggleContours),
+    ("toggle-hud", Action::ToggleHud),
+    ("reset", Action::Reset),
+    ("reset-params", Action::ResetParams),
+    ("cycle-preset", Action::CyclePreset),
+    ("toggle-shape", Action::ToggleShape),
+    ("toggle-raw-view", Action::ToggleRawView),
+    ("toggle-probe", Action::ToggleProbe),
+    ("toggle-flood-fill", Action::ToggleFloodFill),
+    ("cycle-markers", Action::CycleMarkers),
+    ("cycle-render-mode", Action::CycleRenderMode),
+    ("toggle-interpolation", Action::ToggleInterpolation),
+    ("toggle-sensing", Action::ToggleSensing),
+    ("toggle-turn-mode", Action::ToggleTurnMode),
+    ("flip-chemotaxis", Action::FlipChemotaxis),
+    ("grow-agents", Action::GrowAgents),
+    ("shrink-agents", Action::ShrinkAgents),
+    ("faster", Action::Faster),
+    ("slower", Action::Slower),
+    ("longer-trails", Action::LongerTrails),
+    ("shorter-trails", Action::ShorterTrails),
+    ("stronger-deposits", Action::StrongerDeposits),
+    ("weaker-deposits", Action::WeakerDeposits),
+    ("select-species-1", Action::SelectSpecies { index: 0 }),
+    ("select-species-2", Action::SelectSpecies { index: 1 }),
+    ("select-species-3", Action::SelectSpecies { index: 2 }),
+    ("select-species-4", Action::SelectSpecies { index: 3 }),
+    ("select-species-5", Action::SelectSpecies { index: 4 }),
+    ("select-species-6", Action::SelectSpecies { index: 5 }),
+    ("select-species-7", Action::SelectSpecies { index: 6 }),
+    ("select-species-8", Action::SelectSpecies { index: 7 }),
+    ("select-species-9", Action::SelectSpecies { index: 8 }),
+    ("toggle-pause", Action::TogglePause),
+    ("step", Action::Step),
+    ("save-params", Action::SaveParams),
+    ("export-svg", Action::ExportSvg),
+    ("screenshot", Action::Screenshot),
+    ("screenshot-grid", Action::ScreenshotGrid),
+    ("save-state", Action::SaveState),
+    ("load-state", Action::LoadState),
+];
+
+/// Keys that can be named in config files, by their `VirtualKeyCode` names.
+const KEYS: &[(&str, VirtualKeyCode)] = {
+    use VirtualKeyCode as Key;
+    &[
+        ("A", Key::A),
+        ("B", Key::B),
+        ("C", Key::C),
+        ("D", Key::D),
+        ("E", Key::E),
+        ("F", Key::F),
+        ("G", Key::G),
+        ("H", Key::H),
+        ("I", Key::I),
+        ("J", Key::J),
+        ("K", Key::K),
+        ("L", Key::L),
+        ("M", Key::M),
+        ("N", Key::N),
+        ("O", Key::O),
+        ("P", Key::P),
+        ("Q", Key::Q),
+        ("R", Key::R),
+        ("S", Key::S),
+        ("T", Key::T),
+        ("U", Key::U),
+        ("V", Key::V),
+        ("W", Key::W),
+        ("X", Key::X),
+        ("Y", Key::Y),
+        ("Z", Key::Z),
+        ("Key0", Key::Key0),
+        ("Key1", Key::Key1),
+        ("Key2", Key::Key2),
+        ("Key3", Key::Key3),
+        ("Key4", Key::Key4),
+        ("Key5", Key::Key5),
+        ("Key6", Key::Key6),
+        ("Key7", Key::Key7),
+        ("Key8", Key::Key8),
+        ("Key9", Key::Key9),
+        ("F1", Key::F1),
+        ("F2", Key::F2),
+        ("F3", Key::F3),
+        ("F4", Key::F4),
+        ("F5", Key::F5),
+        ("F6", Key::F6),
+        ("F7", Key::F7),
+        ("F8", Key::F8),
+        ("F9", Key::F9),
+        ("F10", Key::F10),
+        ("F11", Key::F11),
+        ("F12", Key::F12),
+        ("Escape", Key::Escape),
+        ("Back", Key::Back),
+        ("Return", Key::Return),
+        ("Space", Key::Space),
+        ("Tab", Key::Tab),
+        ("Delete", Key::Delete),
+        ("Insert", Key::Insert),
+        ("Home", Key::Home),
+        ("End", Key::End),
+        ("PageUp", Key::PageUp),
+        ("PageDown", Key::PageDown),
+        ("Left", Key::Left),
+        ("Right", Key::Right),
+        ("Up", Key::Up),
+        ("Down", Key::Down),
+        ("Minus", Key::Minus),
+        ("Equals", Key::Equals),
+        ("Comma", Key::Comma),
+        ("Period", Key::Period),
+        ("Slash", Key::Slash),
+        ("Backslash", Key::Backslash),
+        ("Semicolon", Key::Semicolon),
+        ("Apostrophe", Key::Apostrophe),
+        ("LBracket", Key::LBracket),
+        ("RBracket", Key::RBracket),
+        ("Grave", Key::Grave),
+    ]
+};
+
+/// Which key triggers which action.
+pub struct InputMap {
+    bindings: Vec<(VirtualKeyCode, Action)>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use VirtualKeyCode as Key;
+        Self {
+            bindings: vec![
+                (Key::Escape, Action::Quit),
+                (Key::O, Action::ToggleOrbit),
+                (Key::C, Action::CycleColor),
+                (Key::X, Action::ToggleRandomColor),
+                (Key::H, Action::ToggleFade),
+                (Key::A, Action::ToggleAgeColors),
+                (Key::E, Action::ToggleAutoExposure),
+                (Key::D, Action::ToggleDither),
+                (Key::V, Action::ToggleAgentsOnly),
+                (Key::U, Action::TogglePalette),
+                (Key::B, Action::ToggleContours),
+                (Key::F1, Action::ToggleHud),
+                (Key::Back, Action::ResetParams),
+                (Key::R, Action::Reset),
+                (Key::P, Action::CyclePreset),
+                (Key::K, Action::ToggleShape),
+                (Key::G, Action::ToggleRawView),
+                (Key::I, Action::ToggleProbe),
+                (Key::F, Action::ToggleFloodFill),
+                (Key::M, Action::CycleMarkers),
+                (Key::Y, Action::CycleRenderMode),
+                (Key::L, Action::ToggleInterpolation),
+                (Key::S, Action::ToggleSensing),
+                (Key::T, Action::ToggleTurnMode),
+                (Key::N, Action::FlipChemotaxis),
+                (Key::Period, Action::GrowAgents),
+                (Key::Comma, Action::ShrinkAgents),
+                (Key::RBracket, Action::Faster),
+                (Key::LBracket, Action::Slower),
+                (Key::Equals, Action::LongerTrails),
+                (Key::Minus, Action::ShorterTrails),
+                // The number keys pick species, so deposits sit beside them.
+                (Key::Apostrophe, Action::StrongerDeposits),
+                (Key::Semicolon, Action::WeakerDeposits),
+                (Key::Key1, Action::SelectSpecies { index: 0 }),
+                (Key::Key2, Action::SelectSpecies { index: 1 }),
+                (Key::Key3, Action::SelectSpecies { index: 2 }),
+                (Key::Key4, Action::SelectSpecies { index: 3 }),
+                (Key::Key5, Action::SelectSpecies { index: 4 }),
+                (Key::Key6, Action::SelectSpecies { index: 5 }),
+                (Key::Key7, Action::SelectSpecies { index: 6 }),
+                (Key::Key8, Action::SelectSpecies { index: 7 }),
+                (Key::Key9, Action::SelectSpecies { index: 8 }),
+                (Key::Space, Action::TogglePause),
+                (Key::Return, Action::Step),
+                (Key::F5, Action::SaveParams),
+                (Key::F6, Action::ExportSvg),
+                (Key::F12, Action::Screenshot),
+                (Key::F11, Action::ScreenshotGrid),
+                (Key::F8, Action::SaveState),
+                (Key::F9, Action::LoadState),
+            ],
+        }
+    }
+}
+
+impl InputMap {
+    /// The default bindings with `overrides` applied, each a `(action, key)`
+    /// pair of config names. Actions left out keep their default key; a key
+    /// may only trigger one action.
+    pub fn with_overrides<'a>(
+        overrides: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self, String> {
+        let mut map = Self::default();
+        for (action_name, key_name) in overrides {
+            let action = BINDABLE
+                .iter()
+                .find(|(name, _)| *name == action_name)
+                .map(|&(_, action)| action)
+                .ok_or_else(|| format!("unknown action {action_name:?}"))?;
+            let key = KEYS
+                .iter()
+                .find(|(name, _)| *name == key_name)
+                .map(|&(_, key)| key)
+                .ok_or_else(|| format!("unknown key {key_name:?} for {action_name}"))?;
+            for binding in map.bindings.iter_mut() {
+                if binding.1 == action {
+                    binding.0 = key;
+                }
+            }
+        }
+
+        for (i, &(key, action)) in map.bindings.iter().enumerate() {
+            if let Some(&(_, other)) = map.bindings[i + 1..].iter().find(|(k, _)| *k == key) {
+                return Err(format!(
+                    "{key:?} is bound to both {} and {}",
+                    name_of(action),
+                    name_of(other)
+                ));
+            }
+        }
+        Ok(map)
+    }
+
+    /// The actions asked for since the last input update, in binding order
+    /// followed by mouse actions. A close request counts as `Action::Quit`.
+    pub fn actions(&self, input: &WinitInputHelper) -> Vec<Action> {
+        let mut actions: Vec<Action> = self
+            .bindings
+            .iter()
+            .filter(|&&(key, _)| input.key_pressed(key))
+            .map(|&(_, action)| action)
+            .collect();
+        if input.close_requested() && !actions.contains(&Action::Quit) {
+            actions.insert(0, Action::Quit);
+        }
+
+        if let Some((x, y)) = input.mouse() {
+            if input.mouse_diff() != (0.0, 0.0) {
+                actions.push(Action::Hover { x, y });
+            }
+            if input.mouse_held(0) && !input.held_control() && !input.held_shift() {
+                actions.push(Action::Brush { x, y });
+            }
+            if input.mouse_released(0) {
+                actions.push(if input.held_control() {
+                    Action::PickColor { x, y }
+                } else if input.held_shift() {
+                    Action::PlaceSource { x, y }
+                } else {
+                    Action::Spawn { x, y }
+                });
+            }
+        }
+        actions
+    }
+}
+
+fn name_of(action: Action) -> &'static str {
+    BINDABLE
+        .iter()
+        .find(|&&(_, bound)| bound == action)
+        .map_or("?", |&(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_for(map: &InputMap, action: Action) -> Option<VirtualKeyCode> {
+        map.bindings
+            .iter()
+            .find(|&&(_, a)| a == action)
+            .map(|&(k, _)| k)
+    }
+
+    #[test]
+    fn overrides_rebind_only_the_named_actions() {
+        let map = InputMap::with_overrides([("toggle-orbit", "F2"), ("quit", "Q")]).unwrap();
+        assert_eq!(key_for(&map, Action::ToggleOrbit), Some(VirtualKeyCode::F2));
+        assert_eq!(key_for(&map, Action::Quit), Some(VirtualKeyCode::Q));
+        assert_eq!(key_for(&map, Action::ToggleShape), Some(VirtualKeyCode::K));
+    }
+
+    #[test]
+    fn double_bound_and_unknown_names_are_errors() {
+        let err = InputMap::with_overrides([("toggle-orbit", "K")])
+            .err()
+            .unwrap();
+        assert_eq!(err, "K is bound to both toggle-orbit and toggle-shape");
+        assert!(InputMap::with_overrides([("jump", "J")]).is_err());
+        assert!(InputMap::with_overrides([("quit", "Hyper")]).is_err());
+    }
+}
@@ -0,0 +1,15 @@
+#![deny(clippy::all)]
+#![forbid(unsafe_code)]
+
+pub mod agent;
+pub mod demo;
+pub mod font;
+pub mod frames;
+pub mod grid;
+pub mod image;
+pub mod overlay;
+pub mod params;
+pub mod script;
+pub mod species;
+pub mod tiled;
+pub mod world;
@@ -2,280 +2,1025 @@
 #![deny(clippy::all)]
 #![forbid(unsafe_code)]
 
-use agent::Agent;
-mod agent;
-use std::time::Duration;
-use std::vec;
+mod cli;
+mod config;
+mod error;
+mod input;
+mod sweep;
+mod threaded;
 
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use cli::StepClock;
+use error::ZSlimeError;
 use error_iter::ErrorIter as _;
-use game_loop::{game_loop, Time, TimeTrait as _};
-use log::error;
-use pixels::{Error, Pixels, SurfaceTexture};
-use rand::Rng;
-use winit::dpi::LogicalSize;
-use winit::event::VirtualKeyCode;
+use game_loop::game_loop;
+use input::{Action, InputMap};
+use log::{debug, error, info, log_enabled, warn, Level};
+use pixels::{wgpu, Error, Pixels, PixelsBuilder, SurfaceTexture};
+use threaded::SimThread;
+use winit::dpi::{LogicalSize, PhysicalSize};
+use winit::event::{Event, WindowEvent};
 use winit::event_loop::EventLoop;
-use winit::window::WindowBuilder;
+use winit::window::{Window, WindowBuilder};
 use winit_input_helper::WinitInputHelper;
+use z_slime::agent;
+use z_slime::demo::DEMO_AGENTS;
+use z_slime::image;
+use z_slime::overlay;
+use z_slime::params::Preset;
+use z_slime::script::{Command, InputScript, Replay};
+use z_slime::tiled::TileLayout;
+use z_slime::world::{Cell, Rect, World, RETRO_PALETTE, SCREEN_HEIGHT, SCREEN_WIDTH};
 
-const SCREEN_WIDTH: u32 = 800;
-const SCREEN_HEIGHT: u32 = 900;
-const CELLS_WIDTH: usize = 300;
-const CELLS_HEIGHT: usize = 300;
-const CELLS_X: usize = 100;
-const CELLS_Y: usize = 200;
-const SCALE: f32 = 2.0;
+/// Simulation steps per second when `--fps` isn't given.
 const FPS: f64 = 20.0;
+/// Longest the main loop sleeps between frames, one 60 Hz frame, so slow
+/// step rates don't keep window events waiting.
+const MAX_SLEEP: Duration = Duration::from_micros(16_667);
+/// Config file read at startup when `--config` isn't given, if it exists.
+const DEFAULT_CONFIG: &str = "z_slime.toml";
+/// Where the save and load state keys write and read the simulation.
+const STATE_PATH: &str = "z_slime-state.json";
+/// Shortest gap between two cursor probe log lines.
+const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+/// Consecutive over-budget frames before warning that the app is lagging.
+const SLOW_FRAMES_TO_WARN: u32 = 40;
+/// Shortest gap between two lag warnings.
+const SLOW_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+/// Height of the strip over `--tiled` worlds left to the overlays.
+const TILED_TOP: usize = 48;
+/// Steps per `--sweep` run when `--steps` isn't given.
+const SWEEP_STEPS: u64 = 300;
+/// Agents per `--sweep` run when `--agents` isn't given.
+const SWEEP_AGENTS: usize = 2000;
+/// Where `--sweep` writes its contact sheet when `--output` isn't given.
+const SWEEP_OUTPUT: &str = "z_slime-sweep.png";
 
+fn main() {
+    env_logger::init();
+    let options = match cli::Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    let script = match &options.replay {
+        Some(path) => match InputScript::load(path) {
+            Ok(script) => Some(script),
+            Err(err) => {
+                log_error("InputScript::load", err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
 
-pub const TIME_STEP: Duration = Duration::from_nanos(1_000_000_000 / FPS as u64);
+    let fps = options.fps.unwrap_or(FPS);
+    let time_step = match cli::time_step(fps) {
+        Ok(time_step) => time_step,
+        Err(err) => {
+            eprintln!("{err}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
 
-/// Representation of the application state. In this example, a box will bounce around the screen.
+    let config = match &options.config {
+        Some(path) => Some(path.clone()),
+        None => Some(PathBuf::from(DEFAULT_CONFIG)).filter(|path| path.exists()),
+    };
+    let config = match config {
+        Some(path) => match config::load(&path) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                error!("Loading config failed: {err}");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let mut world = match script.as_ref().map(|script| script.seed).or(options.seed) {
+        Some(seed) => World::new_seeded(seed),
+        None => World::new(),
+    };
+    let mut palette = RETRO_PALETTE.to_vec();
+    let input_map = match config {
+        Some(config) => {
+            if let Some(params) = config.params {
+                world.defaults = params.clone();
+                world.params = params;
+            }
+            if let Some(colors) = config.palette {
+                world.palette = Some(colors.clone());
+                palette = colors;
+            }
+            if let Some(levels) = config.contour_levels {
+                world.contour_levels = levels;
+            }
+            if let Some(curve) = config.tone_curve {
+                world.tone_curve = curve;
+            }
+            if let Some(strategy) = config.color_strategy {
+                world.color_strategy = strategy;
+            }
+            if let Some(mix) = config.species {
+                world.species = Some(mix);
+            }
+            config.input_map
+        }
+        None => InputMap::default(),
+    };
+    if let Some(path) = &options.attractor {
+        let values = load_grid_image(path, &world);
+        if let Err(err) = world.set_attractor_field(Some(values)) {
+            error!("{}: {err}", path.display());
+            std::process::exit(1);
+        }
+    }
+    if let Some(path) = &options.decay_map {
+        // Black would keep no heat at all, which the map can't hold; the
+        // smallest fraction it can clears a cell just the same.
+        let values = load_grid_image(path, &world)
+            .into_iter()
+            .map(|keep| keep.max(f32::MIN_POSITIVE))
+            .collect();
+        if let Err(err) = world.set_decay_map(Some(values)) {
+            error!("{}: {err}", path.display());
+            std::process::exit(1);
+        }
+    }
+    if let Some(mix) = &options.species {
+        world.species = Some(mix.clone());
+    }
+    if let Some(path) = &options.walls {
+        let walls = load_grid_image(path, &world)
+            .into_iter()
+            .map(|value| value < 0.5)
+            .collect();
+        if let Err(err) = world.set_walls(Some(walls)) {
+            error!("{}: {err}", path.display());
+            std::process::exit(1);
+        }
+    }
+    if let Some(demo) = options.demo {
+        if let Err(err) = demo.set_up(&mut world) {
+            error!("Setting up the {demo} demo failed: {err}");
+            std::process::exit(1);
+        }
+    }
+    if let Some(path) = &options.record_input {
+        world.start_recording(path);
+    }
+    if let Some(path) = &options.autosave {
+        world.autosave_to(path);
+    }
+    // A replay brings its own preset and scatter along with its other inputs.
+    if let (Some(preset), None) = (options.preset, &script) {
+        world.apply(Command::Preset(preset));
+    }
+    if let Some(steps) = options.burn_in {
+        world.params.burn_in = steps;
+        world.defaults.burn_in = steps;
+    }
+    if let Some(floor) = options.sharp_fronts {
+        world.params.sharp_fronts = Some(floor);
+        world.defaults.sharp_fronts = Some(floor);
+    }
+    // Whole species shares count agents when no total is given.
+    let agents = options
+        .agents
+        .or(world.species.as_ref().and_then(|mix| mix.counts()))
+        .or(options
+            .demo
+            .map(|_| DEMO_AGENTS.min(world.params.max_agents)));
+    if let Some(count) = agents {
+        if count > world.params.max_agents {
+            eprintln!(
+                "{count} agents is over the limit of {}\n\n{}",
+                world.params.max_agents,
+                cli::USAGE
+            );
+            std::process::exit(2);
+        }
+        if script.is_none() {
+            world.apply(Command::Scatter { count });
+        }
+    }
+
+    if !options.sweeps.is_empty() {
+        let run = sweep::SweepRun {
+            base: &world.params,
+            seed: options.seed.unwrap_or(0),
+            agents: options.agents.unwrap_or(SWEEP_AGENTS),
+            steps: options.steps.unwrap_or(SWEEP_STEPS),
+        };
+        let output = options
+            .output
+            .unwrap_or_else(|| PathBuf::from(SWEEP_OUTPUT));
+        if let Err(err) = sweep::run(&options.sweeps, &run, &output) {
+            error!("Sweep failed: {err}");
+            std::process::exit(1);
+        }
+        info!("Saved the sweep to {}", output.display());
+        return;
+    }
 
-fn main() -> Result<(), Error> {
-    env_logger::init();
     let event_loop = EventLoop::new();
 
     let window = {
         let size = LogicalSize::new(SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
         WindowBuilder::new()
             .with_title("Z Slime")
-            .with_resizable(false)
+            .with_resizable(true)
             .with_inner_size(size)
             .build(&event_loop)
             .unwrap()
     };
 
-    let pixels = {
-        let window_size = window.inner_size();
-        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(SCREEN_WIDTH, SCREEN_HEIGHT, surface_texture)?
+    let pixels = match create_pixels(&window) {
+        Ok(pixels) => pixels,
+        Err(err) => {
+            log_error("create_pixels", err);
+            std::process::exit(1);
+        }
     };
 
     struct Game {
         pixels: Pixels,
         input: WinitInputHelper,
-        world: World,
+        input_map: InputMap,
+        /// One world, or several side by side when `layout` is set. Empty
+        /// with `--threaded`, where `sim` has the world.
+        worlds: Vec<World>,
+        layout: Option<TileLayout>,
+        /// The world keyboard actions go to, the last one clicked.
+        active: usize,
+        replay: Option<Replay>,
+        /// Pause while the window is unfocused.
+        pause_on_focus_loss: bool,
+        focus_paused: bool,
+        /// Hold the worlds still, drawn as they are, until unpaused or
+        /// stepped.
+        paused: bool,
+        /// Log the cell under the cursor as it moves.
+        probe: bool,
+        last_probe: Option<Instant>,
+        timer: FrameTimer,
+        /// Draw agents between their last two positions by how far the
+        /// loop is into the next update.
+        interpolate: bool,
+        /// Clicks flood the region under the cursor instead of spawning one agent.
+        flood_fill: bool,
+        /// What the palette toggle quantizes drawing to.
+        palette: Vec<(u8, u8, u8)>,
+        /// List the active world's parameters over the frame.
+        hud: bool,
+        /// The thread stepping and drawing the world with `--threaded`.
+        sim: Option<SimThread>,
+        /// Width and height of the frame in `pixels`, the window's logical
+        /// size.
+        frame_size: (usize, usize),
+        /// When the next step falls due.
+        clock: StepClock,
     }
 
     impl Game {
-        fn new(pixels: Pixels) -> Self {
+        fn new(
+            pixels: Pixels,
+            worlds: Vec<World>,
+            layout: Option<TileLayout>,
+            time_step: Duration,
+        ) -> Self {
             Self {
                 pixels,
                 input: WinitInputHelper::new(),
-                world: World::new(),
+                input_map: InputMap::default(),
+                worlds,
+                layout,
+                active: 0,
+                replay: None,
+                pause_on_focus_loss: true,
+                focus_paused: false,
+                paused: false,
+                probe: false,
+                last_probe: None,
+                timer: FrameTimer::default(),
+                interpolate: false,
+                flood_fill: false,
+                palette: RETRO_PALETTE.to_vec(),
+                hud: false,
+                sim: None,
+                frame_size: (SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize),
+                clock: StepClock::new(time_step, Instant::now()),
+            }
+        }
+
+        /// Follow the window to its new physical `size`: the surface at
+        /// that size, the frame at the logical size so high-DPI windows keep
+        /// two pixels to one, and the worlds laid out afresh for the frame.
+        fn resize(&mut self, size: PhysicalSize<u32>, scale_factor: f64) {
+            // A minimized window has no size to draw at.
+            if size.width == 0 || size.height == 0 {
+                return;
+            }
+            let logical: LogicalSize<u32> = size.to_logical(scale_factor);
+            let (width, height) = (logical.width.max(1), logical.height.max(1));
+            if let Err(err) = self.pixels.resize_surface(size.width, size.height) {
+                log_error("pixels.resize_surface", err);
+                return;
+            }
+            if let Err(err) = self.pixels.resize_buffer(width, height) {
+                log_error("pixels.resize_buffer", err);
+                return;
+            }
+            let (width, height) = (width as usize, height as usize);
+            self.frame_size = (width, height);
+            match &mut self.layout {
+                Some(layout) => layout.fit(&mut self.worlds, tiled_area(width, height)),
+                None => self.on_world(0, move |world| world.fit_to(width, height)),
+            }
+            debug!("Window resized, drawing at {width}x{height}");
+        }
+
+        /// Log the grid cell under the window position `mouse`, at most once
+        /// per `PROBE_INTERVAL`.
+        fn probe(&mut self, mouse: (f32, f32)) {
+            if self
+                .last_probe
+                .is_some_and(|at| at.elapsed() < PROBE_INTERVAL)
+            {
+                return;
+            }
+            let Some((index, (x, y))) = self.locate(mouse) else {
+                return;
+            };
+            self.on_world(index, move |world| {
+                let Some((col, row)) = world.screen_to_grid(x, y) else {
+                    return;
+                };
+                let (cx, cy) = (col as f32, row as f32);
+                info!(
+                    "Cell ({col}, {row}): {:?}, age {}",
+                    world.cell_at(cx, cy).unwrap_or(Cell::Empty),
+                    world.age_at(cx, cy).unwrap_or(0.0)
+                );
+            });
+            self.last_probe = Some(Instant::now());
+        }
+
+        /// The world drawn under the window position `mouse`, and the frame
+        /// pixel there moved into that world's own single-view layout.
+        fn locate(&self, mouse: (f32, f32)) -> Option<(usize, (usize, usize))> {
+            let (x, y) = self.pixels.window_pos_to_pixel(mouse).ok()?;
+            match &self.layout {
+                Some(layout) => {
+                    let index = layout.tile_at(x, y)?;
+                    let world = &self.worlds[index];
+                    Some((index, layout.to_world_screen(index, world, x, y)))
+                }
+                None => Some((0, (x, y))),
+            }
+        }
+
+        /// Make the world clicked at `mouse` the active one and return where
+        /// the click landed in its own layout.
+        fn click(&mut self, mouse: (f32, f32)) -> Option<(usize, usize)> {
+            let (index, pixel) = self.locate(mouse)?;
+            self.active = index;
+            Some(pixel)
+        }
+
+        /// Run `job` on the active world, whichever thread has it.
+        fn with_world(&mut self, job: impl FnOnce(&mut World) + Send + 'static) {
+            self.on_world(self.active, job);
+        }
+
+        /// Run `job` on world `index`, here or, with `--threaded`, on the
+        /// simulation thread before its next step.
+        fn on_world(&mut self, index: usize, job: impl FnOnce(&mut World) + Send + 'static) {
+            match &self.sim {
+                Some(sim) => sim.send(Box::new(move |state| job(&mut state.world))),
+                None => job(&mut self.worlds[index]),
+            }
+        }
+
+        /// Feed the replay any inputs due before this step, then update
+        /// every world once.
+        fn advance(&mut self) {
+            if let Some(replay) = &mut self.replay {
+                for command in replay.due(self.worlds[0].step()) {
+                    self.worlds[0].apply(command);
+                }
+            }
+            for world in &mut self.worlds {
+                world.update();
+            }
+        }
+
+        /// Tell the simulation thread, if there is one, whether to hold.
+        fn sync_pause(&self) {
+            if let Some(sim) = &self.sim {
+                let paused = self.paused || self.focus_paused;
+                sim.send(Box::new(move |state| state.paused = paused));
+            }
+        }
+
+        /// Whether a replay is driving the worlds rather than live input.
+        fn replaying(&self) -> bool {
+            self.replay.is_some() || self.sim.as_ref().is_some_and(SimThread::replaying)
+        }
+
+        /// Carry out every action but `Action::Quit`, which needs the loop.
+        fn perform(&mut self, action: Action) {
+            match action {
+                Action::Quit => {}
+                Action::ToggleOrbit => self.command(Command::ToggleOrbit),
+                Action::CycleColor => self.command(Command::CycleColor),
+                Action::ToggleRandomColor => self.command(Command::ToggleRandomColor),
+                Action::ToggleFade => {
+                    self.with_world(|world| world.fade_colors = !world.fade_colors)
+                }
+                Action::ToggleAgeColors => {
+                    self.with_world(|world| world.age_colors = !world.age_colors)
+                }
+                Action::ToggleAutoExposure => self.with_world(|world| {
+                    world.auto_exposure = !world.auto_exposure;
+                    info!("Auto exposure: {}", world.auto_exposure);
+                }),
+                Action::ToggleDither => self.with_world(|world| {
+                    world.dither = !world.dither;
+                    info!("Dither: {}", world.dither);
+                }),
+                Action::ToggleAgentsOnly => self.with_world(|world| {
+                    world.agents_only = !world.agents_only;
+                    info!("Agents only: {}", world.agents_only);
+                }),
+                Action::TogglePalette => {
+                    let palette = self.palette.clone();
+                    self.with_world(move |world| {
+                        world.palette = match world.palette {
+                            Some(_) => None,
+                            None => Some(palette),
+                        };
+                        info!("Palette: {}", world.palette.is_some());
+                    });
+                }
+                Action::ToggleHud => {
+                    self.hud = !self.hud;
+                    let hud = self.hud;
+                    if let Some(sim) = &self.sim {
+                        sim.send(Box::new(move |state| state.hud = hud));
+                    }
+                }
+                Action::ToggleContours => self.with_world(|world| {
+                    world.contours = !world.contours;
+                    info!("Contours: {}", world.contours);
+                }),
+                Action::Reset => self.command(Command::Reset),
+                Action::ResetParams => self.command(Command::ResetParams),
+                Action::CyclePreset => {
+                    if !self.replaying() {
+                        self.with_world(|world| {
+                            let next = world.preset().map_or(Preset::ClassicSlime, Preset::next);
+                            world.apply(Command::Preset(next));
+                        });
+                    }
+                }
+                Action::ToggleShape => self.command_then(Command::ToggleShape, |world| {
+                    info!("Shape: {:?}", world.shape());
+                }),
+                Action::ToggleRawView => self.command_then(Command::ToggleRawView, |world| {
+                    info!("Draw scale: {}", world.draw_scale);
+                }),
+                Action::ToggleProbe => {
+                    self.probe = !self.probe;
+                    info!("Cursor probe: {}", self.probe);
+                }
+                Action::CycleMarkers => self.with_world(World::cycle_agent_markers),
+                Action::CycleRenderMode => self.with_world(|world| {
+                    world.cycle_render_mode();
+                    info!("Render mode: {:?}", world.render_mode);
+                }),
+                Action::ToggleInterpolation => {
+                    self.interpolate = !self.interpolate;
+                    info!("Interpolation: {}", self.interpolate);
+                }
+                Action::ToggleSensing => self.command_then(Command::ToggleSensing, |world| {
+                    info!("Sensing: {}", world.params.sensing);
+                }),
+                Action::ToggleTurnMode => self.command_then(Command::ToggleTurnMode, |world| {
+                    info!("Turn mode: {:?}", world.params.sensors.turn);
+                }),
+                Action::FlipChemotaxis => self.command_then(Command::FlipChemotaxis, |world| {
+                    info!("Chemotaxis: {}", world.params.sensors.chemotaxis);
+                }),
+                Action::GrowAgents => self.command_then(Command::GrowAgents, |world| {
+                    info!("Agent scale: {}", world.params.agent_scale);
+                }),
+                Action::ShrinkAgents => self.command_then(Command::ShrinkAgents, |world| {
+                    info!("Agent scale: {}", world.params.agent_scale);
+                }),
+                Action::Faster => self.command_then(Command::Faster, |world| {
+                    info!("Agent speed: {}", world.params.motion.speed);
+                }),
+                Action::Slower => self.command_then(Command::Slower, |world| {
+                    info!("Agent speed: {}", world.params.motion.speed);
+                }),
+                Action::LongerTrails => self.command_then(Command::LongerTrails, |world| {
+                    info!("Evaporation: keep {} per pass", world.params.evaporation);
+                }),
+                Action::ShorterTrails => self.command_then(Command::ShorterTrails, |world| {
+                    info!("Evaporation: keep {} per pass", world.params.evaporation);
+                }),
+                Action::StrongerDeposits => self.command_then(Command::StrongerDeposits, |world| {
+                    info!("Deposit strength: {}", world.params.deposit_strength);
+                }),
+                Action::WeakerDeposits => self.command_then(Command::WeakerDeposits, |world| {
+                    info!("Deposit strength: {}", world.params.deposit_strength);
+                }),
+                Action::SelectSpecies { index } => self.command(Command::SelectSpecies { index }),
+                Action::ToggleFloodFill => {
+                    self.flood_fill = !self.flood_fill;
+                    info!("Flood fill: {}", self.flood_fill);
+                }
+                Action::TogglePause => {
+                    self.paused = !self.paused;
+                    info!("Paused: {}", self.paused);
+                    self.sync_pause();
+                }
+                Action::Step => match &self.sim {
+                    _ if !self.paused => info!("Pause before stepping"),
+                    Some(sim) => sim.send(Box::new(|state| state.step_once = true)),
+                    None => {
+                        self.advance();
+                        info!("Step {}", self.worlds[self.active].step());
+                    }
+                },
+                Action::SaveParams => self.with_world(|world| {
+                    let path = timestamped("params", "toml");
+                    match config::save_params(&world.params, &path) {
+                        Ok(()) => info!("Saved parameters to {}", path.display()),
+                        Err(err) => error!("Saving parameters failed: {err}"),
+                    }
+                }),
+                Action::ExportSvg => self.with_world(|world| {
+                    if world.params.trajectory_points == 0 {
+                        warn!("No trajectories recorded; set trajectory_points above 0");
+                    }
+                    let path = timestamped("trajectories", "svg");
+                    match world.export_svg(&path) {
+                        Ok(()) => info!("Saved trajectories to {}", path.display()),
+                        Err(err) => error!("Saving trajectories failed: {err}"),
+                    }
+                }),
+                Action::Screenshot => save_screenshot(self.pixels.frame(), self.frame_size.0, None),
+                Action::ScreenshotGrid => {
+                    let frame = self.pixels.frame().to_vec();
+                    let frame_width = self.frame_size.0;
+                    match &self.layout {
+                        Some(layout) => {
+                            save_screenshot(&frame, frame_width, Some(layout.tiles()[self.active]))
+                        }
+                        None => self.with_world(move |world| {
+                            save_screenshot(&frame, frame_width, Some(world.view()))
+                        }),
+                    }
+                }
+                Action::SaveState => {
+                    self.with_world(|world| match world.save(Path::new(STATE_PATH)) {
+                        Ok(()) => info!("Saved the simulation to {STATE_PATH}"),
+                        Err(err) => error!("Saving the simulation failed: {err}"),
+                    })
+                }
+                Action::LoadState => {
+                    self.with_world(|world| match world.restore(Path::new(STATE_PATH)) {
+                        Ok(()) => info!(
+                            "Loaded the simulation from {STATE_PATH} at step {}",
+                            world.step()
+                        ),
+                        Err(err) => error!("Loading {STATE_PATH} failed: {err}"),
+                    })
+                }
+                Action::Spawn { x, y } => {
+                    let Some(pixel) = self.click((x, y)) else {
+                        return;
+                    };
+                    if self.flood_fill {
+                        self.command(Command::Flood {
+                            x: pixel.0 as i16,
+                            y: pixel.1 as i16,
+                        });
+                    } else {
+                        self.command(Command::Spawn {
+                            x: pixel.0 as i16,
+                            y: pixel.1 as i16,
+                        });
+                    }
+                }
+                Action::Brush { x, y } => {
+                    if self.flood_fill {
+                        return;
+                    }
+                    let Some((x, y)) = self.click((x, y)) else {
+                        return;
+                    };
+                    self.command(Command::Brush {
+                        x: x as i16,
+                        y: y as i16,
+                    });
+                }
+                Action::PlaceSource { x, y } => {
+                    let Some((x, y)) = self.click((x, y)) else {
+                        return;
+                    };
+                    self.command(Command::Source {
+                        x: x as i16,
+                        y: y as i16,
+                    });
+                }
+                Action::PickColor { x, y } => {
+                    let Some((x, y)) = self.click((x, y)) else {
+                        return;
+                    };
+                    self.command(Command::PickColor {
+                        x: x as i16,
+                        y: y as i16,
+                    });
+                }
+                Action::Hover { x, y } => {
+                    if self.probe {
+                        self.probe((x, y));
+                    }
+                }
+            }
+        }
+
+        fn shutdown(&mut self) {
+            if let Some(sim) = self.sim.take() {
+                sim.stop();
+            }
+            for world in &mut self.worlds {
+                world.shutdown();
+            }
+        }
+
+        /// Apply a live input, unless a replay is driving the world.
+        fn command(&mut self, command: Command) {
+            self.command_then(command, |_| {});
+        }
+
+        /// Apply a live input like `command`, then hand the world to `after`
+        /// to report on it.
+        fn command_then(&mut self, command: Command, after: impl FnOnce(&World) + Send + 'static) {
+            if !self.replaying() {
+                self.with_world(move |world| {
+                    world.apply(command);
+                    after(world);
+                });
             }
         }
     }
 
-    let game = Game::new(pixels);
+    let (worlds, layout) = match &options.tiled {
+        Some(variants) => {
+            let mut worlds = Vec::new();
+            let mut labels = Vec::new();
+            for &value in &variants.values {
+                let mut variant = world.clone();
+                match sweep::set_param(&world.params, &variants.path, value) {
+                    Ok(params) => {
+                        variant.defaults = params.clone();
+                        variant.params = params;
+                    }
+                    Err(err) => {
+                        eprintln!("{err}\n\n{}", cli::USAGE);
+                        std::process::exit(2);
+                    }
+                }
+                worlds.push(variant);
+                labels.push(sweep::label(&variants.path, value));
+            }
+            let area = tiled_area(SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize);
+            let layout = TileLayout::new(&mut worlds, labels, area);
+            (worlds, Some(layout))
+        }
+        None => (vec![world], None),
+    };
+
+    log_parameters(&worlds[0], fps);
+    let mut game = Game::new(pixels, worlds, layout, time_step);
+    let replay = script.map(Replay::new);
+    if options.threaded {
+        let world = game.worlds.remove(0);
+        game.sim = Some(SimThread::spawn(world, replay, time_step));
+    } else {
+        game.replay = replay;
+    }
+    game.input_map = input_map;
+    game.palette = palette;
+    game.pause_on_focus_loss = !options.no_focus_pause;
+    game.timer.budget = time_step;
 
     game_loop(
         event_loop,
         window,
         game,
-        FPS as u32,
+        // The loop only updates at whole rates, so `game.clock` times the
+        // steps from the exact `time_step` instead and the loop's own
+        // updates are left empty.
+        1,
         0.1,
+        |_| {},
         move |g| {
-            // Update the world
-            g.game.world.update();
-        },
-        move |g| {
+            // Stepping
+
+            // Ticking while paused still uses up the due step, so resuming
+            // doesn't replay the time spent paused.
+            let game = &mut g.game;
+            let due = game.clock.tick(Instant::now());
+            if due && !(game.focus_paused || game.paused || game.sim.is_some()) {
+                let start = Instant::now();
+                game.advance();
+                game.timer.work += start.elapsed();
+            }
+
             // Drawing
 
-            g.game.world.draw(g.game.pixels.frame_mut());
+            if let Some(sim) = &mut g.game.sim {
+                // The simulation thread draws; show its latest frame.
+                // Frames drawn before a resize don't fit the new one.
+                if let Some(frame) = sim.latest_frame() {
+                    let pixels = g.game.pixels.frame_mut();
+                    if frame.len() == pixels.len() {
+                        pixels.copy_from_slice(frame);
+                    }
+                }
+            } else if !g.game.focus_paused {
+                let start = Instant::now();
+                // A paused frame shows the latest step, not a point on the way to it.
+                let alpha = if g.game.interpolate && !g.game.paused {
+                    g.game.clock.progress(Instant::now()) as f32
+                } else {
+                    1.0
+                };
+                let game = &mut g.game;
+                let frame = game.pixels.frame_mut();
+                let frame_width = game.frame_size.0;
+                match &game.layout {
+                    Some(layout) => {
+                        layout.draw(&mut game.worlds, frame, frame_width, alpha, game.active)
+                    }
+                    None => game.worlds[0].draw_blended(frame, alpha),
+                }
+                overlay::draw_stats(&game.worlds[game.active], frame, frame_width);
+                overlay::draw_frame_time(frame, frame_width, game.timer.last, game.timer.budget);
+                if log_enabled!(Level::Debug) {
+                    overlay::draw_diffusion_stats(
+                        frame,
+                        frame_width,
+                        game.worlds[game.active].diffusion_stats(),
+                    );
+                }
+                if game.hud {
+                    overlay::draw_hud(&game.worlds[game.active], frame, frame_width);
+                }
+                game.timer.work += start.elapsed();
+                game.timer.finish_frame();
+            }
 
             if let Err(err) = g.game.pixels.render() {
                 log_error("pixels.render", err);
+                g.game.shutdown();
                 g.exit();
             }
 
-            // Sleep the main thread to limit drawing to the fixed time step.
+            // Sleep the main thread toward the next step to limit drawing to
+            // the fixed time step, waking at least every `MAX_SLEEP` for
+            // window events.
             // See: https://github.com/parasyte/pixels/issues/174
-            let dt = TIME_STEP.as_secs_f64() - Time::now().sub(&g.current_instant());
-            if dt > 0.0 {
-                std::thread::sleep(Duration::from_secs_f64(dt));
-            }
+            let wait = g
+                .game
+                .clock
+                .next()
+                .saturating_duration_since(Instant::now());
+            std::thread::sleep(wait.min(MAX_SLEEP));
         },
         |g, event| {
-            // Let winit_input_helper collect events to build its state.
-            //     // Handle input events
-            if g.game.input.update(event) {
-                // Close events
-                if g.game.input.key_pressed(VirtualKeyCode::Escape)
-                    || g.game.input.close_requested()
-                {
-                    g.exit();
-                    return;
+            if let Event::WindowEvent {
+                event: WindowEvent::Focused(focused),
+                ..
+            } = event
+            {
+                if g.game.pause_on_focus_loss {
+                    g.game.focus_paused = !focused;
+                    debug!("Window focus {focused}, paused: {}", g.game.focus_paused);
+                    g.game.sync_pause();
                 }
+            }
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(size),
+                    ..
+                } => g.game.resize(*size, g.window.scale_factor()),
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::ScaleFactorChanged {
+                            scale_factor,
+                            new_inner_size,
+                        },
+                    ..
+                } => g.game.resize(**new_inner_size, *scale_factor),
+                _ => {}
+            }
 
-                if g.game.input.mouse_released(0) {
-                    let Some((x, y)) = g.game.input.mouse() else { return; };
-
-                    g.game.world.mouse_action(x as i16, y as i16);
+            // Let winit_input_helper collect events to build its state.
+            if g.game.input.update(event) {
+                for action in g.game.input_map.actions(&g.game.input) {
+                    if action == Action::Quit {
+                        g.game.shutdown();
+                        g.exit();
+                        return;
+                    }
+                    g.game.perform(action);
                 }
             }
         },
     );
 }
 
-fn log_error<E: std::error::Error + 'static>(method_name: &str, err: E) {
-    error!("{method_name}() failed: {err}");
-    for source in err.sources().skip(1) {
-        error!("  Caused by: {source}");
+/// A `Pixels` drawing into `window`, retrying on wgpu's software fallback
+/// adapter when there's no usable hardware one.
+fn create_pixels(window: &Window) -> Result<Pixels, ZSlimeError> {
+    let surface_texture = || {
+        let size = window.inner_size();
+        SurfaceTexture::new(size.width, size.height, window)
+    };
+    match Pixels::new(SCREEN_WIDTH, SCREEN_HEIGHT, surface_texture()) {
+        Ok(pixels) => Ok(pixels),
+        Err(err @ (Error::AdapterNotFound | Error::DeviceNotFound(_))) => {
+            warn!("No GPU adapter ({err}), trying the software fallback");
+            PixelsBuilder::new(SCREEN_WIDTH, SCREEN_HEIGHT, surface_texture())
+                .request_adapter_options(wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    force_fallback_adapter: true,
+                    compatible_surface: None,
+                })
+                .build()
+                .map_err(ZSlimeError::GpuInit)
+        }
+        Err(err) => Err(ZSlimeError::GpuInit(err)),
     }
 }
 
-struct World {
-    width: usize,
-    height: usize,
-    draw_scale: f32,
-    tiles: Vec<Cell>,
-    agents: Vec<Agent>,
-}
-
-impl World {
-    /// Create a new `World` instance that can draw a moving box.
-    fn new() -> Self {
-        Self {
-            width: CELLS_WIDTH,
-            height: CELLS_HEIGHT,
-            draw_scale: SCALE,
-            tiles: vec![Cell::Empty; CELLS_WIDTH.checked_mul(CELLS_HEIGHT).expect("overflow")],
-            agents: Vec::new(),
-        }
+/// Where `--tiled` lays its worlds out in a `width` x `height` frame, below
+/// the overlays.
+fn tiled_area(width: usize, height: usize) -> Rect {
+    Rect {
+        x: 0,
+        y: TILED_TOP,
+        width,
+        height: height.saturating_sub(TILED_TOP),
     }
+}
 
-    fn mouse_inside_world(&self, x: i16, y: i16) -> bool {
-        let cells_pixel_width = (CELLS_WIDTH as f32 * self.draw_scale) as i16;
-        let cells_pixel_height = (CELLS_HEIGHT as f32 * self.draw_scale) as i16;
-        let inside_cells = x > CELLS_X.try_into().unwrap()
-            && x < CELLS_X as i16 + cells_pixel_width
-            && y > CELLS_Y.try_into().unwrap()
-            && y < CELLS_Y as i16 + cells_pixel_height;
-
-        inside_cells
-    }
-
-    fn mouse_action(&mut self, x: i16, y: i16) {
-        let inside_cells = self.mouse_inside_world(x, y);
-        if inside_cells {
-            let agent = Agent::new(
-                x as f32,
-                y as f32,
-                (random_int(0, 255), random_int(0, 255), random_int(0, 255)),
-            );
-            self.agents.push(agent);
-        }
+/// Write the window's `frame`, `frame_width` pixels wide, or just the
+/// `region` of it, to a timestamped PNG file.
+fn save_screenshot(frame: &[u8], frame_width: usize, region: Option<Rect>) {
+    let frame_height = frame.len() / 4 / frame_width;
+    let region = region.unwrap_or(Rect {
+        x: 0,
+        y: 0,
+        width: frame_width,
+        height: frame_height,
+    });
+    let (x, y) = (region.x.min(frame_width), region.y.min(frame_height));
+    let width = region.width.min(frame_width - x);
+    let height = region.height.min(frame_height - y);
+    let pixels = image::crop_rgba(frame, frame_width, (x, y), (width, height));
+    let path = timestamped("screenshot", "png");
+    match image::save_rgba(&path, width as u32, height as u32, &pixels) {
+        Ok(()) => info!("Saved screenshot to {}", path.display()),
+        Err(err) => log_error("image::save_rgba", err),
     }
+}
 
-    fn update(&mut self) {
-        self.update_agents();
-        self.update_tiles();
-    }
+/// `z_slime-<what>-<unix secs>.<extension>` in the working directory.
+fn timestamped(what: &str, extension: &str) -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    PathBuf::from(format!("z_slime-{what}-{secs}.{extension}"))
+}
 
-    fn update_agents(&mut self) {
-        for agent in self.agents.iter_mut() {
-            agent.update(self.height, self.width);
-            self.tiles[(agent.x.round() * agent.y.round()) as usize] =
-                Cell::Heat(agent.rgb.0, agent.rgb.1, agent.rgb.2);
-        }
-    }
+/// Update and draw time spent per frame, and the lag warning it drives.
+#[derive(Default)]
+struct FrameTimer {
+    /// Time each frame may take, one simulation step.
+    budget: Duration,
+    /// Work done so far this frame.
+    work: Duration,
+    /// Work done in the last finished frame.
+    last: Duration,
+    slow_frames: u32,
+    last_warning: Option<Instant>,
+}
 
-    fn update_tiles(&mut self) {
-        let mut write_tiles = self.tiles.clone();
-        for x in 0..self.width {
-            for y in 0..self.height {
-                self.diffuse(x, y, &mut write_tiles)
-            }
+impl FrameTimer {
+    /// Close the current frame, warning if frames have run over `budget`
+    /// for a while.
+    fn finish_frame(&mut self) {
+        self.last = std::mem::take(&mut self.work);
+        if self.last <= self.budget {
+            self.slow_frames = 0;
+            return;
         }
-        self.tiles = write_tiles;
-    }
-
-    fn diffuse(&mut self, x: usize, y: usize, write_tiles: &mut Vec<Cell>) {
-        let idx = x + y * self.width;
-        let mut r_sum = 0;
-        let mut g_sum = 0;
-        let mut b_sum = 0;
-        match self.tiles[idx] {
-            Cell::Empty => {}
-            Cell::Heat(cr, cg, cb) => {
-                r_sum += cr;
-                g_sum += cg;
-                b_sum += cb;
-            }
-        }
-
-        for i in x - 1..x + 1 {
-            for j in y - 1..y + 1 {
-                match self.tiles[i + j * self.width] {
-                    Cell::Empty => {}
-                    Cell::Heat(r, g, b) => {
-                        r_sum += r;
-                        g_sum += g;
-                        b_sum += b;
-                    }
-                }
-            }
+        self.slow_frames += 1;
+        let recently_warned = self
+            .last_warning
+            .is_some_and(|at| at.elapsed() < SLOW_WARNING_INTERVAL);
+        if self.slow_frames >= SLOW_FRAMES_TO_WARN && !recently_warned {
+            warn!(
+                "Frames take {:.1}ms against a {:.1}ms budget; try fewer agents or a smaller grid",
+                self.last.as_secs_f64() * 1000.0,
+                self.budget.as_secs_f64() * 1000.0
+            );
+            self.last_warning = Some(Instant::now());
         }
-        write_tiles[idx] = Cell::Heat(r_sum / 9, g_sum / 9, b_sum / 9);
     }
+}
 
-    /// Draw the `World` state to the frame buffer.
-    ///
-    /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
-    ///
-    fn draw(&mut self, frame: &mut [u8]) {
-        // clear(frame);
-
-        let cells_pixel_width = (CELLS_WIDTH as f32 * self.draw_scale) as i16;
-        let cells_pixel_height = (CELLS_HEIGHT as f32 * self.draw_scale) as i16;
-        for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let x = (i % SCREEN_WIDTH as usize) as i16;
-            let y = (i / SCREEN_WIDTH as usize) as i16;
-            let inside_cells = x > CELLS_X.try_into().unwrap()
-                && x < CELLS_X as i16 + cells_pixel_width
-                && y > CELLS_Y.try_into().unwrap()
-                && y < CELLS_Y as i16 + cells_pixel_height;
-
-            let rgba = if inside_cells {
-                let row: usize = ((y - CELLS_Y as i16) as f32 / self.draw_scale) as usize
-                    % CELLS_HEIGHT as usize;
-                let col: usize =
-                    ((x - CELLS_X as i16) as f32 / self.draw_scale) as usize % CELLS_WIDTH as usize;
-                let tile = self.tiles[row * CELLS_WIDTH + col];
-
-                match tile {
-                    Cell::Empty => [0xff, 0xff, 0xff, 0xff],
-                    Cell::Heat(r, g, b) => [
-                        r.try_into().unwrap(),
-                        g.try_into().unwrap(),
-                        b.try_into().unwrap(),
-                        0xff,
-                    ],
-                }
-            } else {
-                //white background
-                [0xff, 0xff, 0xff, 0xff]
-            };
-
-            pixel.copy_from_slice(&rgba);
+/// The grayscale values of the PNG at `path`, exiting unless it loads and
+/// is the size of `world`'s grid.
+fn load_grid_image(path: &Path, world: &World) -> Vec<f32> {
+    let image = match image::load_grayscale(path) {
+        Ok(image) => image,
+        Err(err) => {
+            log_error("image::load_grayscale", err);
+            std::process::exit(1);
         }
+    };
+    if (image.width, image.height) != (world.width(), world.height()) {
+        error!(
+            "{} is {}x{}, the grid is {}x{}",
+            path.display(),
+            image.width,
+            image.height,
+            world.width(),
+            world.height()
+        );
+        std::process::exit(1);
     }
+    image.values
 }
 
-fn random_int(min: u8, max: u8) -> u8 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..max)
+fn log_error<E: std::error::Error + 'static>(method_name: &str, err: E) {
+    error!("{method_name}() failed: {err}");
+    for source in err.sources().skip(1) {
+        error!("  Caused by: {source}");
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Cell {
-    Empty,
-    Heat(u8, u8, u8),
+/// Log the parameters a run starts with so it can be identified later.
+fn log_parameters(world: &World, fps: f64) {
+    info!(
+        "z_slime parameters:\n  \
+         grid: {}x{} cells, scale {}, fps {}\n  \
+         decay: {}\n  \
+         kernel: 3x3 {:?}, rgb rates {:?}\n  \
+         edges: agents bounce, no wrap\n  \
+         seed: {}\n  \
+         agent speed: {}..{} per axis\n  \
+         spawn mode: {:?}\n  \
+         sensing: {}, {:?}\n  \
+         motion: {:?}\n  \
+         mass normalization: {}",
+        world.width(),
+        world.height(),
+        world.draw_scale,
+        fps,
+        match world.decay_map() {
+            Some(_) => "per-cell map".to_string(),
+            None => format!(
+                "keep {} per pass, empty below {}",
+                world.params.evaporation, world.params.evaporation_threshold
+            ),
+        },
+        world.params.kernel,
+        world.params.diffusion_rate,
+        world.seed(),
+        agent::VELOCITY_RANGE.0 * world.params.motion.speed,
+        agent::VELOCITY_RANGE.1 * world.params.motion.speed,
+        world.params.spawn_mode,
+        if world.params.sensing { "on" } else { "off" },
+        world.params.sensors,
+        world.params.motion,
+        match (world.params.normalize_mass, world.params.mass_target) {
+            (false, _) => "off".to_string(),
+            (true, None) => "on".to_string(),
+            (true, Some(target)) => format!("on, target {target}"),
+        },
+    );
 }
-
-
-
-
-
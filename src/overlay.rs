@@ -0,0 +1,254 @@
+//! Status indicators drawn over the frame, in the margin above the grid.
+
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::font;
+use crate::params::SimParams;
+use crate::world::{gradient_color, ColorStrategy, DiffusionStats, World, SPAWN_PALETTE};
+
+const SWATCH_X: usize = 10;
+const SWATCH_Y: usize = 10;
+const SWATCH_SIZE: usize = 24;
+const BORDER: [u8; 4] = [0x00, 0x00, 0x00, 0xff];
+
+const TIMING_X: usize = 44;
+const TIMING_Y: usize = 18;
+const TIMING_HEIGHT: usize = 8;
+/// Bar length for a frame that takes exactly its budget; the bar is cut off
+/// at twice that.
+const TIMING_BUDGET_WIDTH: usize = 100;
+const TIMING_OK: [u8; 4] = [0x30, 0xa0, 0x40, 0xff];
+const TIMING_SLOW: [u8; 4] = [0xd0, 0x30, 0x30, 0xff];
+
+const STATS_X: usize = 44;
+const STATS_Y: usize = 30;
+const STATS_HEIGHT: usize = 2;
+/// Bar length per doubling of a diffusion stats count.
+const STATS_UNIT: usize = 8;
+const STATS_EDGE: [u8; 4] = [0x40, 0x60, 0xd0, 0xff];
+const STATS_SATURATED: [u8; 4] = [0xe0, 0x90, 0x20, 0xff];
+const STATS_UNDERFLOW: [u8; 4] = [0x80, 0x80, 0x80, 0xff];
+
+const HUD_X: usize = 10;
+const HUD_Y: usize = 48;
+const HUD_SCALE: usize = 2;
+const HUD_LINE: usize = (font::GLYPH_HEIGHT + 2) * HUD_SCALE;
+const HUD_PADDING: usize = 6;
+const HUD_TEXT: [u8; 4] = [0xf0, 0xf0, 0xf0, 0xff];
+/// How much of the frame shows through the HUD's black backdrop.
+const HUD_SHOW_THROUGH: f32 = 0.35;
+
+/// Draw the stats overlay into an RGBA `frame` that is `frame_width` pixels
+/// wide: currently a swatch of the color new agents will get, split into
+/// palette colors while random colors are on and shading through the
+/// gradient while colors come from one.
+pub fn draw_stats(world: &World, frame: &mut [u8], frame_width: usize) {
+    for dy in 0..SWATCH_SIZE {
+        for dx in 0..SWATCH_SIZE {
+            let edge = dx == 0 || dy == 0 || dx == SWATCH_SIZE - 1 || dy == SWATCH_SIZE - 1;
+            let rgba = if edge {
+                BORDER
+            } else {
+                let (r, g, b) = match &world.color_strategy {
+                    ColorStrategy::Random => {
+                        let quadrant = (dx * 2 / SWATCH_SIZE) + 2 * (dy * 2 / SWATCH_SIZE);
+                        SPAWN_PALETTE[quadrant * 2]
+                    }
+                    ColorStrategy::PaletteGradient(stops) => {
+                        gradient_color(stops, dx as f32 / (SWATCH_SIZE - 1) as f32)
+                    }
+                    ColorStrategy::Fixed => world.spawn_color,
+                };
+                [r, g, b, 0xff]
+            };
+            put_pixel(frame, frame_width, SWATCH_X + dx, SWATCH_Y + dy, rgba);
+        }
+    }
+}
+
+/// Draw a bar showing `measured` frame time against the `budget`: green
+/// while within it, red beyond it, with a tick where the budget ends.
+pub fn draw_frame_time(frame: &mut [u8], frame_width: usize, measured: Duration, budget: Duration) {
+    let ratio = measured.as_secs_f64() / budget.as_secs_f64().max(f64::EPSILON);
+    let length = ((ratio * TIMING_BUDGET_WIDTH as f64) as usize).min(2 * TIMING_BUDGET_WIDTH);
+    let color = if measured > budget {
+        TIMING_SLOW
+    } else {
+        TIMING_OK
+    };
+    for dy in 0..TIMING_HEIGHT {
+        for dx in 0..length {
+            put_pixel(frame, frame_width, TIMING_X + dx, TIMING_Y + dy, color);
+        }
+    }
+    for dy in 0..TIMING_HEIGHT + 4 {
+        put_pixel(
+            frame,
+            frame_width,
+            TIMING_X + TIMING_BUDGET_WIDTH,
+            TIMING_Y + dy - 2,
+            BORDER,
+        );
+    }
+}
+
+/// Draw one thin bar per `DiffusionStats` counter under the frame time bar,
+/// growing with the count's logarithm: edge clamping in blue, saturation in
+/// orange and underflow in gray.
+pub fn draw_diffusion_stats(frame: &mut [u8], frame_width: usize, stats: DiffusionStats) {
+    let bars = [
+        (stats.edge_clamped, STATS_EDGE),
+        (stats.saturated, STATS_SATURATED),
+        (stats.underflowed, STATS_UNDERFLOW),
+    ];
+    for (row, (count, color)) in bars.into_iter().enumerate() {
+        let length = (u64::BITS - count.leading_zeros()) as usize * STATS_UNIT;
+        let y = STATS_Y + row * (STATS_HEIGHT + 1);
+        for dy in 0..STATS_HEIGHT {
+            for dx in 0..length {
+                put_pixel(frame, frame_width, STATS_X + dx, y + dy, color);
+            }
+        }
+    }
+}
+
+/// Draw the spawn color, with a swatch while it's fixed, then every field
+/// of `world.params` as a `path = value` line, paths dotted as in the
+/// config file, over a darkened backdrop that keeps the text legible on
+/// any trails. Only the frame is touched.
+pub fn draw_hud(world: &World, frame: &mut [u8], frame_width: usize) {
+    let color_line = match world.color_strategy {
+        ColorStrategy::Random => "spawn color = random".to_string(),
+        ColorStrategy::PaletteGradient(_) => "spawn color = gradient".to_string(),
+        ColorStrategy::Fixed => {
+            let (r, g, b) = world.spawn_color;
+            format!("spawn color = {r}, {g}, {b}")
+        }
+    };
+    let color_width = font::text_width(&color_line, HUD_SCALE) + HUD_PADDING + HUD_LINE;
+    let mut lines = vec![color_line];
+    lines.extend(param_lines(&world.params));
+    let width = lines
+        .iter()
+        .map(|line| font::text_width(line, HUD_SCALE))
+        .chain([color_width])
+        .max()
+        .unwrap_or(0)
+        + 2 * HUD_PADDING;
+    let height = lines.len() * HUD_LINE + 2 * HUD_PADDING;
+    let frame_height = frame.len() / 4 / frame_width;
+    for y in HUD_Y..(HUD_Y + height).min(frame_height) {
+        for x in HUD_X..(HUD_X + width).min(frame_width) {
+            let idx = (x + y * frame_width) * 4;
+            for channel in &mut frame[idx..idx + 3] {
+                *channel = (*channel as f32 * HUD_SHOW_THROUGH) as u8;
+            }
+        }
+    }
+    for (i, line) in lines.iter().enumerate() {
+        font::draw_text(
+            frame,
+            frame_width,
+            (HUD_X + HUD_PADDING, HUD_Y + HUD_PADDING + i * HUD_LINE),
+            line,
+            HUD_TEXT,
+            HUD_SCALE,
+        );
+    }
+    if world.color_strategy == ColorStrategy::Fixed {
+        let (r, g, b) = world.spawn_color;
+        let left = HUD_X + HUD_PADDING + color_width - HUD_LINE;
+        for dy in 0..HUD_LINE - HUD_SCALE * 2 {
+            for dx in 0..HUD_LINE - HUD_SCALE * 2 {
+                put_pixel(
+                    frame,
+                    frame_width,
+                    left + dx,
+                    HUD_Y + HUD_PADDING + dy,
+                    [r, g, b, 0xff],
+                );
+            }
+        }
+    }
+}
+
+/// One `path = value` line per leaf of `params`.
+fn param_lines(params: &SimParams) -> Vec<String> {
+    fn walk(path: &str, value: &Value, lines: &mut Vec<String>) {
+        let join = |key: &str| match path {
+            "" => key.to_string(),
+            _ => format!("{path}.{key}"),
+        };
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    walk(&join(key), value, lines);
+                }
+            }
+            value => lines.push(format!("{path} = {}", show(value))),
+        }
+    }
+    fn show(value: &Value) -> String {
+        match value {
+            Value::Null => "none".into(),
+            Value::String(text) => text.clone(),
+            // Most parameters are f32; showing them at that precision
+            // drops the noise widening them to f64 adds.
+            Value::Number(n) if n.is_f64() => (n.as_f64().unwrap_or(0.0) as f32).to_string(),
+            Value::Array(items) => items.iter().map(show).collect::<Vec<_>>().join(", "),
+            value => value.to_string(),
+        }
+    }
+
+    let mut lines = Vec::new();
+    if let Ok(value) = serde_json::to_value(params) {
+        walk("", &value, &mut lines);
+    }
+    lines
+}
+
+fn put_pixel(frame: &mut [u8], frame_width: usize, x: usize, y: usize, rgba: [u8; 4]) {
+    if x >= frame_width {
+        return;
+    }
+    let idx = (x + y * frame_width) * 4;
+    if let Some(pixel) = frame.get_mut(idx..idx + 4) {
+        pixel.copy_from_slice(&rgba);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hud_lists_every_parameter_by_path() {
+        let mut params = SimParams::default();
+        params.sensors.angle = 0.3;
+        let lines = param_lines(&params);
+        assert!(lines.contains(&"sensors.angle = 0.3".to_string()));
+        assert!(lines.contains(&"diffusion_rate = 1, 1, 1".to_string()));
+        assert!(lines.contains(&"mass_target = none".to_string()));
+        assert!(lines.contains(&"kernel = box".to_string()));
+
+        let mut world = World::with_size(10, 10, 0);
+        world.params = params;
+        world.color_strategy = ColorStrategy::Fixed;
+        world.spawn_color = (10, 20, 30);
+        let mut frame = vec![0xff; 800 * 900 * 4];
+        draw_hud(&world, &mut frame, 800);
+        let pixel = |x: usize, y: usize| frame[(x + y * 800) * 4];
+        assert_eq!(
+            pixel(HUD_X + 1, HUD_Y + 1),
+            (255.0 * HUD_SHOW_THROUGH) as u8
+        );
+        assert_eq!(pixel(HUD_X - 1, HUD_Y + 1), 0xff);
+
+        // The spawn color's swatch ends the first line.
+        let text = font::text_width("spawn color = 10, 20, 30", HUD_SCALE);
+        let swatch = HUD_X + 2 * HUD_PADDING + text + 1;
+        assert_eq!(pixel(swatch, HUD_Y + HUD_PADDING + 1), 10);
+    }
+}
@@ -0,0 +1,341 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::agent::{Motion, MovementMode, SensorConfig, SpawnMode, TurnMode};
+use crate::world::{GridUpdate, Kernel, ScanOrder, UpdateOrder};
+
+const MAX_AGENTS: usize = 10_000;
+
+/// The simulation's tunable parameters, grouped so a whole set can be kept
+/// as defaults and restored in one go.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimParams {
+    /// Rescale the field after diffusion so its total intensity is conserved.
+    pub normalize_mass: bool,
+    /// Total intensity to normalize to; `None` keeps the pre-diffusion total.
+    pub mass_target: Option<f64>,
+    pub spawn_mode: SpawnMode,
+    /// Steer agents with `sensors`; otherwise they move in straight lines.
+    pub sensing: bool,
+    pub sensors: SensorConfig,
+    /// Inertia and speed limit applied whenever agents steer; the default
+    /// steers instantly.
+    pub motion: Motion,
+    pub movement: MovementMode,
+    /// Positions each agent keeps for `World::export_svg`; `0` records no
+    /// trajectories.
+    pub trajectory_points: usize,
+    /// Agents lay heat on every `deposit_interval`th step, so values above
+    /// 1 leave dashed trails.
+    pub deposit_interval: u32,
+    /// Radius in cells of the gaussian blob agents deposit; `0.0` lays
+    /// heat on the single cell under the agent.
+    pub deposit_radius: f32,
+    /// Peak of a blob deposit, or all of a single-cell one, as a fraction
+    /// of the agent's full color.
+    pub deposit_strength: f32,
+    /// Size of every agent: multiplies `sensors.distance`, `deposit_radius`
+    /// and the size of the agent markers drawn. A single-cell deposit
+    /// (`deposit_radius` of `0.0`) stays a single cell.
+    pub agent_scale: f32,
+    /// Updates at the start of a run that only diffuse the field, letting
+    /// seeded heat settle before agents start moving.
+    pub burn_in: u32,
+    /// Whether new trails are laid before or after each step's diffusion,
+    /// softening or sharpening them.
+    pub update_order: UpdateOrder,
+    pub grid_update: GridUpdate,
+    /// Cell order for dense and in-place passes; see `ScanOrder` for when
+    /// it makes a difference.
+    pub scan_order: ScanOrder,
+    /// How far each of red, green and blue moves toward the neighbourhood
+    /// average per diffusion pass: `1.0` takes the full box blur, `0.0`
+    /// doesn't spread that channel at all.
+    pub diffusion_rate: [f32; 3],
+    /// The neighbourhood filter diffusion blurs with.
+    pub kernel: Kernel,
+    /// Keep trail fronts sharp: with `Some(floor)`, no cell loses more than
+    /// `1 - floor` of its heat to a diffusion pass, so empty surroundings
+    /// don't wash a lone bright cell out into haze. Walls and cells outside
+    /// the shape never count toward a neighbourhood average either way.
+    pub sharp_fronts: Option<f32>,
+    /// Fraction of each cell's heat kept after every diffusion pass; `1.0`
+    /// never fades trails. A world's decay map, if it has one, takes over
+    /// from this cell by cell.
+    pub evaporation: f32,
+    /// Cells whose channels have all faded below this are emptied, rather
+    /// than lingering as a faint haze over the whole grid.
+    pub evaporation_threshold: f32,
+    /// How strongly diffusion favours the local flow direction, along trails
+    /// rather than across them: `0.0` spreads evenly in every direction,
+    /// `1.0` stops spreading straight across the gradient.
+    pub anisotropy: f32,
+    /// Diffuse on every `diffuse_every`th update only. Trails look much the
+    /// same at 2 while large grids update nearly twice as fast.
+    pub diffuse_every: u32,
+    /// Still evaporate on the updates `diffuse_every` skips, so trails fade
+    /// at the same rate whatever it is. Off saves those passes too, and
+    /// trails last `diffuse_every` times as long.
+    pub decay_between_diffusions: bool,
+    /// Agents per cell a flood spawn scatters over the region it fills.
+    pub flood_density: f32,
+    /// Radius in cells of the disc a held mouse button spawns agents over.
+    pub brush_radius: f32,
+    /// Agents the held mouse button spawns per frame.
+    pub brush_count: usize,
+    /// Upper bound on the agent population.
+    pub max_agents: usize,
+    /// Chance per update that an agent on a strong trail spawns a child.
+    pub reproduction_chance: f64,
+    /// Mean channel intensity a cell needs before agents on it can reproduce.
+    pub reproduction_threshold: f32,
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        Self {
+            normalize_mass: false,
+            mass_target: None,
+            spawn_mode: SpawnMode::Bounce,
+            sensing: true,
+            sensors: SensorConfig::default(),
+            motion: Motion::default(),
+            movement: MovementMode::Continuous,
+            trajectory_points: 0,
+            deposit_interval: 1,
+            deposit_radius: 0.0,
+            deposit_strength: 1.0,
+            agent_scale: 1.0,
+            burn_in: 0,
+            update_order: UpdateOrder::DepositThenDiffuse,
+            grid_update: GridUpdate::Dense,
+            scan_order: ScanOrder::Raster,
+            diffusion_rate: [1.0; 3],
+            kernel: Kernel::Box,
+            sharp_fronts: None,
+            evaporation: 0.98,
+            evaporation_threshold: 2.0,
+            anisotropy: 0.0,
+            diffuse_every: 1,
+            decay_between_diffusions: true,
+            flood_density: 0.05,
+            brush_radius: 4.0,
+            brush_count: 3,
+            max_agents: MAX_AGENTS,
+            reproduction_chance: 0.0,
+            reproduction_threshold: 128.0,
+        }
+    }
+}
+
+/// Curated parameter bundles that give a good-looking run straight away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Sensing agents with the classic Physarum sensor layout, moving a cell
+    /// an update, snapping onto the strongest trail and leaving a
+    /// full-strength box blur. Trails keep 95% a pass and clear below 1.0,
+    /// fed by 1.5-strength deposits of radius 1.
+    ClassicSlime,
+    /// Long, narrow sensors and gentle turns at 1.5 cells an update with
+    /// anisotropic diffusion, drawing fine strands that stay thin. Faint
+    /// single-cell deposits (0.75) keep 97% a pass and clear below 4.0 so the
+    /// gaps stay dark.
+    Cobweb,
+    /// Short, wide sensors, slow diffusion and reproduction on strong trails,
+    /// growing branching clumps outward at half a cell an update. Trails keep
+    /// 99% a pass and linger down to 1.0, fed by 1.25-strength blobs of
+    /// radius 1.5.
+    Coral,
+    /// Eager reproduction and full mass normalization, filling the grid with
+    /// a tight mesh at 0.75 cells an update. Strong 2.0 deposits of radius 1
+    /// fade fast, keeping 90% a pass and clearing below 3.0.
+    Dense,
+    /// Far-sighted, heavy agents at 1.25 cells an update that settle into a
+    /// few widely spaced paths. Soft 0.5-strength blobs of radius 2 keep
+    /// 99.5% a pass and linger down to 0.5.
+    Sparse,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [
+        Preset::ClassicSlime,
+        Preset::Cobweb,
+        Preset::Coral,
+        Preset::Dense,
+        Preset::Sparse,
+    ];
+
+    /// The parameters this preset stands for.
+    pub fn params(self) -> SimParams {
+        let defaults = SimParams::default();
+        match self {
+            Preset::ClassicSlime => SimParams {
+                sensing: true,
+                motion: Motion {
+                    speed: 1.0,
+                    ..defaults.motion
+                },
+                evaporation: 0.95,
+                evaporation_threshold: 1.0,
+                deposit_strength: 1.5,
+                deposit_radius: 1.0,
+                ..defaults
+            },
+            Preset::Cobweb => SimParams {
+                sensing: true,
+                sensors: SensorConfig {
+                    distance: 20.0,
+                    angle: 0.2,
+                    turn: TurnMode::Gradual { rate: 0.05 },
+                    ..defaults.sensors
+                },
+                motion: Motion {
+                    speed: 1.5,
+                    ..defaults.motion
+                },
+                diffusion_rate: [0.5; 3],
+                anisotropy: 0.8,
+                evaporation: 0.97,
+                evaporation_threshold: 4.0,
+                deposit_strength: 0.75,
+                deposit_radius: 0.0,
+                ..defaults
+            },
+            Preset::Coral => SimParams {
+                sensing: true,
+                sensors: SensorConfig {
+                    distance: 4.0,
+                    angle: 0.8,
+                    turn: TurnMode::Gradual { rate: 0.2 },
+                    ..defaults.sensors
+                },
+                motion: Motion {
+                    speed: 0.5,
+                    ..defaults.motion
+                },
+                diffusion_rate: [0.3; 3],
+                reproduction_chance: 0.01,
+                reproduction_threshold: 96.0,
+                evaporation: 0.99,
+                evaporation_threshold: 1.0,
+                deposit_strength: 1.25,
+                deposit_radius: 1.5,
+                ..defaults
+            },
+            Preset::Dense => SimParams {
+                sensing: true,
+                sensors: SensorConfig {
+                    distance: 6.0,
+                    angle: 0.6,
+                    ..defaults.sensors
+                },
+                motion: Motion {
+                    speed: 0.75,
+                    ..defaults.motion
+                },
+                normalize_mass: true,
+                reproduction_chance: 0.05,
+                reproduction_threshold: 64.0,
+                evaporation: 0.9,
+                evaporation_threshold: 3.0,
+                deposit_strength: 2.0,
+                deposit_radius: 1.0,
+                ..defaults
+            },
+            Preset::Sparse => SimParams {
+                sensing: true,
+                sensors: SensorConfig {
+                    distance: 15.0,
+                    angle: 0.3,
+                    ..defaults.sensors
+                },
+                motion: Motion {
+                    inertia: 0.5,
+                    max_speed: Some(1.0),
+                    speed: 1.25,
+                    ..defaults.motion
+                },
+                evaporation: 0.995,
+                evaporation_threshold: 0.5,
+                deposit_strength: 0.5,
+                deposit_radius: 2.0,
+                ..defaults
+            },
+        }
+    }
+
+    /// The preset after this one, wrapping around.
+    pub fn next(self) -> Preset {
+        let i = Preset::ALL.iter().position(|&p| p == self).unwrap_or(0);
+        Preset::ALL[(i + 1) % Preset::ALL.len()]
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Preset::ClassicSlime => "classic-slime",
+            Preset::Cobweb => "cobweb",
+            Preset::Coral => "coral",
+            Preset::Dense => "dense",
+            Preset::Sparse => "sparse",
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Preset::ALL
+            .into_iter()
+            .find(|preset| preset.name() == s)
+            .ok_or_else(|| format!("unknown preset {s:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_parse_from_their_names_and_cycle_through_all() {
+        let mut preset = Preset::ClassicSlime;
+        for _ in Preset::ALL {
+            assert_eq!(preset.to_string().parse(), Ok(preset));
+            preset = preset.next();
+        }
+        assert_eq!(preset, Preset::ClassicSlime);
+        assert!("mould".parse::<Preset>().is_err());
+    }
+
+    #[test]
+    fn every_preset_sets_its_own_speed_decay_and_deposit() {
+        let defaults = SimParams::default();
+        for (i, a) in Preset::ALL.iter().enumerate() {
+            let params = a.params();
+            assert_ne!(params.evaporation, defaults.evaporation, "{a}");
+            assert_ne!(params.deposit_strength, defaults.deposit_strength, "{a}");
+            for b in &Preset::ALL[i + 1..] {
+                let other = b.params();
+                assert_ne!(params.motion.speed, other.motion.speed, "{a} and {b}");
+                assert_ne!(
+                    (params.evaporation, params.evaporation_threshold),
+                    (other.evaporation, other.evaporation_threshold),
+                    "{a} and {b}"
+                );
+                assert_ne!(
+                    (params.deposit_strength, params.deposit_radius),
+                    (other.deposit_strength, other.deposit_radius),
+                    "{a} and {b}"
+                );
+            }
+        }
+    }
+}
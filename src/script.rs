@@ -0,0 +1,331 @@
+//! Recorded input scripts for replaying a session step for step.
+//!
+//! A script is plain text: a `seed <n>` line followed by one
+//! `<step> <command>` line per input, where `<step>` is the number of
+//! updates the world had completed when the input arrived. Blank lines and
+//! lines starting with `#` are ignored.
+//!
+//! ```text
+//! seed 42
+//! 0 spawn 250 310
+//! 57 orbit
+//! 60 sensing
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::params::Preset;
+
+/// An input that changes the simulation, as recorded in a script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// A click at screen position `(x, y)`.
+    Spawn {
+        x: i16,
+        y: i16,
+    },
+    /// One frame of the mouse held down at screen position `(x, y)`: a few
+    /// agents scattered around it.
+    Brush {
+        x: i16,
+        y: i16,
+    },
+    /// Fill the empty region under screen position `(x, y)` with agents.
+    Flood {
+        x: i16,
+        y: i16,
+    },
+    /// Pin the cell under screen position `(x, y)` as a heat source.
+    Source {
+        x: i16,
+        y: i16,
+    },
+    /// Take the color of the heat under screen position `(x, y)` as the
+    /// spawn color.
+    PickColor {
+        x: i16,
+        y: i16,
+    },
+    /// Scatter `count` agents over random cells.
+    Scatter {
+        count: usize,
+    },
+    /// Switch new spawns between bouncing and orbiting.
+    ToggleOrbit,
+    /// Switch trail-following sensors on or off.
+    ToggleSensing,
+    /// Switch steering between snapping and gradual turns.
+    ToggleTurnMode,
+    /// Flip trails between attracting and repelling sensing agents.
+    FlipChemotaxis,
+    /// Make agents a step bigger, or with `ShrinkAgents` smaller; see
+    /// `SimParams::agent_scale`.
+    GrowAgents,
+    ShrinkAgents,
+    /// Speed agents up a step, or with `Slower` slow them down; see
+    /// `Motion::speed`.
+    Faster,
+    Slower,
+    /// Keep a little more of the heat each pass, or with `ShorterTrails`
+    /// less; see `SimParams::evaporation`.
+    LongerTrails,
+    ShorterTrails,
+    /// Make deposits a step hotter, or with `WeakerDeposits` fainter; see
+    /// `SimParams::deposit_strength`.
+    StrongerDeposits,
+    WeakerDeposits,
+    /// Empty the grid and remove every agent.
+    Reset,
+    /// Put every simulation parameter back to its default.
+    ResetParams,
+    /// Switch to a preset's parameters, which also become the defaults.
+    Preset(Preset),
+    /// Switch the simulated region between the full grid and a disc.
+    ToggleShape,
+    /// Select the next palette color for new agents.
+    CycleColor,
+    /// Spawn species `index` of the world's species mix, or go back to
+    /// picking by share if it's already selected.
+    SelectSpecies {
+        index: usize,
+    },
+    /// Switch new agents between random colors and the selected color.
+    ToggleRandomColor,
+    /// Switch between the scaled grid and one cell per screen pixel. Clicks
+    /// map onto the grid differently in each, so this is recorded too.
+    ToggleRawView,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Spawn { x, y } => write!(f, "spawn {x} {y}"),
+            Command::Brush { x, y } => write!(f, "brush {x} {y}"),
+            Command::Flood { x, y } => write!(f, "flood {x} {y}"),
+            Command::Source { x, y } => write!(f, "source {x} {y}"),
+            Command::PickColor { x, y } => write!(f, "pick-color {x} {y}"),
+            Command::Scatter { count } => write!(f, "scatter {count}"),
+            Command::ToggleOrbit => write!(f, "orbit"),
+            Command::ToggleSensing => write!(f, "sensing"),
+            Command::ToggleTurnMode => write!(f, "turn-mode"),
+            Command::FlipChemotaxis => write!(f, "flip-chemotaxis"),
+            Command::GrowAgents => write!(f, "grow-agents"),
+            Command::ShrinkAgents => write!(f, "shrink-agents"),
+            Command::Faster => write!(f, "faster"),
+            Command::Slower => write!(f, "slower"),
+            Command::LongerTrails => write!(f, "longer-trails"),
+            Command::ShorterTrails => write!(f, "shorter-trails"),
+            Command::StrongerDeposits => write!(f, "stronger-deposits"),
+            Command::WeakerDeposits => write!(f, "weaker-deposits"),
+            Command::Reset => write!(f, "reset"),
+            Command::ResetParams => write!(f, "reset-params"),
+            Command::Preset(preset) => write!(f, "preset {preset}"),
+            Command::ToggleShape => write!(f, "shape"),
+            Command::CycleColor => write!(f, "color"),
+            Command::SelectSpecies { index } => write!(f, "species {index}"),
+            Command::ToggleRandomColor => write!(f, "random-color"),
+            Command::ToggleRawView => write!(f, "raw-view"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputScript {
+    pub seed: u64,
+    /// `(step, command)` pairs in the order they were applied.
+    pub events: Vec<(u64, Command)>,
+}
+
+impl InputScript {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for InputScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "seed {}", self.seed)?;
+        for (step, command) in &self.events {
+            writeln!(f, "{step} {command}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for InputScript {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let invalid = |line: usize, msg: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {line}: {msg}"))
+        };
+
+        let mut seed = None;
+        let mut events = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let ["seed", value] = words[..] {
+                let value = value.parse().map_err(|_| invalid(line_no, "bad seed"))?;
+                seed = Some(value);
+                continue;
+            }
+
+            let step: u64 = words[0].parse().map_err(|_| invalid(line_no, "bad step"))?;
+            let command = match words[1..] {
+                ["spawn", x, y] => Command::Spawn {
+                    x: x.parse().map_err(|_| invalid(line_no, "bad x"))?,
+                    y: y.parse().map_err(|_| invalid(line_no, "bad y"))?,
+                },
+                ["flood", x, y] => Command::Flood {
+                    x: x.parse().map_err(|_| invalid(line_no, "bad x"))?,
+                    y: y.parse().map_err(|_| invalid(line_no, "bad y"))?,
+                },
+                ["brush", x, y] => Command::Brush {
+                    x: x.parse().map_err(|_| invalid(line_no, "bad x"))?,
+                    y: y.parse().map_err(|_| invalid(line_no, "bad y"))?,
+                },
+                ["source", x, y] => Command::Source {
+                    x: x.parse().map_err(|_| invalid(line_no, "bad x"))?,
+                    y: y.parse().map_err(|_| invalid(line_no, "bad y"))?,
+                },
+                ["pick-color", x, y] => Command::PickColor {
+                    x: x.parse().map_err(|_| invalid(line_no, "bad x"))?,
+                    y: y.parse().map_err(|_| invalid(line_no, "bad y"))?,
+                },
+                ["scatter", count] => Command::Scatter {
+                    count: count.parse().map_err(|_| invalid(line_no, "bad count"))?,
+                },
+                ["orbit"] => Command::ToggleOrbit,
+                ["sensing"] => Command::ToggleSensing,
+                ["turn-mode"] => Command::ToggleTurnMode,
+                ["flip-chemotaxis"] => Command::FlipChemotaxis,
+                ["grow-agents"] => Command::GrowAgents,
+                ["shrink-agents"] => Command::ShrinkAgents,
+                ["faster"] => Command::Faster,
+                ["slower"] => Command::Slower,
+                ["longer-trails"] => Command::LongerTrails,
+                ["shorter-trails"] => Command::ShorterTrails,
+                ["stronger-deposits"] => Command::StrongerDeposits,
+                ["weaker-deposits"] => Command::WeakerDeposits,
+                ["reset"] => Command::Reset,
+                ["reset-params"] => Command::ResetParams,
+                ["preset", name] => {
+                    Command::Preset(name.parse().map_err(|err: String| invalid(line_no, &err))?)
+                }
+                ["shape"] => Command::ToggleShape,
+                ["color"] => Command::CycleColor,
+                ["species", index] => Command::SelectSpecies {
+                    index: index.parse().map_err(|_| invalid(line_no, "bad species"))?,
+                },
+                ["random-color"] => Command::ToggleRandomColor,
+                ["raw-view"] => Command::ToggleRawView,
+                _ => return Err(invalid(line_no, "unknown command")),
+            };
+            events.push((step, command));
+        }
+
+        let seed = seed.ok_or_else(|| invalid(0, "missing seed line"))?;
+        Ok(Self { seed, events })
+    }
+}
+
+/// Feeds a script's commands back out as the world reaches their steps.
+pub struct Replay {
+    script: InputScript,
+    next: usize,
+}
+
+impl Replay {
+    pub fn new(script: InputScript) -> Self {
+        Self { script, next: 0 }
+    }
+
+    /// Commands recorded at or before `step` that haven't been handed out yet.
+    pub fn due(&mut self, step: u64) -> impl Iterator<Item = Command> + '_ {
+        let start = self.next;
+        while self
+            .script
+            .events
+            .get(self.next)
+            .is_some_and(|(at, _)| *at <= step)
+        {
+            self.next += 1;
+        }
+        self.script.events[start..self.next]
+            .iter()
+            .map(|(_, command)| *command)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.script.events.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_round_trips_through_text() {
+        let mut script = InputScript::new(42);
+        script.events.push((0, Command::Spawn { x: 250, y: 310 }));
+        script.events.push((0, Command::Scatter { count: 500 }));
+        script.events.push((1, Command::Flood { x: 300, y: 400 }));
+        script.events.push((1, Command::Brush { x: 315, y: 405 }));
+        script.events.push((1, Command::Source { x: 320, y: 410 }));
+        script
+            .events
+            .push((1, Command::PickColor { x: 330, y: 420 }));
+        script.events.push((57, Command::ToggleOrbit));
+        script.events.push((60, Command::ToggleSensing));
+        script.events.push((60, Command::ToggleTurnMode));
+        script.events.push((60, Command::FlipChemotaxis));
+        script.events.push((60, Command::GrowAgents));
+        script.events.push((60, Command::ShrinkAgents));
+        script.events.push((60, Command::Faster));
+        script.events.push((60, Command::Slower));
+        script.events.push((60, Command::LongerTrails));
+        script.events.push((60, Command::ShorterTrails));
+        script.events.push((60, Command::StrongerDeposits));
+        script.events.push((60, Command::WeakerDeposits));
+        script.events.push((60, Command::Reset));
+        script.events.push((60, Command::ResetParams));
+        script.events.push((60, Command::Preset(Preset::Coral)));
+        script.events.push((60, Command::ToggleShape));
+        script.events.push((61, Command::CycleColor));
+        script
+            .events
+            .push((61, Command::SelectSpecies { index: 2 }));
+        script.events.push((61, Command::ToggleRandomColor));
+        script.events.push((62, Command::ToggleRawView));
+
+        let parsed: InputScript = script.to_string().parse().unwrap();
+        assert_eq!(parsed, script);
+    }
+
+    #[test]
+    fn malformed_script_is_an_error() {
+        assert!("0 spawn 1 2".parse::<InputScript>().is_err());
+        assert!("seed 1\n3 jump".parse::<InputScript>().is_err());
+    }
+}
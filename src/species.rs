@@ -0,0 +1,158 @@
+//! Mixes of species, each a color, for multi-species runs.
+//!
+//! Agents follow trails in their own color (see
+//! `SensorConfig::cross_channel_weight`), so the color an agent spawns in
+//! is its species. A `SpeciesMix` says how many of each to spawn.
+
+use std::str::FromStr;
+
+use rand::Rng;
+
+/// Species with their shares of the population, in the order they were
+/// given. Shares are relative: `70/30` and `0.7/0.3` make the same mix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeciesMix {
+    species: Vec<((u8, u8, u8), f64)>,
+}
+
+impl SpeciesMix {
+    /// A mix of `(color, share)` pairs. Needs at least one species, no
+    /// color twice and every share above zero.
+    pub fn new(species: Vec<((u8, u8, u8), f64)>) -> Result<Self, String> {
+        if species.is_empty() {
+            return Err("a species mix needs at least one species".into());
+        }
+        for (i, &(rgb, share)) in species.iter().enumerate() {
+            if !(share.is_finite() && share > 0.0) {
+                return Err(format!("species share {share} must be above 0"));
+            }
+            if species[..i].iter().any(|&(other, _)| other == rgb) {
+                return Err(format!("species {rgb:?} is given twice"));
+            }
+        }
+        Ok(Self { species })
+    }
+
+    pub fn len(&self) -> usize {
+        self.species.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.species.is_empty()
+    }
+
+    /// The color of species `index`, if there is one.
+    pub fn color(&self, index: usize) -> Option<(u8, u8, u8)> {
+        self.species.get(index).map(|&(rgb, _)| rgb)
+    }
+
+    /// The shares taken as agent counts, when they're all whole numbers.
+    pub fn counts(&self) -> Option<usize> {
+        self.species
+            .iter()
+            .map(|&(_, share)| (share.fract() == 0.0).then_some(share as usize))
+            .sum()
+    }
+
+    /// How many of `total` agents each species gets, in species order:
+    /// their shares rounded so the counts add up to exactly `total`, with
+    /// leftover agents going to the largest remainders first.
+    pub fn split(&self, total: usize) -> Vec<usize> {
+        let sum: f64 = self.species.iter().map(|&(_, share)| share).sum();
+        let exact: Vec<f64> = self
+            .species
+            .iter()
+            .map(|&(_, share)| share / sum * total as f64)
+            .collect();
+        let mut counts: Vec<usize> = exact.iter().map(|&count| count as usize).collect();
+        let mut by_remainder: Vec<usize> = (0..counts.len()).collect();
+        by_remainder.sort_by(|&a, &b| exact[b].fract().total_cmp(&exact[a].fract()));
+        let short = total - counts.iter().sum::<usize>();
+        for &i in by_remainder.iter().cycle().take(short) {
+            counts[i] += 1;
+        }
+        counts
+    }
+
+    /// A species color chosen at random by share.
+    pub fn pick(&self, rng: &mut impl Rng) -> (u8, u8, u8) {
+        let sum: f64 = self.species.iter().map(|&(_, share)| share).sum();
+        let mut at = rng.gen_range(0.0..sum);
+        for &(rgb, share) in &self.species {
+            if at < share {
+                return rgb;
+            }
+            at -= share;
+        }
+        self.species[self.species.len() - 1].0
+    }
+}
+
+/// `#rrggbb=share` pairs separated by commas, the `#` optional.
+impl FromStr for SpeciesMix {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, String> {
+        let species = spec
+            .split(',')
+            .map(|part| {
+                let (color, share) = part
+                    .split_once('=')
+                    .ok_or_else(|| format!("species {part:?} needs the form #rrggbb=share"))?;
+                let hex = color.trim().trim_start_matches('#');
+                let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+                let rgb = match (hex.len(), channel(0), channel(2), channel(4)) {
+                    (6, Some(r), Some(g), Some(b)) => (r, g, b),
+                    _ => return Err(format!("bad species color {color:?}")),
+                };
+                let share = share
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("bad species share {share:?}"))?;
+                Ok((rgb, share))
+            })
+            .collect::<Result<_, String>>()?;
+        Self::new(species)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn splits_hit_the_total_exactly() {
+        let mix: SpeciesMix = "#ff0000=70,0000ff=30".parse().unwrap();
+        assert_eq!(mix.color(1), Some((0, 0, 255)));
+        assert_eq!(mix.counts(), Some(100));
+        assert_eq!(mix.split(1000), [700, 300]);
+        assert_eq!(mix.split(7), [5, 2]);
+
+        let thirds: SpeciesMix = "111111=1,222222=1,333333=1".parse().unwrap();
+        assert_eq!(thirds.split(100), [34, 33, 33]);
+        let ratios: SpeciesMix = "111111=0.5,222222=0.5".parse().unwrap();
+        assert_eq!(ratios.counts(), None);
+    }
+
+    #[test]
+    fn picks_follow_the_shares() {
+        let mix: SpeciesMix = "ff0000=3,0000ff=1".parse().unwrap();
+        let mut rng = StdRng::seed_from_u64(5);
+        let red = (0..4000)
+            .filter(|_| mix.pick(&mut rng) == (255, 0, 0))
+            .count();
+        assert!((2800..3200).contains(&red), "{red} red of 4000");
+    }
+
+    #[test]
+    fn bad_mixes_are_errors() {
+        assert!("".parse::<SpeciesMix>().is_err());
+        assert!("ff0000".parse::<SpeciesMix>().is_err());
+        assert!("ff00=1".parse::<SpeciesMix>().is_err());
+        assert!("ff0000=0".parse::<SpeciesMix>().is_err());
+        assert!("ff0000=-1,00ff00=2".parse::<SpeciesMix>().is_err());
+        assert!("ff0000=1,ff0000=2".parse::<SpeciesMix>().is_err());
+    }
+}
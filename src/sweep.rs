@@ -0,0 +1,225 @@
+//! `--sweep`: run short headless simulations across a range of parameter
+//! values and tile their final frames into one labeled contact sheet.
+//!
+//! Each `--sweep` flag names one `SimParams` field by its dotted path in
+//! the config file's `[params]` table, and the values to try:
+//!
+//! ```text
+//! --sweep sensors.distance=4:16:4     4 evenly spaced values from 4 to 16
+//! --sweep diffusion_rate=1,0.5,0.25   exactly these values
+//! --sweep diffusion_rate.0=0.2:1:5    one element of an array field
+//! ```
+//!
+//! A path to an array field without an index sets every element. The
+//! first sweep runs along the sheet's columns and an optional second one
+//! down its rows. Every run starts from the same seed and parameters.
+
+use std::path::Path;
+
+use serde_json::{Number, Value};
+use z_slime::font;
+use z_slime::image;
+use z_slime::params::SimParams;
+use z_slime::world::World;
+
+const LABEL_SCALE: usize = 2;
+const LABEL_LINE: usize = (font::GLYPH_HEIGHT + 2) * LABEL_SCALE;
+const GAP: usize = 4;
+const SHEET_BACKGROUND: [u8; 4] = [0x20, 0x20, 0x20, 0xff];
+const LABEL_COLOR: [u8; 4] = [0xf0, 0xf0, 0xf0, 0xff];
+
+/// One swept parameter: its path in `SimParams` and the values it takes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep {
+    pub path: String,
+    pub values: Vec<f64>,
+}
+
+impl std::str::FromStr for Sweep {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, String> {
+        let (path, range) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("sweep {spec:?} needs the form name=values"))?;
+        let number = |text: &str| {
+            text.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| format!("bad sweep value {text:?}"))
+        };
+        let values = match range.split(':').collect::<Vec<_>>()[..] {
+            [start, end, count] => {
+                let (start, end) = (number(start)?, number(end)?);
+                let count: usize = count
+                    .parse()
+                    .ok()
+                    .filter(|&count| count > 0)
+                    .ok_or_else(|| format!("bad sweep count {count:?}"))?;
+                let step = if count > 1 {
+                    (end - start) / (count - 1) as f64
+                } else {
+                    0.0
+                };
+                (0..count).map(|i| start + step * i as f64).collect()
+            }
+            [list] => list.split(',').map(number).collect::<Result<_, _>>()?,
+            _ => return Err(format!("bad sweep range {range:?}")),
+        };
+        Ok(Self {
+            path: path.to_string(),
+            values,
+        })
+    }
+}
+
+/// How each run of a sweep is set up.
+pub struct SweepRun<'a> {
+    pub base: &'a SimParams,
+    pub seed: u64,
+    pub agents: usize,
+    pub steps: u64,
+}
+
+/// Run every combination of `sweeps` (one or two of them) and write the
+/// contact sheet to `output`.
+pub fn run(sweeps: &[Sweep], run: &SweepRun, output: &Path) -> Result<(), String> {
+    let (columns, rows) = match sweeps {
+        [columns] => (columns, None),
+        [columns, rows] => (columns, Some(rows)),
+        _ => return Err("give one or two --sweep parameters".into()),
+    };
+    let row_values = rows.map_or(vec![None], |rows| rows.values.iter().map(Some).collect());
+
+    let (tile_width, tile_height) = {
+        let world = World::new_seeded(run.seed);
+        (world.width(), world.height())
+    };
+    let label_height = LABEL_LINE * sweeps.len() + GAP;
+    let cell_width = tile_width + GAP;
+    let cell_height = tile_height + label_height + GAP;
+    let sheet_width = GAP + cell_width * columns.values.len();
+    let sheet_height = GAP + cell_height * row_values.len();
+    let mut sheet: Vec<u8> = SHEET_BACKGROUND.repeat(sheet_width * sheet_height);
+
+    for (row, row_value) in row_values.iter().enumerate() {
+        for (column, &column_value) in columns.values.iter().enumerate() {
+            let mut params = set_param(run.base, &columns.path, column_value)?;
+            let mut labels = vec![label(&columns.path, column_value)];
+            if let (Some(rows), Some(&row_value)) = (rows, row_value) {
+                params = set_param(&params, &rows.path, row_value)?;
+                labels.push(label(&rows.path, row_value));
+            }
+
+            let mut world = World::new_seeded(run.seed);
+            world.params = params;
+            world.spawn_random(run.agents);
+            world.advance(run.steps);
+            let mut frame = vec![0; tile_width * tile_height * 4];
+            world.render_to_buffer(&mut frame);
+
+            let (left, top) = (GAP + column * cell_width, GAP + row * cell_height);
+            for (line, text) in labels.iter().enumerate() {
+                font::draw_text(
+                    &mut sheet,
+                    sheet_width,
+                    (left, top + line * LABEL_LINE),
+                    text,
+                    LABEL_COLOR,
+                    LABEL_SCALE,
+                );
+            }
+            for (y, frame_row) in frame.chunks_exact(tile_width * 4).enumerate() {
+                let start = (left + (top + label_height + y) * sheet_width) * 4;
+                sheet[start..start + frame_row.len()].copy_from_slice(frame_row);
+            }
+            log::info!("Sweep: ran {}", labels.join(", "));
+        }
+    }
+
+    image::save_rgba(output, sheet_width as u32, sheet_height as u32, &sheet)
+        .map_err(|err| format!("{}: {err}", output.display()))
+}
+
+/// `value` under the last part of `path`, short enough to fit over a tile.
+pub fn label(path: &str, value: f64) -> String {
+    let name = path
+        .rsplit('.')
+        .find(|part| part.parse::<usize>().is_err())
+        .unwrap_or(path);
+    format!("{name}={}", (value * 1000.0).round() / 1000.0)
+}
+
+/// `params` with the number at the dotted `path` set to `value`. An array
+/// at the end of the path has every element set.
+pub fn set_param(params: &SimParams, path: &str, value: f64) -> Result<SimParams, String> {
+    let mut tree = serde_json::to_value(params).map_err(|err| err.to_string())?;
+    let mut node = &mut tree;
+    for part in path.split('.') {
+        node = match node {
+            Value::Object(map) => map.get_mut(part),
+            Value::Array(items) => part.parse::<usize>().ok().and_then(|i| items.get_mut(i)),
+            _ => None,
+        }
+        .ok_or_else(|| format!("no parameter {path:?}"))?;
+    }
+    match node {
+        Value::Array(items) => {
+            for item in items {
+                set_number(item, value, path)?;
+            }
+        }
+        node => set_number(node, value, path)?,
+    }
+    serde_json::from_value(tree).map_err(|err| format!("{path} = {value}: {err}"))
+}
+
+/// Overwrite the number `node`, keeping it an integer if it was one.
+fn set_number(node: &mut Value, value: f64, path: &str) -> Result<(), String> {
+    let Value::Number(old) = node else {
+        return Err(format!("{path} isn't a number"));
+    };
+    *node = if old.is_u64() && value >= 0.0 && value.fract() == 0.0 {
+        Value::Number((value as u64).into())
+    } else {
+        Number::from_f64(value)
+            .map(Value::Number)
+            .ok_or_else(|| format!("bad value {value} for {path}"))?
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep_specs_take_ranges_or_lists() {
+        let sweep: Sweep = "sensors.distance=4:16:4".parse().unwrap();
+        assert_eq!(sweep.path, "sensors.distance");
+        assert_eq!(sweep.values, [4.0, 8.0, 12.0, 16.0]);
+        let sweep: Sweep = "diffusion_rate=1,0.5".parse().unwrap();
+        assert_eq!(sweep.values, [1.0, 0.5]);
+        assert!("distance".parse::<Sweep>().is_err());
+        assert!("distance=1:2:0".parse::<Sweep>().is_err());
+        assert!("distance=1:x:3".parse::<Sweep>().is_err());
+    }
+
+    #[test]
+    fn params_are_set_by_path() {
+        let base = SimParams::default();
+        let params = set_param(&base, "sensors.distance", 12.0).unwrap();
+        assert_eq!(params.sensors.distance, 12.0);
+        let params = set_param(&base, "diffusion_rate", 0.5).unwrap();
+        assert_eq!(params.diffusion_rate, [0.5; 3]);
+        let params = set_param(&base, "diffusion_rate.1", 0.25).unwrap();
+        assert_eq!(params.diffusion_rate, [1.0, 0.25, 1.0]);
+        let params = set_param(&base, "deposit_interval", 3.0).unwrap();
+        assert_eq!(params.deposit_interval, 3);
+
+        assert!(set_param(&base, "sensors.range", 1.0).is_err());
+        assert!(set_param(&base, "sensing", 1.0).is_err());
+        assert!(set_param(&base, "deposit_interval", 1.5).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! `--threaded`: step and draw the world on a thread of its own, so slow
+//! updates don't hold up the window and a slow window doesn't hold up the
+//! simulation.
+//!
+//! The simulation thread owns the `World` outright. It steps it at the
+//! fixed rate, draws each new state with its overlays into the back buffer
+//! of a `frame_exchange` and publishes it; the main thread only copies the
+//! latest published frame into `pixels` and presents it. Nothing else is
+//! shared: inputs reach the world as jobs over a channel, which the
+//! simulation thread runs between steps, so a job never sees a world
+//! halfway through an update.
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::{error, info, log_enabled, Level};
+use z_slime::frames::{frame_exchange, FrameReader, FrameWriter};
+use z_slime::overlay;
+use z_slime::script::Replay;
+use z_slime::world::World;
+
+/// What the simulation thread keeps, for jobs to change.
+pub struct SimState {
+    pub world: World,
+    /// Draw the parameter HUD over each frame.
+    pub hud: bool,
+    /// Neither step nor draw, as while the window is unfocused.
+    pub paused: bool,
+    /// Take a single step while paused.
+    pub step_once: bool,
+    replay: Option<Replay>,
+    stopped: bool,
+}
+
+/// Work for the simulation thread to run between steps.
+pub type Job = Box<dyn FnOnce(&mut SimState) + Send>;
+
+/// A running simulation thread and the frames it publishes.
+pub struct SimThread {
+    jobs: Sender<Job>,
+    frames: FrameReader,
+    handle: JoinHandle<()>,
+    replaying: bool,
+}
+
+impl SimThread {
+    /// Move `world` to a new thread that steps it every `time_step`,
+    /// feeding it `replay`'s inputs as they come due.
+    pub fn spawn(world: World, replay: Option<Replay>, time_step: Duration) -> Self {
+        let (jobs, inbox) = mpsc::channel();
+        let (frame_width, frame_height) = world.frame_size();
+        let (writer, frames) = frame_exchange(frame_width * frame_height * 4);
+        let replaying = replay.is_some();
+        let state = SimState {
+            world,
+            hud: false,
+            paused: false,
+            step_once: false,
+            replay,
+            stopped: false,
+        };
+        let handle = std::thread::Builder::new()
+            .name("simulation".into())
+            .spawn(move || run(state, inbox, writer, time_step))
+            .expect("failed to spawn the simulation thread");
+        Self {
+            jobs,
+            frames,
+            handle,
+            replaying,
+        }
+    }
+
+    /// Run `job` on the simulation thread before its next step.
+    pub fn send(&self, job: Job) {
+        // A closed channel means the thread is gone, which `stop` reports.
+        let _ = self.jobs.send(job);
+    }
+
+    /// The frame published since the last call, if there is one.
+    pub fn latest_frame(&mut self) -> Option<&[u8]> {
+        self.frames.latest()
+    }
+
+    /// Whether a replay is driving the world.
+    pub fn replaying(&self) -> bool {
+        self.replaying
+    }
+
+    /// Shut the world down and wait for the thread to finish.
+    pub fn stop(self) {
+        self.send(Box::new(|state| {
+            state.world.shutdown();
+            state.stopped = true;
+        }));
+        if self.handle.join().is_err() {
+            error!("The simulation thread panicked");
+        }
+    }
+}
+
+fn run(mut state: SimState, inbox: Receiver<Job>, mut writer: FrameWriter, time_step: Duration) {
+    let mut next_step = Instant::now();
+    loop {
+        loop {
+            let wait = next_step.saturating_duration_since(Instant::now());
+            match inbox.recv_timeout(wait) {
+                Ok(job) => {
+                    job(&mut state);
+                    if state.stopped {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        // Fall behind by at most one step rather than rushing to catch up.
+        next_step = (next_step + time_step).max(Instant::now());
+        if state.paused && !state.step_once {
+            continue;
+        }
+        let single_step = std::mem::take(&mut state.step_once);
+
+        let world = &mut state.world;
+        if let Some(replay) = &mut state.replay {
+            for command in replay.due(world.step()) {
+                world.apply(command);
+            }
+        }
+        world.update();
+        if single_step {
+            info!("Step {}", world.step());
+        }
+
+        let (frame_width, frame_height) = world.frame_size();
+        writer.resize(frame_width * frame_height * 4);
+        let frame = writer.back_mut();
+        world.draw_blended(frame, 1.0);
+        overlay::draw_stats(world, frame, frame_width);
+        if log_enabled!(Level::Debug) {
+            overlay::draw_diffusion_stats(frame, frame_width, world.diffusion_stats());
+        }
+        if state.hud {
+            overlay::draw_hud(world, frame, frame_width);
+        }
+        writer.publish();
+    }
+}
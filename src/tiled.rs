@@ -0,0 +1,159 @@
+//! Laying several worlds out side by side in one frame, for comparing
+//! variants live.
+
+use crate::font;
+use crate::world::{Rect, World, BACKGROUND};
+
+/// Space left around each tile.
+const GAP: usize = 4;
+/// Height of the label strip over each tile.
+const LABEL_HEIGHT: usize = font::GLYPH_HEIGHT + 3;
+const LABEL_COLOR: [u8; 4] = [0x20, 0x20, 0x20, 0xff];
+/// Label color of the tile that keyboard actions go to.
+const ACTIVE_COLOR: [u8; 4] = [0xd0, 0x60, 0x00, 0xff];
+
+/// Where each of a set of worlds is drawn: a near-square grid of tiles
+/// over part of the frame, each with a label above it.
+#[derive(Debug, Clone)]
+pub struct TileLayout {
+    tiles: Vec<Rect>,
+    labels: Vec<String>,
+}
+
+impl TileLayout {
+    /// Split `area` into one tile per label and scale each world's drawing
+    /// to fit its tile. `worlds` and `labels` pair up by index.
+    pub fn new(worlds: &mut [World], labels: Vec<String>, area: Rect) -> Self {
+        let mut layout = Self {
+            tiles: Vec::new(),
+            labels,
+        };
+        layout.fit(worlds, area);
+        layout
+    }
+
+    /// Split `area` afresh, as when the window changes size, rescaling the
+    /// worlds to their new tiles.
+    pub fn fit(&mut self, worlds: &mut [World], area: Rect) {
+        let count = self.labels.len().max(1);
+        let columns = (1..=count).find(|c| c * c >= count).unwrap_or(1);
+        let rows = count.div_ceil(columns);
+        let (width, height) = (area.width / columns, area.height / rows);
+        self.tiles = (0..self.labels.len())
+            .map(|i| Rect {
+                x: area.x + (i % columns) * width + GAP,
+                y: area.y + (i / columns) * height + GAP + LABEL_HEIGHT,
+                width: width.saturating_sub(2 * GAP),
+                height: height.saturating_sub(2 * GAP + LABEL_HEIGHT),
+            })
+            .collect();
+        for (world, tile) in worlds.iter_mut().zip(&self.tiles) {
+            world.draw_scale = (tile.width as f32 / world.width() as f32)
+                .min(tile.height as f32 / world.height() as f32);
+        }
+    }
+
+    /// The frame rectangle each world is drawn into.
+    pub fn tiles(&self) -> &[Rect] {
+        &self.tiles
+    }
+
+    /// The index of the tile holding frame pixel `(x, y)`, if any.
+    pub fn tile_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.tiles.iter().position(|tile| tile.contains(x, y))
+    }
+
+    /// Frame pixel `(x, y)` moved from tile `index` to where the same spot
+    /// is in `world`'s own single-view layout, which is what the mouse
+    /// commands and `World::screen_to_grid` expect.
+    pub fn to_world_screen(
+        &self,
+        index: usize,
+        world: &World,
+        x: usize,
+        y: usize,
+    ) -> (usize, usize) {
+        let (tile, view) = (self.tiles[index], world.view());
+        (x - tile.x + view.x, y - tile.y + view.y)
+    }
+
+    /// Draw every world into its tile with its label above it, the
+    /// `active` one's label highlighted.
+    pub fn draw(
+        &self,
+        worlds: &mut [World],
+        frame: &mut [u8],
+        frame_width: usize,
+        alpha: f32,
+        active: usize,
+    ) {
+        for pixel in frame.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&BACKGROUND);
+        }
+        for (i, (world, tile)) in worlds.iter_mut().zip(&self.tiles).enumerate() {
+            world.draw_in(frame, frame_width, *tile, alpha);
+            let label_y = tile.y - LABEL_HEIGHT;
+            let color = if i == active {
+                ACTIVE_COLOR
+            } else {
+                LABEL_COLOR
+            };
+            font::draw_text(
+                frame,
+                frame_width,
+                (tile.x, label_y),
+                &self.labels[i],
+                color,
+                1,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Cell;
+
+    #[test]
+    fn tiles_split_the_area_and_route_clicks() {
+        let area = Rect {
+            x: 0,
+            y: 40,
+            width: 800,
+            height: 800,
+        };
+        let mut worlds = vec![World::with_size(50, 50, 0); 3];
+        let labels = ["a", "b", "c"].map(String::from).to_vec();
+        let layout = TileLayout::new(&mut worlds, labels, area);
+
+        // Three worlds take a 2x2 grid, leaving the last tile empty.
+        let tiles = layout.tiles();
+        assert_eq!(tiles.len(), 3);
+        assert_eq!((tiles[1].x, tiles[2].y), (404, 440 + LABEL_HEIGHT + GAP));
+        assert!(worlds.iter().all(|world| world.draw_scale * 50.0 <= 392.0));
+        assert_eq!(layout.tile_at(600, 100), Some(1));
+        assert_eq!(layout.tile_at(600, 600), None);
+        assert_eq!(layout.tile_at(2, 100), None);
+
+        let (x, y) = layout.to_world_screen(1, &worlds[1], tiles[1].x + 21, tiles[1].y + 41);
+        let (cx, cy) = worlds[1].screen_to_grid(x, y).unwrap();
+        worlds[1].set_tile(cx, cy, Cell::Heat(9.0, 8.0, 7.0));
+        let mut frame = vec![0; 800 * 900 * 4];
+        layout.draw(&mut worlds, &mut frame, 800, 1.0, 0);
+        let idx = (tiles[1].x + 21 + (tiles[1].y + 41) * 800) * 4;
+        assert_eq!(frame[idx..idx + 4], [9, 8, 7, 0xff]);
+
+        // A smaller window gets smaller tiles in the same arrangement.
+        let mut layout = layout;
+        layout.fit(
+            &mut worlds,
+            Rect {
+                height: 400,
+                ..area
+            },
+        );
+        assert_eq!(layout.tiles()[2].y, 240 + LABEL_HEIGHT + GAP);
+        assert!(worlds.iter().all(|world| world.draw_scale * 50.0 <= 192.0));
+    }
+}
@@ -0,0 +1,4277 @@
+use crate::agent::{Agent, FieldView, MovementMode, SensorConfig, SpawnMode, TurnMode};
+use crate::grid::{crop, Grid};
+use crate::params::{Preset, SimParams};
+use crate::script::{Command, InputScript};
+use crate::species::SpeciesMix;
+use log::{debug, error, info, warn};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const SCREEN_WIDTH: u32 = 800;
+pub const SCREEN_HEIGHT: u32 = 900;
+pub const CELLS_WIDTH: usize = 300;
+pub const CELLS_HEIGHT: usize = 300;
+pub const CELLS_X: usize = 100;
+pub const CELLS_Y: usize = 200;
+pub const SCALE: f32 = 2.0;
+/// Space under the grid in the default window.
+const CELLS_BOTTOM_MARGIN: usize = 100;
+/// Smallest `draw_scale` that `fit_to` shrinks the grid to.
+const MIN_DRAW_SCALE: f32 = 0.1;
+const ORBIT_ANGULAR_SPEED: f32 = 0.02;
+const ORBIT_INWARD_RATE: f32 = 0.05;
+const GRADUAL_TURN_RATE: f32 = 0.1;
+/// Factor each grow or shrink command changes `agent_scale` by.
+const AGENT_SCALE_STEP: f32 = 1.25;
+const MIN_AGENT_SCALE: f32 = 0.25;
+const MAX_AGENT_SCALE: f32 = 8.0;
+const SPEED_STEP: f32 = 1.25;
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.0;
+/// How much `params.evaporation` moves by per step, and how low it goes.
+const EVAPORATION_STEP: f32 = 0.01;
+const MIN_EVAPORATION: f32 = 0.5;
+const DEPOSIT_STEP: f32 = 1.25;
+const MIN_DEPOSIT_STRENGTH: f32 = 0.1;
+const MAX_DEPOSIT_STRENGTH: f32 = 4.0;
+/// How far, in cells, an agent may stray off the grid before it is culled.
+const CULL_MARGIN: f32 = 2.0;
+/// The hottest a cell's channel gets, drawn at full brightness.
+pub const MAX_HEAT: f32 = 255.0;
+/// Channel value below which a diffused cell is emptied, far under the
+/// smallest step drawing shows, so the whole grid doesn't fill with heat too
+/// faint to see.
+const MIN_HEAT: f32 = 1.0 / 64.0;
+pub const BACKGROUND: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const MARKER_COLOR: [u8; 3] = [0x00, 0x00, 0x00];
+/// Colors `World::cycle_spawn_color` steps through.
+pub const SPAWN_PALETTE: [(u8, u8, u8); 8] = [
+    (230, 40, 40),
+    (240, 140, 30),
+    (240, 220, 40),
+    (60, 200, 60),
+    (40, 200, 210),
+    (50, 80, 230),
+    (160, 60, 220),
+    (240, 90, 170),
+];
+/// How new agents get their colors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorStrategy {
+    /// A uniformly random color each.
+    Random,
+    /// A random point along the gradient running through these colors in
+    /// order, so a run's colors all belong together.
+    PaletteGradient(Vec<(u8, u8, u8)>),
+    /// `World::spawn_color` for every agent.
+    Fixed,
+}
+
+/// How `draw` maps channel values, after exposure, onto brightness.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneCurve {
+    Linear,
+    /// Raise each value, as a fraction of full, to this power; below `1.0`
+    /// lifts faint trails, above it darkens them.
+    Gamma(f32),
+    /// Compress values logarithmically in this base, above `1.0`: the
+    /// larger it is the brighter faint trails come out.
+    Log(f32),
+}
+
+impl ToneCurve {
+    /// `value`, from `0.0` to `255.0`, on this curve.
+    pub fn apply(self, value: f32) -> f32 {
+        let v = (value / 255.0).clamp(0.0, 1.0);
+        match self {
+            ToneCurve::Linear => value,
+            ToneCurve::Gamma(exponent) => 255.0 * v.powf(exponent),
+            ToneCurve::Log(base) if base > 1.0 => 255.0 * (1.0 + (base - 1.0) * v).log(base),
+            ToneCurve::Log(_) => value,
+        }
+    }
+}
+
+/// A small fixed palette for `World::palette`: black, white and six hues.
+pub const RETRO_PALETTE: [(u8, u8, u8); 8] = [
+    (0, 0, 0),
+    (255, 255, 255),
+    (136, 0, 0),
+    (170, 255, 238),
+    (204, 68, 204),
+    (0, 204, 85),
+    (0, 0, 170),
+    (238, 238, 119),
+];
+/// How far, in channel levels, dithering nudges colors before they're
+/// snapped to `World::palette`.
+const PALETTE_DITHER_SPREAD: f32 = 48.0;
+/// Intensities `World::contour_levels` starts with.
+pub const DEFAULT_CONTOUR_LEVELS: [f32; 3] = [64.0, 128.0, 192.0];
+const CONTOUR_COLOR: [u8; 4] = [0xe0, 0x10, 0xa0, 0xff];
+const WALL_COLOR: [u8; 4] = [0x30, 0x30, 0x30, 0xff];
+/// The gradient `RenderMode::Palette` draws intensity along, faint to full.
+const RENDER_GRADIENT: [(u8, u8, u8); 3] = [(0, 0, 0), (0, 0, 255), (255, 255, 255)];
+/// Largest heading change, in radians, between a parent and its offspring.
+const OFFSPRING_MAX_TURN: f32 = 0.5;
+/// Fraction of the way the auto exposure level moves toward each frame's
+/// brightest value, to keep it from flickering.
+const EXPOSURE_SMOOTHING: f32 = 0.1;
+/// Age in updates at which `age_colors` reaches `cool_color`.
+const AGE_SPAN: f32 = 200.0;
+/// Ordered dither thresholds, indexed by screen position modulo 4.
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// How `World::update_tiles` walks the grid.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GridUpdate {
+    /// Diffuse every cell.
+    Dense,
+    /// Only diffuse cells holding heat and their neighbours, falling back to
+    /// a dense pass while more than `max_active_fraction` of the grid is active.
+    Sparse { max_active_fraction: f32 },
+    /// Diffuse every cell in place, in `scan_order`, so each cell already
+    /// sees the new values of the neighbours visited before it. Saves the
+    /// second buffer at the cost of biasing spread against the scan direction.
+    InPlace,
+}
+
+/// Whether `World::update` moves agents and lays their trails down before
+/// or after the step's diffusion pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateOrder {
+    /// Fresh trails are blurred in the same step they're laid, so they
+    /// come out soft straight away.
+    DepositThenDiffuse,
+    /// Fresh trails show at full strength for a step before diffusion
+    /// reaches them, keeping the leading edge of each trail sharp. Agents
+    /// also sense the field after it has spread.
+    DiffuseThenDeposit,
+}
+
+/// The 3x3 filter a diffusion pass averages each cell's neighbourhood
+/// with.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kernel {
+    /// Every neighbour counts the same.
+    Box,
+    /// Neighbours count less the farther they are, by a gaussian of
+    /// `sigma` cells.
+    Gaussian { sigma: f32 },
+    /// Like `Gaussian` with a `spatial_sigma`, and neighbours also count
+    /// less the further their intensity is from the cell's own, by a
+    /// gaussian of `intensity_sigma` levels. Smooths within trails and
+    /// within the background without smearing one into the other, so
+    /// filaments stay crisp. Costs nine `exp` calls per cell on top of the
+    /// others' work, making passes several times slower than `Box`.
+    Bilateral {
+        spatial_sigma: f32,
+        intensity_sigma: f32,
+    },
+}
+
+/// The order an in-place `update_tiles` pass visits cells in.
+///
+/// `GridUpdate::Dense` and `GridUpdate::Sparse` read only the previous
+/// step's values, so every order would give the same result there and they
+/// don't follow one; only `GridUpdate::InPlace` depends on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScanOrder {
+    /// Row by row, each left to right.
+    Raster,
+    /// Row by row, alternating direction, so left-right bias cancels out
+    /// between rows.
+    Boustrophedon,
+    /// A fresh permutation every pass, drawn from the world's RNG, so no
+    /// direction is favoured.
+    Random,
+}
+
+/// How `World::blend_into` combines two worlds' pixels, channel by channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Sum of both, saturating at full brightness.
+    Add,
+    /// The brighter of the two.
+    Max,
+    /// Inverted product of the inverses: always at least as bright as
+    /// either, without saturating the way `Add` does.
+    Screen,
+}
+
+impl BlendMode {
+    fn blend(self, a: u8, b: u8) -> u8 {
+        match self {
+            BlendMode::Add => a.saturating_add(b),
+            BlendMode::Max => a.max(b),
+            BlendMode::Screen => 255 - ((255 - a as u16) * (255 - b as u16) / 255) as u8,
+        }
+    }
+}
+
+/// Outline of the region the simulation runs in. Cells outside it are walls:
+/// they hold no heat, draw as background and agents bounce off them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    /// The whole grid.
+    Rect,
+    /// The largest disc centered on the grid.
+    Disc,
+}
+
+/// How `World::draw` marks agent positions on top of the trails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentMarkers {
+    Off,
+    /// One pixel at the agent's rounded screen position.
+    Dot,
+    /// The marker's color spread over the 2x2 pixels it overlaps, weighted by
+    /// the agent's fractional position, so slow agents glide instead of jumping.
+    Smooth,
+}
+
+/// How `World::draw` colors cells holding heat. Empty cells are drawn in
+/// `BACKGROUND` in every mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Each cell in its own channels, or as `fade_colors` and `age_colors`
+    /// say.
+    Rgb,
+    /// A gray as bright as the cell's intensity.
+    Grayscale,
+    /// The cell's intensity along `RENDER_GRADIENT`, black to blue to white.
+    Palette,
+}
+
+#[derive(Clone)]
+pub struct World {
+    width: usize,
+    height: usize,
+    pub draw_scale: f32,
+    /// The configured `draw_scale`, held while the raw 1:1 view is on.
+    scaled_view: Option<f32>,
+    /// Where the grid's top left corner is drawn in the window's frame.
+    origin: (usize, usize),
+    /// Width and height of the window's frame that `draw` fills.
+    frame_size: (usize, usize),
+    tiles: Grid<Cell>,
+    /// Updates since each cell's heat was deposited, blended by intensity as
+    /// it diffuses. `0.0` for empty cells.
+    ages: Vec<f32>,
+    /// What a dense pass writes into before swapping them with `tiles` and
+    /// `ages`, kept between passes to save allocating them every update.
+    back_tiles: Vec<Cell>,
+    back_ages: Vec<f32>,
+    agents: Vec<Agent>,
+    /// The id last given to an agent; ids start at `1`.
+    last_agent_id: u64,
+    pub params: SimParams,
+    shape: Shape,
+    /// `true` for cells inside `shape`.
+    mask: Vec<bool>,
+    /// What `reset_params` restores.
+    pub defaults: SimParams,
+    /// The preset last applied with `set_preset`, if any.
+    preset: Option<Preset>,
+    /// Color new agents get with `ColorStrategy::Fixed`.
+    pub spawn_color: (u8, u8, u8),
+    pub color_strategy: ColorStrategy,
+    /// Species new agents belong to, taking over from `color_strategy`
+    /// while set: scatters split exactly by share, other spawns pick at
+    /// random by share unless a species is selected.
+    pub species: Option<SpeciesMix>,
+    /// The species `select_species` last picked for spawns.
+    selected_species: Option<usize>,
+    palette_index: usize,
+    /// Draw trails on a gradient from `cool_color` (faint) to `hot_color`
+    /// (full intensity) instead of in their deposited colors.
+    pub fade_colors: bool,
+    /// Draw trails by age instead, from `hot_color` where freshly laid down
+    /// to `cool_color` once `AGE_SPAN` updates old.
+    pub age_colors: bool,
+    /// Stretch colors each frame so the brightest channel on the grid,
+    /// smoothed over recent frames, is drawn at full brightness.
+    pub auto_exposure: bool,
+    /// Break up banding in drawn gradients with a 4x4 ordered dither,
+    /// trading a little grain for smoother-looking fades. Only affects `draw`.
+    pub dither: bool,
+    /// Draw every grid pixel in the nearest of these colors, for a retro
+    /// look. The simulation keeps its full range; only `draw` is affected.
+    pub palette: Option<Vec<(u8, u8, u8)>>,
+    /// The smoothed brightest channel value `auto_exposure` scales to.
+    exposure: Option<f32>,
+    /// Applied to channel values on top of any exposure, to bring out faint
+    /// trails next to bright ones. Only affects `draw`.
+    pub tone_curve: ToneCurve,
+    pub hot_color: (u8, u8, u8),
+    pub cool_color: (u8, u8, u8),
+    pub agent_markers: AgentMarkers,
+    /// Anything but `RenderMode::Rgb` takes over from `fade_colors` and
+    /// `age_colors`. Only affects `draw`.
+    pub render_mode: RenderMode,
+    /// Skip the trail map in `draw`, leaving just agent markers (dots, if
+    /// markers are off) on the background.
+    pub agents_only: bool,
+    /// Draw iso-intensity lines over the trail map where cell intensity
+    /// crosses one of `contour_levels`. Only affects `draw`.
+    pub contours: bool,
+    /// Mean channel values, from 0 to 255, that `contours` traces.
+    pub contour_levels: Vec<f32>,
+    sparse: SparseState,
+    diffusion_stats: DiffusionStats,
+    /// Keep adding up `diffusion_stats` over the whole run instead of
+    /// starting them over on each `update_tiles`.
+    pub cumulative_stats: bool,
+    /// The block-averaged trail map agents sense from when
+    /// `params.sensors.downsample` is above `1`.
+    sense_field: Vec<Cell>,
+    /// A static field agents steer toward while sensing, one value per cell.
+    attractor_field: Option<Vec<f32>>,
+    /// Per-cell `params.evaporation`, one value per cell.
+    decay_map: Option<Vec<f32>>,
+    /// Cells agents bounce off and heat never enters, one flag per cell.
+    walls: Option<Vec<bool>>,
+    /// Cells reset to full heat in their color after every diffusion pass,
+    /// as `(index, rgb)`.
+    sources: Vec<(usize, (u8, u8, u8))>,
+    seed: u64,
+    rng: StdRng,
+    step: u64,
+    recording: Option<Recording>,
+    /// Where `shutdown` saves the whole simulation, if anywhere.
+    autosave: Option<PathBuf>,
+    events: EventHook,
+}
+
+/// The state saved in `path`, if it is whole and consistent.
+fn read_state(path: &Path) -> io::Result<SavedState> {
+    let file = io::BufReader::new(fs::File::open(path)?);
+    let state: SavedState = serde_json::from_reader(file).map_err(io::Error::from)?;
+    let Some(len) = state.width.checked_mul(state.height).filter(|&len| len > 0) else {
+        return Err(invalid_state(format!(
+            "bad grid size {}x{}",
+            state.width, state.height
+        )));
+    };
+    if state.heat.len() != len * 3 || state.ages.len() != len {
+        return Err(invalid_state(format!(
+            "{} heat values and {} ages for a {}x{} grid",
+            state.heat.len(),
+            state.ages.len(),
+            state.width,
+            state.height
+        )));
+    }
+    if let Some(heat) = state
+        .heat
+        .iter()
+        .find(|heat| !(0.0..=MAX_HEAT).contains(*heat))
+    {
+        return Err(invalid_state(format!("heat {heat} is out of range")));
+    }
+    if state
+        .agents
+        .iter()
+        .any(|agent| agent.id() > state.last_agent_id)
+    {
+        return Err(invalid_state(
+            "an agent's id is past the last one given out".into(),
+        ));
+    }
+    Ok(state)
+}
+
+fn invalid_state(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A rectangle of a frame, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+}
+
+/// How often diffusion hit its edge cases, for checking in the field that
+/// it behaves as intended. See `World::diffusion_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffusionStats {
+    /// Cells whose 3x3 neighbourhood was cut short by the grid edge or the
+    /// shape.
+    pub edge_clamped: u64,
+    /// Channels that came out above 255 and were clamped to it.
+    pub saturated: u64,
+    /// Cells holding heat that spread too thin to keep.
+    pub underflowed: u64,
+}
+
+impl DiffusionStats {
+    fn add(&mut self, other: DiffusionStats) {
+        self.edge_clamped += other.edge_clamped;
+        self.saturated += other.saturated;
+        self.underflowed += other.underflowed;
+    }
+}
+
+/// How well trails join a set of sources. See `World::network_metrics`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NetworkMetrics {
+    /// Fraction of source pairs joined by a path along the trails; `1.0`
+    /// with fewer than two sources.
+    pub connectivity: f32,
+    /// Length in cells of the shortest trail paths needed to join every
+    /// connected pair, through other sources where that is shorter. A
+    /// rough measure of how much network the connections cost.
+    pub total_length: f32,
+}
+
+/// A distance and cell on the Dijkstra queue in `World::trail_distances`,
+/// ordered by distance.
+#[derive(PartialEq)]
+struct PathStep(f32, usize);
+
+impl Eq for PathStep {}
+
+impl PartialOrd for PathStep {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PathStep {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// Something that happened to an agent, reported to the callback set with
+/// `World::on_event`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimEvent {
+    /// Agent `id` was added at `(x, y)` by a click, a scatter or
+    /// reproduction.
+    Spawn {
+        id: u64,
+        x: f32,
+        y: f32,
+        rgb: (u8, u8, u8),
+    },
+    /// Agent `id` had wandered off the grid to `(x, y)` and was removed.
+    Cull {
+        id: u64,
+        x: f32,
+        y: f32,
+        rgb: (u8, u8, u8),
+    },
+    /// Agent `id` was at `(x, y)` when a reset, a restore or a resize down
+    /// to no grid at all removed it.
+    Death {
+        id: u64,
+        x: f32,
+        y: f32,
+        rgb: (u8, u8, u8),
+    },
+}
+
+/// The optional `SimEvent` callback. A cloned world starts without one, as a
+/// callback can't be shared between worlds.
+#[derive(Default)]
+struct EventHook(Option<Box<dyn FnMut(SimEvent) + Send + Sync>>);
+
+impl EventHook {
+    fn emit(&mut self, event: SimEvent) {
+        if let Some(callback) = &mut self.0 {
+            callback(event);
+        }
+    }
+}
+
+impl Clone for EventHook {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+/// What `World::save` writes: the grid, its heat and every agent, with the
+/// parameters they were running under.
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    width: usize,
+    height: usize,
+    seed: u64,
+    step: u64,
+    params: SimParams,
+    /// Each cell's channels in row-major order, `0.0, 0.0, 0.0` for empty
+    /// ones.
+    heat: Vec<f32>,
+    ages: Vec<f32>,
+    agents: Vec<Agent>,
+    last_agent_id: u64,
+}
+
+/// An input script being recorded, and where `World::shutdown` writes it.
+#[derive(Clone)]
+struct Recording {
+    path: PathBuf,
+    script: InputScript,
+}
+
+/// Bookkeeping for `GridUpdate::Sparse`, kept up to date in every mode so
+/// switching modes never needs a rescan.
+#[derive(Clone)]
+struct SparseState {
+    /// Indices of every non-empty cell.
+    active: Vec<usize>,
+    active_mask: Vec<bool>,
+    /// Scratch space for the cells a sparse pass needs to diffuse.
+    candidates: Vec<usize>,
+    candidate_mask: Vec<bool>,
+    updates: Vec<(usize, Cell, f32)>,
+}
+
+impl SparseState {
+    fn new(len: usize) -> Self {
+        Self {
+            active: Vec::new(),
+            active_mask: vec![false; len],
+            candidates: Vec::new(),
+            candidate_mask: vec![false; len],
+            updates: Vec::new(),
+        }
+    }
+
+    fn activate(&mut self, idx: usize) {
+        if !self.active_mask[idx] {
+            self.active_mask[idx] = true;
+            self.active.push(idx);
+        }
+    }
+
+    fn clear_active(&mut self) {
+        for &idx in &self.active {
+            self.active_mask[idx] = false;
+        }
+        self.active.clear();
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl World {
+    /// Create a new `World` instance that can draw a moving box.
+    pub fn new() -> Self {
+        Self::new_seeded(rand::random())
+    }
+
+    /// Create a `World` whose random choices are all driven by `seed`, so the
+    /// same seed and inputs reproduce the same run.
+    pub fn new_seeded(seed: u64) -> Self {
+        Self::with_size(CELLS_WIDTH, CELLS_HEIGHT, seed)
+    }
+
+    /// A seeded world with a `width` x `height` grid.
+    pub fn with_size(width: usize, height: usize, seed: u64) -> Self {
+        let len = width.checked_mul(height).expect("overflow");
+        Self {
+            width,
+            height,
+            draw_scale: SCALE,
+            scaled_view: None,
+            origin: (CELLS_X, CELLS_Y),
+            frame_size: (SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize),
+            tiles: Grid::new(width, height, Cell::Empty),
+            ages: vec![0.0; len],
+            back_tiles: Vec::new(),
+            back_ages: Vec::new(),
+            agents: Vec::new(),
+            last_agent_id: 0,
+            params: SimParams::default(),
+            shape: Shape::Rect,
+            mask: vec![true; len],
+            defaults: SimParams::default(),
+            preset: None,
+            spawn_color: SPAWN_PALETTE[0],
+            color_strategy: ColorStrategy::Random,
+            species: None,
+            selected_species: None,
+            palette_index: 0,
+            fade_colors: false,
+            age_colors: false,
+            auto_exposure: false,
+            dither: false,
+            palette: None,
+            exposure: None,
+            tone_curve: ToneCurve::Linear,
+            hot_color: (255, 236, 120),
+            cool_color: (40, 60, 170),
+            agent_markers: AgentMarkers::Off,
+            render_mode: RenderMode::Rgb,
+            agents_only: false,
+            contours: false,
+            contour_levels: DEFAULT_CONTOUR_LEVELS.to_vec(),
+            sparse: SparseState::new(len),
+            diffusion_stats: DiffusionStats::default(),
+            cumulative_stats: false,
+            sense_field: Vec::new(),
+            attractor_field: None,
+            decay_map: None,
+            walls: None,
+            sources: Vec::new(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            step: 0,
+            recording: None,
+            autosave: None,
+            events: EventHook::default(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Number of completed `update` calls.
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    /// Start recording every applied `Command` into a script for this seed,
+    /// to be written to `path` on `shutdown`.
+    pub fn start_recording(&mut self, path: impl Into<PathBuf>) {
+        self.recording = Some(Recording {
+            path: path.into(),
+            script: InputScript::new(self.seed),
+        });
+    }
+
+    /// Stop recording and hand back what was recorded, if anything.
+    pub fn take_recording(&mut self) -> Option<InputScript> {
+        self.recording.take().map(|recording| recording.script)
+    }
+
+    /// Save the whole simulation to `path` on `shutdown`, as `save` writes
+    /// it, so the next run can pick up where this one stopped.
+    pub fn autosave_to(&mut self, path: impl Into<PathBuf>) {
+        self.autosave = Some(path.into());
+    }
+
+    /// Finish everything that writes output before the app exits. Call this
+    /// instead of just dropping the world so no output is left half written.
+    ///
+    /// Currently this writes out the input recording and the autosave, if
+    /// either was asked for.
+    pub fn shutdown(&mut self) {
+        if let Some(Recording { path, script }) = self.recording.take() {
+            match script.save(&path) {
+                Ok(()) => info!("Saved input script to {}", path.display()),
+                Err(err) => error!("Saving input script to {} failed: {err}", path.display()),
+            }
+        }
+        if let Some(path) = self.autosave.take() {
+            match self.save(&path) {
+                Ok(()) => info!("Saved the simulation to {}", path.display()),
+                Err(err) => error!("Saving the simulation to {} failed: {err}", path.display()),
+            }
+        }
+    }
+
+    /// Call `callback` with every `SimEvent` from now on, replacing any
+    /// earlier callback. It has to be `Send` so the world can move to a
+    /// simulation thread.
+    pub fn on_event(&mut self, callback: impl FnMut(SimEvent) + Send + Sync + 'static) {
+        self.events = EventHook(Some(Box::new(callback)));
+    }
+
+    /// Apply an input, recording it against the current step if recording.
+    pub fn apply(&mut self, command: Command) {
+        if let Some(recording) = &mut self.recording {
+            recording.script.events.push((self.step, command));
+        }
+        match command {
+            Command::Spawn { x, y } => self.mouse_action(x, y),
+            Command::Scatter { count } => self.spawn_random(count),
+            Command::Brush { x, y } => {
+                if self.mouse_inside_world(x, y) {
+                    let col = (x as f32 - self.origin.0 as f32) / self.draw_scale;
+                    let row = (y as f32 - self.origin.1 as f32) / self.draw_scale;
+                    let (radius, count) = (self.params.brush_radius, self.params.brush_count);
+                    self.spawn_brush(col, row, radius, count);
+                }
+            }
+            Command::Flood { x, y } => {
+                if let Some((col, row)) = self.screen_to_grid(x.max(0) as usize, y.max(0) as usize)
+                {
+                    self.flood_spawn(col, row);
+                }
+            }
+            Command::Source { x, y } => {
+                if let Some((col, row)) = self.screen_to_grid(x.max(0) as usize, y.max(0) as usize)
+                {
+                    let rgb = self.next_spawn_color();
+                    self.add_source(col, row, rgb);
+                }
+            }
+            Command::PickColor { x, y } => {
+                if let Some((col, row)) = self.screen_to_grid(x.max(0) as usize, y.max(0) as usize)
+                {
+                    self.pick_color(col, row);
+                }
+            }
+            Command::ToggleOrbit => self.toggle_orbit(),
+            Command::ToggleSensing => self.params.sensing = !self.params.sensing,
+            Command::ToggleTurnMode => self.toggle_turn_mode(),
+            Command::GrowAgents => self.scale_agents(AGENT_SCALE_STEP),
+            Command::ShrinkAgents => self.scale_agents(1.0 / AGENT_SCALE_STEP),
+            Command::Faster => self.scale_speed(SPEED_STEP),
+            Command::Slower => self.scale_speed(1.0 / SPEED_STEP),
+            Command::LongerTrails => self.step_evaporation(EVAPORATION_STEP),
+            Command::ShorterTrails => self.step_evaporation(-EVAPORATION_STEP),
+            Command::StrongerDeposits => self.scale_deposits(DEPOSIT_STEP),
+            Command::WeakerDeposits => self.scale_deposits(1.0 / DEPOSIT_STEP),
+            Command::FlipChemotaxis => {
+                self.params.sensors.chemotaxis = -self.params.sensors.chemotaxis
+            }
+            Command::Reset => self.reset(),
+            Command::ResetParams => self.reset_params(),
+            Command::Preset(preset) => self.set_preset(preset),
+            Command::ToggleShape => self.set_shape(match self.shape {
+                Shape::Rect => Shape::Disc,
+                Shape::Disc => Shape::Rect,
+            }),
+            Command::CycleColor => self.cycle_spawn_color(),
+            Command::SelectSpecies { index } => self.select_species(index),
+            Command::ToggleRandomColor => {
+                self.color_strategy = match self.color_strategy {
+                    ColorStrategy::Random => ColorStrategy::Fixed,
+                    _ => ColorStrategy::Random,
+                }
+            }
+            Command::ToggleRawView => self.toggle_raw_view(),
+        }
+    }
+
+    pub fn agents(&self) -> &[Agent] {
+        &self.agents
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    /// Restrict the simulation to `shape`, clearing any heat left outside it.
+    pub fn set_shape(&mut self, shape: Shape) {
+        self.shape = shape;
+        for idx in 0..self.tiles.len() {
+            let (x, y) = self.tiles.coords(idx);
+            self.mask[idx] =
+                self.shape_contains(x as f32 + 0.5, y as f32 + 0.5) && !self.is_wall(idx);
+            if !self.mask[idx] {
+                self.tiles[idx] = Cell::Empty;
+                self.ages[idx] = 0.0;
+            }
+        }
+    }
+
+    /// Change the grid to `width` x `height` cells, keeping the overlap of
+    /// the old grid anchored at the top left and leaving any new cells
+    /// empty. Agents outside the new bounds are pulled back to its edge, or
+    /// dropped if the grid is now empty.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let len = width.checked_mul(height).expect("overflow");
+        let old = (self.width, self.height);
+        self.tiles.resize(width, height, Cell::Empty);
+        self.ages = crop(&self.ages, old, (width, height), 0.0);
+        self.attractor_field = self
+            .attractor_field
+            .as_ref()
+            .map(|field| crop(field, old, (width, height), 0.0));
+        self.walls = self
+            .walls
+            .as_ref()
+            .map(|walls| crop(walls, old, (width, height), false));
+        self.decay_map = self.decay_map.as_ref().map(|map| {
+            crop(
+                map,
+                old,
+                (width, height),
+                self.params.evaporation.clamp(f32::MIN_POSITIVE, 1.0),
+            )
+        });
+        self.sources = std::mem::take(&mut self.sources)
+            .into_iter()
+            .filter_map(|(idx, rgb)| {
+                let (x, y) = (idx % old.0, idx / old.0);
+                (x < width && y < height).then_some((x + y * width, rgb))
+            })
+            .collect();
+        self.width = width;
+        self.height = height;
+        self.mask = vec![true; len];
+        self.set_shape(self.shape);
+        self.sense_field.clear();
+        self.exposure = None;
+        self.sparse = SparseState::new(len);
+        self.rebuild_active();
+
+        if len == 0 {
+            self.clear_agents();
+        }
+        let (max_x, max_y) = (width as f32 - 1.0, height as f32 - 1.0);
+        for agent in &mut self.agents {
+            agent.x = agent.x.clamp(0.0, max_x);
+            agent.y = agent.y.clamp(0.0, max_y);
+            agent.remember_position();
+        }
+        info!("Resized grid to {width}x{height}");
+    }
+
+    /// Whether the point `(x, y)`, in cell coordinates, lies inside the shape.
+    fn shape_contains(&self, x: f32, y: f32) -> bool {
+        match self.shape {
+            Shape::Rect => true,
+            Shape::Disc => {
+                let (cx, cy) = self.center();
+                let radius = cx.min(cy);
+                (x - cx).powi(2) + (y - cy).powi(2) <= radius * radius
+            }
+        }
+    }
+
+    /// Set the attractor field agents steer toward while sensing, one value
+    /// per cell in row-major order, or `None` to turn it off.
+    pub fn set_attractor_field(&mut self, field: Option<Vec<f32>>) -> Result<(), String> {
+        if let Some(len) = field.as_ref().map(Vec::len) {
+            if len != self.tiles.len() {
+                return Err(format!(
+                    "attractor field has {len} values, the {}x{} grid needs {}",
+                    self.width,
+                    self.height,
+                    self.tiles.len()
+                ));
+            }
+        }
+        self.attractor_field = field;
+        Ok(())
+    }
+
+    pub fn decay_map(&self) -> Option<&[f32]> {
+        self.decay_map.as_deref()
+    }
+
+    /// Set the fraction of heat each cell keeps per diffusion pass, one
+    /// value in `(0, 1]` per cell in row-major order, or `None` to go back
+    /// to `params.evaporation` everywhere.
+    pub fn set_decay_map(&mut self, map: Option<Vec<f32>>) -> Result<(), String> {
+        if let Some(map) = &map {
+            if map.len() != self.tiles.len() {
+                return Err(format!(
+                    "decay map has {} values, the {}x{} grid needs {}",
+                    map.len(),
+                    self.width,
+                    self.height,
+                    self.tiles.len()
+                ));
+            }
+            if let Some(bad) = map.iter().find(|&&keep| !(keep > 0.0 && keep <= 1.0)) {
+                return Err(format!("decay map value {bad} is outside (0, 1]"));
+            }
+        }
+        self.decay_map = map;
+        Ok(())
+    }
+
+    pub fn walls(&self) -> Option<&[bool]> {
+        self.walls.as_deref()
+    }
+
+    /// Wall off cells, one flag per cell in row-major order, or `None` to
+    /// take every wall down. Heat already on a new wall is cleared.
+    pub fn set_walls(&mut self, walls: Option<Vec<bool>>) -> Result<(), String> {
+        if let Some(len) = walls.as_ref().map(Vec::len) {
+            if len != self.tiles.len() {
+                return Err(format!(
+                    "wall map has {len} values, the {}x{} grid needs {}",
+                    self.width,
+                    self.height,
+                    self.tiles.len()
+                ));
+            }
+        }
+        self.walls = walls;
+        self.set_shape(self.shape);
+        Ok(())
+    }
+
+    fn is_wall(&self, idx: usize) -> bool {
+        self.walls.as_ref().is_some_and(|walls| walls[idx])
+    }
+
+    /// Whether the point `(x, y)`, in cell coordinates, is in a wall cell.
+    /// Walls at the edge carry on past it, so agents that bounce a little
+    /// way off the grid can't slip around their ends.
+    fn walled(&self, x: f32, y: f32) -> bool {
+        let col = (x.max(0.0) as usize).min(self.width.saturating_sub(1));
+        let row = (y.max(0.0) as usize).min(self.height.saturating_sub(1));
+        self.tiles.contains(col, row) && self.is_wall(self.tiles.index(col, row))
+    }
+
+    /// The first point of a move from `prev` to `next` that is in a wall
+    /// cell, checking halfway along too so that agents turned onto a
+    /// diagonal, and so moving more than a cell at a time on one axis, can't
+    /// step over a wall one cell thick.
+    fn wall_hit(&self, prev: (f32, f32), next: (f32, f32)) -> Option<(f32, f32)> {
+        let halfway = ((prev.0 + next.0) / 2.0, (prev.1 + next.1) / 2.0);
+        [halfway, next]
+            .into_iter()
+            .find(|&(x, y)| self.walled(x, y))
+    }
+
+    /// Read-only view of the grid, row-major.
+    pub fn tiles(&self) -> &[Cell] {
+        &self.tiles
+    }
+
+    /// Overwrite the cell at `(x, y)`.
+    pub fn set_tile(&mut self, x: usize, y: usize, cell: Cell) {
+        let idx = self.tiles.index(x, y);
+        self.tiles[idx] = cell;
+        self.ages[idx] = 0.0;
+        if cell != Cell::Empty {
+            self.sparse.activate(idx);
+        }
+    }
+
+    /// Pin the cell at `(x, y)` as a heat source: it's set to `rgb` now and
+    /// after every diffusion pass, continuously feeding the field around it.
+    /// A source already there takes on the new color.
+    pub fn add_source(&mut self, x: usize, y: usize, rgb: (u8, u8, u8)) {
+        let idx = self.tiles.index(x, y);
+        self.sources.retain(|&(source, _)| source != idx);
+        self.sources.push((idx, rgb));
+        self.refresh_sources();
+    }
+
+    /// Set every source inside the shape back to full heat.
+    fn refresh_sources(&mut self) {
+        for &(idx, (r, g, b)) in &self.sources {
+            if self.mask[idx] {
+                self.tiles[idx] = Cell::from_rgb((r, g, b));
+                self.ages[idx] = 0.0;
+                self.sparse.activate(idx);
+            }
+        }
+    }
+
+    /// The cell containing the point `(x, y)`, if it is on the grid.
+    pub fn cell_at(&self, x: f32, y: f32) -> Option<Cell> {
+        self.tiles.at_point(x, y).copied()
+    }
+
+    /// Sum of every channel of every heat cell.
+    pub fn total_intensity(&self) -> f64 {
+        self.tiles
+            .iter()
+            .map(|tile| match *tile {
+                Cell::Empty => 0.0,
+                Cell::Heat(r, g, b) => r as f64 + g as f64 + b as f64,
+            })
+            .sum()
+    }
+
+    /// Center of the grid in cell coordinates.
+    fn center(&self) -> (f32, f32) {
+        (self.width as f32 / 2.0, self.height as f32 / 2.0)
+    }
+
+    pub fn mouse_inside_world(&self, x: i16, y: i16) -> bool {
+        let view = self.view();
+        let (x, y) = (x as i32, y as i32);
+        let (left, top) = (view.x as i32, view.y as i32);
+        x > left && x < left + view.width as i32 && y > top && y < top + view.height as i32
+    }
+
+    /// The grid cell drawn at screen pixel `(x, y)`, matching `draw`'s
+    /// mapping, or `None` outside the grid.
+    pub fn screen_to_grid(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let (left, top) = self.origin;
+        if x <= left || y <= top {
+            return None;
+        }
+        let col = ((x - left) as f32 / self.draw_scale) as usize;
+        let row = ((y - top) as f32 / self.draw_scale) as usize;
+        (col < self.width && row < self.height).then_some((col, row))
+    }
+
+    /// Spawn an agent under screen pixel `(x, y)`, if that's on the grid.
+    /// Agents live in cell coordinates, so the pixel is mapped back through
+    /// `draw`'s offset and scale first.
+    pub fn mouse_action(&mut self, x: i16, y: i16) {
+        let inside_cells = self.mouse_inside_world(x, y);
+        if inside_cells {
+            let col = (x as f32 - self.origin.0 as f32) / self.draw_scale;
+            let row = (y as f32 - self.origin.1 as f32) / self.draw_scale;
+            self.spawn_agent(col, row);
+        }
+    }
+
+    /// Spawn up to `count` agents like `spawn_agent` at random points within
+    /// `radius` cells of `(x, y)`, leaving out any that land off the grid or
+    /// outside the shape.
+    pub fn spawn_brush(&mut self, x: f32, y: f32, radius: f32, count: usize) {
+        for _ in 0..count {
+            let angle = self.rng.gen_range(0.0..std::f32::consts::TAU);
+            let distance = radius.max(0.0) * self.rng.gen::<f32>().sqrt();
+            let (px, py) = (x + distance * angle.cos(), y + distance * angle.sin());
+            if px < 0.0 || py < 0.0 {
+                continue;
+            }
+            let (col, row) = (px as usize, py as usize);
+            if self.tiles.contains(col, row) && self.mask[self.tiles.index(col, row)] {
+                self.spawn_agent(px, py);
+            }
+        }
+    }
+
+    /// Spawn an agent at `(x, y)` in the current spawn color and mode,
+    /// unless the population is already at `max_agents`.
+    pub fn spawn_agent(&mut self, x: f32, y: f32) {
+        if self.agents.len() >= self.params.max_agents {
+            return;
+        }
+        let rgb = self.next_spawn_color();
+        let agent = match self.params.spawn_mode {
+            SpawnMode::Bounce => Agent::new(x, y, rgb, &mut self.rng),
+            SpawnMode::Orbit {
+                angular_speed,
+                inward_rate,
+            } => Agent::orbiting(x, y, rgb, self.center(), angular_speed, inward_rate),
+        };
+        self.push_agent(agent);
+    }
+
+    /// Scatter up to `count` agents over random cells inside the shape,
+    /// heading in random directions, without going over `max_agents`. With
+    /// a species mix, each species gets its exact share of them. Places
+    /// none, with a warning, when walls or the shape leave no cell open.
+    pub fn spawn_random(&mut self, count: usize) {
+        let count = count.min(self.params.max_agents.saturating_sub(self.agents.len()));
+        let mut colors: Vec<_> = match &self.species {
+            Some(mix) => mix
+                .split(count)
+                .into_iter()
+                .enumerate()
+                .flat_map(|(i, n)| std::iter::repeat_n(mix.color(i).unwrap_or_default(), n))
+                .collect(),
+            None => Vec::new(),
+        };
+        colors.shuffle(&mut self.rng);
+        // Cells whose middle is inside the shape and not walled off.
+        let open: Vec<(usize, usize)> = (0..self.tiles.len())
+            .map(|idx| self.tiles.coords(idx))
+            .filter(|&(col, row)| {
+                let (x, y) = (col as f32 + 0.5, row as f32 + 0.5);
+                self.shape_contains(x, y) && !self.walled(x, y)
+            })
+            .collect();
+        if open.is_empty() {
+            if count > 0 {
+                warn!("No open cells to scatter {count} agents over");
+            }
+            return;
+        }
+        for i in 0..count {
+            let (col, row) = open[self.rng.gen_range(0..open.len())];
+            let (x, y) = (
+                col as f32 + self.rng.gen::<f32>(),
+                row as f32 + self.rng.gen::<f32>(),
+            );
+            // A cell on the disc's rim may be only partly inside it.
+            let (x, y) = if self.shape_contains(x, y) {
+                (x, y)
+            } else {
+                (col as f32 + 0.5, row as f32 + 0.5)
+            };
+            let rgb = match colors.get(i) {
+                Some(&rgb) => rgb,
+                None => self.next_spawn_color(),
+            };
+            self.spawn_heading_anywhere(x, y, rgb);
+        }
+    }
+
+    /// Fill the contiguous empty region around cell `(col, row)`, bounded by
+    /// walls, trails and the grid edges, with agents at `params.flood_density`
+    /// per cell, without going over `max_agents`.
+    pub fn flood_spawn(&mut self, col: usize, row: usize) {
+        let region = self.empty_region(col, row);
+        let wanted = (region.len() as f32 * self.params.flood_density).round() as usize;
+        let count = wanted.min(self.params.max_agents.saturating_sub(self.agents.len()));
+        for idx in rand::seq::index::sample(&mut self.rng, region.len(), count) {
+            let (col, row) = self.tiles.coords(region[idx]);
+            let x = col as f32 + self.rng.gen_range(0.0..1.0);
+            let y = row as f32 + self.rng.gen_range(0.0..1.0);
+            let rgb = self.next_spawn_color();
+            self.spawn_heading_anywhere(x, y, rgb);
+        }
+    }
+
+    /// Indices of the empty cells inside the shape that are 4-connected to
+    /// `(col, row)`, found by flood fill. Empty if that cell isn't one.
+    fn empty_region(&self, col: usize, row: usize) -> Vec<usize> {
+        let open = |idx: usize| self.mask[idx] && self.tiles[idx] == Cell::Empty;
+        if !self.tiles.contains(col, row) || !open(self.tiles.index(col, row)) {
+            return Vec::new();
+        }
+        let mut seen = vec![false; self.tiles.len()];
+        let mut region = vec![self.tiles.index(col, row)];
+        seen[region[0]] = true;
+        let mut next = 0;
+        while let Some(&idx) = region.get(next) {
+            next += 1;
+            let (x, y) = self.tiles.coords(idx);
+            let neighbours = [
+                (x > 0).then(|| idx - 1),
+                (x + 1 < self.width).then(|| idx + 1),
+                (y > 0).then(|| idx - self.width),
+                (y + 1 < self.height).then(|| idx + self.width),
+            ];
+            for n in neighbours.into_iter().flatten() {
+                if !seen[n] && open(n) {
+                    seen[n] = true;
+                    region.push(n);
+                }
+            }
+        }
+        region
+    }
+
+    /// Spawn an agent in `rgb` at `(x, y)` heading in a random direction,
+    /// or joining the orbit in orbit mode.
+    fn spawn_heading_anywhere(&mut self, x: f32, y: f32, rgb: (u8, u8, u8)) {
+        let agent = match self.params.spawn_mode {
+            SpawnMode::Bounce => {
+                let heading = self.rng.gen_range(0.0..std::f32::consts::TAU);
+                Agent::with_heading(x, y, rgb, heading)
+            }
+            SpawnMode::Orbit {
+                angular_speed,
+                inward_rate,
+            } => Agent::orbiting(x, y, rgb, self.center(), angular_speed, inward_rate),
+        };
+        self.push_agent(agent);
+    }
+
+    /// Give `agent` the next id, add it to the population and report it to
+    /// the event callback.
+    fn push_agent(&mut self, mut agent: Agent) {
+        self.last_agent_id += 1;
+        agent.set_id(self.last_agent_id);
+        self.events.emit(SimEvent::Spawn {
+            id: agent.id(),
+            x: agent.x,
+            y: agent.y,
+            rgb: agent.rgb,
+        });
+        self.agents.push(agent);
+    }
+
+    /// The color for the next agent, as picked by `species` or, without a
+    /// mix, `color_strategy`.
+    fn next_spawn_color(&mut self) -> (u8, u8, u8) {
+        if let Some(mix) = &self.species {
+            return match self.selected_species.and_then(|i| mix.color(i)) {
+                Some(rgb) => rgb,
+                None => mix.pick(&mut self.rng),
+            };
+        }
+        match &self.color_strategy {
+            ColorStrategy::Random => (self.rng.gen(), self.rng.gen(), self.rng.gen()),
+            ColorStrategy::PaletteGradient(stops) => gradient_color(stops, self.rng.gen()),
+            ColorStrategy::Fixed => self.spawn_color,
+        }
+    }
+
+    /// Switch to the fixed spawn color and move it to the next palette
+    /// entry. The first press from another strategy picks the current
+    /// palette entry.
+    pub fn cycle_spawn_color(&mut self) {
+        if self.color_strategy != ColorStrategy::Fixed {
+            self.color_strategy = ColorStrategy::Fixed;
+        } else {
+            self.palette_index = (self.palette_index + 1) % SPAWN_PALETTE.len();
+        }
+        self.spawn_color = SPAWN_PALETTE[self.palette_index];
+    }
+
+    /// Make every spawn but scatters species `index` of the mix, counting
+    /// from `0`. Selecting the species already selected goes back to
+    /// picking by share.
+    pub fn select_species(&mut self, index: usize) {
+        let Some(rgb) = self.species.as_ref().and_then(|mix| mix.color(index)) else {
+            info!("No species {} to select", index + 1);
+            return;
+        };
+        if self.selected_species == Some(index) {
+            self.selected_species = None;
+            info!("Spawning species by share");
+        } else {
+            self.selected_species = Some(index);
+            info!("Spawning species {} {rgb:?}", index + 1);
+        }
+    }
+
+    /// Switch to the fixed spawn color and set it to the heat at
+    /// `(col, row)`, like an eyedropper. An empty cell leaves the color and
+    /// strategy as they were.
+    pub fn pick_color(&mut self, col: usize, row: usize) {
+        let Some(&tile @ Cell::Heat(..)) = self.tiles.get(col, row) else {
+            return;
+        };
+        let (r, g, b) = tile.rgb();
+        self.color_strategy = ColorStrategy::Fixed;
+        self.spawn_color = (r, g, b);
+        info!("Picked spawn color ({r}, {g}, {b})");
+    }
+
+    /// Switch new spawns between bouncing and orbiting the grid center.
+    pub fn toggle_orbit(&mut self) {
+        self.params.spawn_mode = match self.params.spawn_mode {
+            SpawnMode::Bounce => SpawnMode::Orbit {
+                angular_speed: ORBIT_ANGULAR_SPEED,
+                inward_rate: ORBIT_INWARD_RATE,
+            },
+            SpawnMode::Orbit { .. } => SpawnMode::Bounce,
+        };
+    }
+
+    /// Empty every cell and remove every agent, keeping the parameters,
+    /// the shape and the grid's allocations. Pinned sources stay and are
+    /// back at full heat straight away.
+    pub fn reset(&mut self) {
+        self.tiles.fill(Cell::Empty);
+        self.ages.fill(0.0);
+        self.clear_agents();
+        self.exposure = None;
+        self.sparse.clear_active();
+        self.refresh_sources();
+        info!("Cleared the grid and agents");
+    }
+
+    /// Put every simulation parameter back to `defaults`, leaving the grid
+    /// and agents alone.
+    pub fn reset_params(&mut self) {
+        self.params = self.defaults.clone();
+        info!("Reset parameters to defaults");
+    }
+
+    /// Take on `preset`'s parameters, making them the defaults too so a
+    /// reset comes back to the preset.
+    pub fn set_preset(&mut self, preset: Preset) {
+        self.params = preset.params();
+        self.defaults = self.params.clone();
+        self.preset = Some(preset);
+        info!("Preset: {preset}");
+    }
+
+    pub fn preset(&self) -> Option<Preset> {
+        self.preset
+    }
+
+    /// Switch steering between snapping onto the chosen sensor and turning
+    /// gradually toward it.
+    pub fn toggle_turn_mode(&mut self) {
+        self.params.sensors.turn = match self.params.sensors.turn {
+            TurnMode::Snap => TurnMode::Gradual {
+                rate: GRADUAL_TURN_RATE,
+            },
+            TurnMode::Gradual { .. } => TurnMode::Snap,
+        };
+    }
+
+    /// Multiply `agent_scale` by `factor`, keeping it within sensible bounds.
+    pub fn scale_agents(&mut self, factor: f32) {
+        let scale = self.params.agent_scale * factor;
+        self.params.agent_scale = scale.clamp(MIN_AGENT_SCALE, MAX_AGENT_SCALE);
+    }
+
+    pub fn scale_speed(&mut self, factor: f32) {
+        let speed = self.params.motion.speed * factor;
+        self.params.motion.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    /// Move `params.evaporation` by `delta`, to the nearest hundredth so
+    /// repeated steps land back on round values.
+    pub fn step_evaporation(&mut self, delta: f32) {
+        let evaporation = ((self.params.evaporation + delta) * 100.0).round() / 100.0;
+        self.params.evaporation = evaporation.clamp(MIN_EVAPORATION, 1.0);
+    }
+
+    pub fn scale_deposits(&mut self, factor: f32) {
+        let strength = self.params.deposit_strength * factor;
+        self.params.deposit_strength = strength.clamp(MIN_DEPOSIT_STRENGTH, MAX_DEPOSIT_STRENGTH);
+    }
+
+    pub fn update(&mut self) {
+        let diffuse = self
+            .step
+            .is_multiple_of(u64::from(self.params.diffuse_every.max(1)));
+        if self.step < u64::from(self.params.burn_in) {
+            self.update_field(diffuse);
+            self.step += 1;
+            return;
+        }
+        match self.params.update_order {
+            UpdateOrder::DepositThenDiffuse => {
+                self.update_agents();
+                self.update_field(diffuse);
+            }
+            UpdateOrder::DiffuseThenDeposit => {
+                self.update_field(diffuse);
+                self.update_agents();
+            }
+        }
+        self.step += 1;
+    }
+
+    /// Diffuse the field, or on the updates `diffuse_every` skips just
+    /// evaporate it, if `decay_between_diffusions` says to, and top the
+    /// sources back up.
+    fn update_field(&mut self, diffuse: bool) {
+        if diffuse {
+            self.update_tiles();
+            return;
+        }
+        if self.params.decay_between_diffusions {
+            self.evaporate();
+        }
+        self.refresh_sources();
+    }
+
+    /// Run `steps` updates back to back.
+    pub fn advance(&mut self, steps: u64) {
+        for _ in 0..steps {
+            self.update();
+        }
+    }
+
+    pub fn update_agents(&mut self) {
+        let factor = self.params.sensors.downsample.max(1);
+        if self.params.sensing && factor > 1 {
+            self.sense_field = downsample(&self.tiles, self.width, self.height, factor);
+        }
+        let (field, field_width, field_height) = if factor > 1 {
+            (
+                &self.sense_field[..],
+                self.width.div_ceil(factor),
+                self.height.div_ceil(factor),
+            )
+        } else {
+            (&self.tiles[..], self.width, self.height)
+        };
+
+        let attractor = self.attractor_field.as_ref().map(|values| FieldView {
+            values,
+            width: self.width,
+            height: self.height,
+        });
+
+        let sensors = SensorConfig {
+            distance: self.params.sensors.distance * self.params.agent_scale,
+            ..self.params.sensors
+        };
+        let mut agents = std::mem::take(&mut self.agents);
+        for agent in agents.iter_mut() {
+            if self.params.sensing {
+                let angle = agent.sense(field, field_width, field_height, &sensors, attractor);
+                agent.turn(angle, &self.params.motion);
+            }
+            agent.remember_position();
+            let prev = (agent.x, agent.y);
+            match self.params.movement {
+                MovementMode::Continuous => {
+                    agent.update(self.height, self.width, &self.params.motion)
+                }
+                MovementMode::Lattice => {
+                    agent.step_lattice(self.height, self.width, &self.params.motion)
+                }
+            }
+            if !self.shape_contains(agent.x, agent.y) {
+                agent.reflect(
+                    prev,
+                    self.wall_normal(agent.x, agent.y),
+                    &self.params.motion,
+                );
+            } else if let Some(hit) = self.wall_hit(prev, (agent.x, agent.y)) {
+                let normal = self.wall_cell_normal(prev, hit);
+                agent.reflect(prev, normal, &self.params.motion);
+            }
+            agent.record_position(self.params.trajectory_points);
+        }
+        self.agents = agents;
+        self.cull_agents();
+
+        let mut offspring = Vec::new();
+        let mut agents = std::mem::take(&mut self.agents);
+        for agent in agents.iter_mut() {
+            // Check the trail before this agent's own deposit lands on it.
+            let on_strong_trail = self
+                .tiles
+                .at_point(agent.x, agent.y)
+                .is_some_and(|cell| cell.intensity() >= self.params.reproduction_threshold);
+            if on_strong_trail && self.rng.gen_bool(self.params.reproduction_chance) {
+                offspring.push(agent.offspring(OFFSPRING_MAX_TURN, &mut self.rng));
+            }
+
+            if !agent.deposit_due(self.params.deposit_interval) {
+                continue;
+            }
+            if self.params.deposit_radius > 0.0 {
+                self.stamp(agent.x, agent.y, agent.rgb);
+                continue;
+            }
+            if agent.x < 0.0 || agent.y < 0.0 {
+                continue;
+            }
+            let (col, row) = (agent.x as usize, agent.y as usize);
+            if !self.tiles.contains(col, row) {
+                continue;
+            }
+            let idx = col + row * self.width;
+            let strength = self.params.deposit_strength;
+            let heat = |c: u8| (c as f32 * strength).min(MAX_HEAT);
+            let (r, g, b) = (heat(agent.rgb.0), heat(agent.rgb.1), heat(agent.rgb.2));
+            if self.mask[idx] && (r, g, b) != (0.0, 0.0, 0.0) {
+                self.tiles[idx] = Cell::Heat(r, g, b);
+                self.ages[idx] = 0.0;
+                self.sparse.activate(idx);
+            }
+        }
+        self.agents = agents;
+
+        let room = self.params.max_agents.saturating_sub(self.agents.len());
+        offspring.truncate(room);
+        for child in offspring {
+            self.push_agent(child);
+        }
+    }
+
+    /// Deposit a gaussian blob of `rgb` heat centered on `(x, y)`, out to
+    /// `params.deposit_radius` times `params.agent_scale` cells and peaking
+    /// at `params.deposit_strength` of full color. Cells keep whichever is
+    /// hotter, per channel, of their heat and the blob's.
+    fn stamp(&mut self, x: f32, y: f32, rgb: (u8, u8, u8)) {
+        let radius = self.params.deposit_radius * self.params.agent_scale;
+        let sigma = radius / 2.0;
+        let (x0, x1) = ((x - radius).floor().max(0.0), (x + radius).ceil());
+        let (y0, y1) = ((y - radius).floor().max(0.0), (y + radius).ceil());
+        for row in y0 as usize..=(y1 as usize).min(self.height.saturating_sub(1)) {
+            for col in x0 as usize..=(x1 as usize).min(self.width.saturating_sub(1)) {
+                let idx = self.tiles.index(col, row);
+                let (dx, dy) = (col as f32 + 0.5 - x, row as f32 + 0.5 - y);
+                let d2 = dx * dx + dy * dy;
+                if !self.mask[idx] || d2 > radius * radius {
+                    continue;
+                }
+                let weight = self.params.deposit_strength * (-d2 / (2.0 * sigma * sigma)).exp();
+                let blob = [rgb.0, rgb.1, rgb.2].map(|c| (c as f32 * weight).min(MAX_HEAT));
+                let old = self.tiles[idx].channels();
+                let [r, g, b] = [0, 1, 2].map(|c| old[c].max(blob[c]));
+                if [r, g, b] == old {
+                    continue;
+                }
+                self.tiles[idx] = Cell::Heat(r, g, b);
+                self.ages[idx] = 0.0;
+                self.sparse.activate(idx);
+            }
+        }
+    }
+
+    /// Unit normal of the shape's boundary nearest `(x, y)`, pointing inwards.
+    fn wall_normal(&self, x: f32, y: f32) -> (f32, f32) {
+        let (cx, cy) = self.center();
+        let (dx, dy) = (cx - x, cy - y);
+        let len = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+        (dx / len, dy / len)
+    }
+
+    /// Unit normal of the wall cell a move from `prev` to `next` ran into:
+    /// along whichever axis alone would have crossed into a wall, or back
+    /// the way it came when only the diagonal move does.
+    fn wall_cell_normal(&self, prev: (f32, f32), next: (f32, f32)) -> (f32, f32) {
+        let across_x = self.walled(next.0, prev.1);
+        let across_y = self.walled(prev.0, next.1);
+        let (dx, dy) = match (across_x, across_y) {
+            (false, false) => (prev.0 - next.0, prev.1 - next.1),
+            _ => (across_x as u8 as f32, across_y as u8 as f32),
+        };
+        let len = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+        (dx / len, dy / len)
+    }
+
+    /// Remove every agent, reporting each one's death.
+    fn clear_agents(&mut self) {
+        for agent in self.agents.drain(..) {
+            self.events.emit(SimEvent::Death {
+                id: agent.id(),
+                x: agent.x,
+                y: agent.y,
+                rgb: agent.rgb,
+            });
+        }
+    }
+
+    /// Drop agents that have ended up more than `CULL_MARGIN` cells off the
+    /// grid, before they try to deposit there.
+    fn cull_agents(&mut self) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let before = self.agents.len();
+        let events = &mut self.events;
+        self.agents.retain(|agent| {
+            let on_grid = agent.x >= -CULL_MARGIN
+                && agent.y >= -CULL_MARGIN
+                && agent.x < width + CULL_MARGIN
+                && agent.y < height + CULL_MARGIN;
+            if !on_grid {
+                events.emit(SimEvent::Cull {
+                    id: agent.id(),
+                    x: agent.x,
+                    y: agent.y,
+                    rgb: agent.rgb,
+                });
+            }
+            on_grid
+        });
+        let culled = before - self.agents.len();
+        if culled > 0 {
+            debug!("Culled {culled} off-grid agents");
+        }
+    }
+
+    /// Edge cases hit by the latest `update_tiles`, or by all of them so far
+    /// while `cumulative_stats` is set.
+    pub fn diffusion_stats(&self) -> DiffusionStats {
+        self.diffusion_stats
+    }
+
+    pub fn update_tiles(&mut self) {
+        if !self.cumulative_stats {
+            self.diffusion_stats = DiffusionStats::default();
+        }
+        let mass_before = self.params.normalize_mass.then(|| self.total_intensity());
+        match self.params.grid_update {
+            GridUpdate::Sparse {
+                max_active_fraction,
+            } if (self.sparse.active.len() as f32)
+                <= max_active_fraction * self.tiles.len() as f32 =>
+            {
+                self.update_tiles_sparse()
+            }
+            GridUpdate::InPlace => self.update_tiles_in_place(),
+            _ => self.update_tiles_dense(),
+        }
+
+        if let Some(mass_before) = mass_before {
+            self.normalize(self.params.mass_target.unwrap_or(mass_before));
+        }
+        self.evaporate();
+        self.refresh_sources();
+    }
+
+    /// Scale each cell's heat by the fraction it keeps, from the decay map
+    /// or else `params.evaporation`, emptying cells it fades below
+    /// `params.evaporation_threshold`. Runs on the diffused field, so fresh
+    /// deposits spread before they start to fade.
+    ///
+    /// Every heated cell is in the active set, whatever the update mode, so
+    /// only those are visited, and the ones emptied here leave it.
+    fn evaporate(&mut self) {
+        let threshold = self.params.evaporation_threshold;
+        if self.decay_map.is_none() && self.params.evaporation >= 1.0 && threshold <= 0.0 {
+            return;
+        }
+        let mut active = std::mem::take(&mut self.sparse.active);
+        active.retain(|&idx| {
+            let keep = match &self.decay_map {
+                Some(map) => map[idx],
+                None => self.params.evaporation.clamp(0.0, 1.0),
+            };
+            let fade = |c: f32| c * keep;
+            if let Cell::Heat(r, g, b) = self.tiles[idx] {
+                match [fade(r), fade(g), fade(b)] {
+                    channels if channels.iter().all(|&c| c < threshold.max(MIN_HEAT)) => {}
+                    [r, g, b] => {
+                        self.tiles[idx] = Cell::Heat(r, g, b);
+                        return true;
+                    }
+                }
+            }
+            self.tiles[idx] = Cell::Empty;
+            self.ages[idx] = 0.0;
+            self.sparse.active_mask[idx] = false;
+            false
+        });
+        self.sparse.active = active;
+    }
+
+    /// Diffuse every row at once from `tiles` into the back buffers, then
+    /// swap them in. Each cell reads only the front buffer and is written by
+    /// its own row, so the rows run in parallel.
+    fn update_tiles_dense(&mut self) {
+        let mut tiles = std::mem::take(&mut self.back_tiles);
+        let mut ages = std::mem::take(&mut self.back_ages);
+        tiles.resize(self.tiles.len(), Cell::Empty);
+        ages.resize(self.tiles.len(), 0.0);
+        let width = self.width.max(1);
+        let stats = tiles
+            .par_chunks_mut(width)
+            .zip(ages.par_chunks_mut(width))
+            .enumerate()
+            .map(|(y, (tile_row, age_row))| {
+                let mut stats = DiffusionStats::default();
+                for (x, (tile, age)) in tile_row.iter_mut().zip(age_row).enumerate() {
+                    *tile = self.diffuse(x, y, &mut stats);
+                    *age = self.diffused_age(x, y, *tile);
+                }
+                stats
+            })
+            .reduce(DiffusionStats::default, |mut total, stats| {
+                total.add(stats);
+                total
+            });
+        let front = std::mem::replace(
+            &mut self.tiles,
+            Grid::from_vec(self.width, self.height, tiles),
+        );
+        self.back_tiles = front.into_vec();
+        std::mem::swap(&mut self.ages, &mut ages);
+        self.back_ages = ages;
+        self.diffusion_stats.add(stats);
+        self.rebuild_active();
+    }
+
+    /// Diffuse each cell straight into `tiles`, in `scan_order`.
+    fn update_tiles_in_place(&mut self) {
+        let mut stats = DiffusionStats::default();
+        for idx in self.scan_indices() {
+            let (x, y) = self.tiles.coords(idx);
+            let cell = self.diffuse(x, y, &mut stats);
+            self.ages[idx] = self.diffused_age(x, y, cell);
+            self.tiles[idx] = cell;
+        }
+        self.diffusion_stats.add(stats);
+        self.rebuild_active();
+    }
+
+    /// Every cell index, in the order `params.scan_order` visits them.
+    fn scan_indices(&mut self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.tiles.len()).collect();
+        match self.params.scan_order {
+            ScanOrder::Raster => {}
+            ScanOrder::Boustrophedon => {
+                for row in order.chunks_mut(self.width).skip(1).step_by(2) {
+                    row.reverse();
+                }
+            }
+            ScanOrder::Random => order.shuffle(&mut self.rng),
+        }
+        order
+    }
+
+    /// Mark exactly the non-empty cells active after a full pass.
+    fn rebuild_active(&mut self) {
+        self.sparse.clear_active();
+        for (idx, tile) in self.tiles.iter().enumerate() {
+            if *tile != Cell::Empty {
+                self.sparse.activate(idx);
+            }
+        }
+    }
+
+    /// Diffuse only the active cells and their neighbours; every other cell
+    /// has no heat around it and stays empty.
+    fn update_tiles_sparse(&mut self) {
+        let mut candidates = std::mem::take(&mut self.sparse.candidates);
+        for &idx in &self.sparse.active {
+            let (x, y) = self.tiles.coords(idx);
+            for (nx, ny) in self.tiles.window(x, y) {
+                let n = self.tiles.index(nx, ny);
+                if !self.sparse.candidate_mask[n] {
+                    self.sparse.candidate_mask[n] = true;
+                    candidates.push(n);
+                }
+            }
+        }
+
+        let mut updates = std::mem::take(&mut self.sparse.updates);
+        let mut stats = DiffusionStats::default();
+        updates.extend(candidates.iter().map(|&idx| {
+            let (x, y) = self.tiles.coords(idx);
+            let cell = self.diffuse(x, y, &mut stats);
+            (idx, cell, self.diffused_age(x, y, cell))
+        }));
+        self.diffusion_stats.add(stats);
+
+        self.sparse.clear_active();
+        for &(idx, cell, age) in &updates {
+            self.sparse.candidate_mask[idx] = false;
+            self.tiles[idx] = cell;
+            self.ages[idx] = age;
+            if cell != Cell::Empty {
+                self.sparse.activate(idx);
+            }
+        }
+
+        candidates.clear();
+        updates.clear();
+        self.sparse.candidates = candidates;
+        self.sparse.updates = updates;
+    }
+
+    /// The diffused value of the cell at `(x, y)`: each channel moved toward
+    /// the 3x3 box average of its neighbourhood by that channel's diffusion
+    /// rate, and held up by `params.sharp_fronts`. The average is over the
+    /// open cells on the grid, each counted once, so walls and cells outside
+    /// the shape take no heat. `Cell::Empty` when no heat survives. Edge cases hit along the way are
+    /// counted in `stats`.
+    fn diffuse(&self, x: usize, y: usize, stats: &mut DiffusionStats) -> Cell {
+        let idx = self.tiles.index(x, y);
+        if !self.mask[idx] {
+            return Cell::Empty;
+        }
+        let mut sum = [0.0; 3];
+        let mut neighbours = 0;
+        for (i, j) in self.tiles.window(x, y) {
+            let n = self.tiles.index(i, j);
+            if !self.mask[n] {
+                continue;
+            }
+            neighbours += 1;
+            if let Cell::Heat(r, g, b) = self.tiles[n] {
+                sum[0] += r;
+                sum[1] += g;
+                sum[2] += b;
+            }
+        }
+        if neighbours < 9 && sum != [0.0; 3] {
+            stats.edge_clamped += 1;
+        }
+        let center = self.tiles[idx].channels();
+        let weights = match (self.kernel_weights(x, y), self.flow_weights(x, y)) {
+            (Some(kernel), Some(flow)) => Some(std::array::from_fn(|i| kernel[i] * flow[i])),
+            (kernel, flow) => kernel.or(flow),
+        };
+        let floor = self.params.sharp_fronts;
+        let average = match weights {
+            Some(weights) => self.weighted_average(x, y, &weights),
+            None => sum.map(|sum| sum / neighbours as f32),
+        };
+        let [r, g, b] = [0, 1, 2].map(|c| {
+            let rate = self.params.diffusion_rate[c];
+            let mut value = center[c] + rate * (average[c] - center[c]);
+            if let Some(floor) = floor {
+                value = value.max(center[c] * floor.clamp(0.0, 1.0));
+            }
+            if value > MAX_HEAT {
+                stats.saturated += 1;
+            }
+            value.min(MAX_HEAT)
+        });
+        if r < MIN_HEAT && g < MIN_HEAT && b < MIN_HEAT {
+            if center != [0.0; 3] {
+                stats.underflowed += 1;
+            }
+            return Cell::Empty;
+        }
+        Cell::Heat(r, g, b)
+    }
+
+    /// The age of the cell at `(x, y)` once it diffuses into `cell`: the
+    /// intensity-weighted mean age of the heat it draws from, one update older.
+    fn diffused_age(&self, x: usize, y: usize, cell: Cell) -> f32 {
+        if cell == Cell::Empty {
+            return 0.0;
+        }
+        let (mut weighted, mut total) = (0.0, 0.0);
+        for (i, j) in self.tiles.window(x, y) {
+            let n = self.tiles.index(i, j);
+            if self.mask[n] {
+                let intensity = self.tiles[n].intensity();
+                weighted += intensity * self.ages[n];
+                total += intensity;
+            }
+        }
+        let age = if total > 0.0 { weighted / total } else { 0.0 };
+        age + 1.0
+    }
+
+    /// Updates since the heat at `(x, y)` was laid down, `None` off the grid.
+    pub fn age_at(&self, x: f32, y: f32) -> Option<f32> {
+        if x < 0.0 || y < 0.0 || !self.tiles.contains(x as usize, y as usize) {
+            return None;
+        }
+        Some(self.ages[self.tiles.index(x as usize, y as usize)])
+    }
+
+    /// Intensity of the cell `(dx, dy)` from `(x, y)`, read as `0` off the
+    /// grid or outside the shape.
+    fn neighbour_intensity(&self, x: usize, y: usize, dx: isize, dy: isize) -> f32 {
+        let Some((i, j)) = self.tiles.offset(x, y, dx, dy) else {
+            return 0.0;
+        };
+        let n = self.tiles.index(i, j);
+        if self.mask[n] {
+            self.tiles[n].intensity()
+        } else {
+            0.0
+        }
+    }
+
+    /// Weights for the 3x3 neighbourhood of `(x, y)` under `params.kernel`,
+    /// indexed like `flow_weights`' and with off-grid and masked neighbours
+    /// read as empty. `None` for the plain box kernel.
+    fn kernel_weights(&self, x: usize, y: usize) -> Option<[f32; 9]> {
+        let (spatial_sigma, intensity_sigma) = match self.params.kernel {
+            Kernel::Box => return None,
+            Kernel::Gaussian { sigma } => (sigma, None),
+            Kernel::Bilateral {
+                spatial_sigma,
+                intensity_sigma,
+            } => (spatial_sigma, Some(intensity_sigma)),
+        };
+        let intensity = |dx, dy| self.neighbour_intensity(x, y, dx, dy);
+        let center = intensity(0, 0);
+        let falloff = |distance_squared: f32, sigma: f32| {
+            (-distance_squared / (2.0 * sigma * sigma).max(f32::EPSILON)).exp()
+        };
+        let mut weights = [0.0; 9];
+        for dy in -1..=1isize {
+            for dx in -1..=1isize {
+                let mut weight = falloff((dx * dx + dy * dy) as f32, spatial_sigma);
+                if let Some(sigma) = intensity_sigma {
+                    weight *= falloff((intensity(dx, dy) - center).powi(2), sigma);
+                }
+                weights[((dx + 1) + (dy + 1) * 3) as usize] = weight;
+            }
+        }
+        Some(weights)
+    }
+
+    /// Weights for the 3x3 neighbourhood of `(x, y)`, indexed by
+    /// `(dx + 1) + (dy + 1) * 3`, that favour neighbours lying along the local
+    /// flow: the direction across the intensity gradient, along a trail
+    /// rather than off its sides. `None` when `anisotropy` is `0` or the
+    /// gradient is flat, leaving diffusion isotropic.
+    fn flow_weights(&self, x: usize, y: usize) -> Option<[f32; 9]> {
+        let strength = self.params.anisotropy.clamp(0.0, 1.0);
+        if strength == 0.0 {
+            return None;
+        }
+        let intensity = |dx, dy| self.neighbour_intensity(x, y, dx, dy);
+        let (gx, gy) = (
+            intensity(1, 0) - intensity(-1, 0),
+            intensity(0, 1) - intensity(0, -1),
+        );
+        let len = (gx * gx + gy * gy).sqrt();
+        if len <= f32::EPSILON {
+            return None;
+        }
+        let flow = (-gy / len, gx / len);
+
+        let mut weights = [1.0; 9];
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+                let (fx, fy) = (dx as f32, dy as f32);
+                let along = (fx * flow.0 + fy * flow.1).powi(2) / (fx * fx + fy * fy);
+                weights[((dx + 1) + (dy + 1) * 3) as usize] = 1.0 + strength * (2.0 * along - 1.0);
+            }
+        }
+        Some(weights)
+    }
+
+    /// Per-channel average of the 3x3 neighbourhood of `(x, y)` under
+    /// `weights`, over the open neighbours on the grid.
+    fn weighted_average(&self, x: usize, y: usize, weights: &[f32; 9]) -> [f32; 3] {
+        let mut sum = [0.0; 3];
+        let mut total = 0.0;
+        for dy in -1..=1isize {
+            for dx in -1..=1isize {
+                let Some((i, j)) = self.tiles.offset(x, y, dx, dy) else {
+                    continue;
+                };
+                let n = self.tiles.index(i, j);
+                let w = weights[((dx + 1) + (dy + 1) * 3) as usize];
+                if !self.mask[n] {
+                    continue;
+                }
+                total += w;
+                if let Cell::Heat(r, g, b) = self.tiles[n] {
+                    sum[0] += w * r;
+                    sum[1] += w * g;
+                    sum[2] += w * b;
+                }
+            }
+        }
+        sum.map(|s| s / total.max(f32::EPSILON))
+    }
+
+    /// Rescale every heat cell so the field's total intensity matches `target`,
+    /// making up for whatever the diffusion pass lost to the edges.
+    fn normalize(&mut self, target: f64) {
+        let total = self.total_intensity();
+        if total <= 0.0 {
+            return;
+        }
+        let factor = target / total;
+        let scale = |c: f32| (c as f64 * factor).clamp(0.0, MAX_HEAT as f64) as f32;
+        for tile in self.tiles.iter_mut() {
+            if let Cell::Heat(r, g, b) = *tile {
+                *tile = Cell::Heat(scale(r), scale(g), scale(b));
+            }
+        }
+    }
+
+    /// Draw the `World` state to the frame buffer.
+    ///
+    /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
+    ///
+    pub fn draw(&mut self, frame: &mut [u8]) {
+        self.draw_blended(frame, 1.0);
+    }
+
+    /// Draw like `draw`, with agent markers placed `alpha` of the way from
+    /// where each agent was before the latest update to where it is now, so
+    /// motion stays smooth when frames fall between updates.
+    pub fn draw_blended(&mut self, frame: &mut [u8], alpha: f32) {
+        for pixel in frame.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&BACKGROUND);
+        }
+        self.draw_in(frame, self.frame_size.0, self.view(), alpha);
+    }
+
+    /// Where the grid is drawn in the window's own screen layout.
+    pub fn view(&self) -> Rect {
+        Rect {
+            x: self.origin.0,
+            y: self.origin.1,
+            width: (self.width as f32 * self.draw_scale) as usize,
+            height: (self.height as f32 * self.draw_scale) as usize,
+        }
+    }
+
+    /// Draw like `draw_blended` into just the `rect` of a `frame` that is
+    /// `frame_width` pixels wide, with the grid's top left corner at the
+    /// rectangle's and cut off at its edges. Leaves the rest of the frame
+    /// alone.
+    pub fn draw_in(&mut self, frame: &mut [u8], frame_width: usize, rect: Rect, alpha: f32) {
+        if self.auto_exposure {
+            self.update_exposure();
+        }
+        self.render_in(frame, frame_width, rect, alpha);
+    }
+
+    /// Draw like `draw_in` without stepping `auto_exposure`, so a world can
+    /// be drawn through a shared reference.
+    pub fn render_in(&self, frame: &mut [u8], frame_width: usize, rect: Rect, alpha: f32) {
+        let cells_pixel_width = (self.width as f32 * self.draw_scale) as usize;
+        let cells_pixel_height = (self.height as f32 * self.draw_scale) as usize;
+        let frame_height = frame.len() / 4 / frame_width;
+        let contour_mask = if self.contours {
+            self.contour_mask()
+        } else {
+            Vec::new()
+        };
+        for y in rect.y..(rect.y + rect.height).min(frame_height) {
+            for x in rect.x..(rect.x + rect.width).min(frame_width) {
+                let (dx, dy) = (x - rect.x, y - rect.y);
+                let inside_cells = dx < cells_pixel_width && dy < cells_pixel_height;
+
+                let rgba = if inside_cells && !self.agents_only {
+                    let row = (dy as f32 / self.draw_scale) as usize % self.height;
+                    let col = (dx as f32 / self.draw_scale) as usize % self.width;
+                    let cell = self.tiles.index(col, row);
+                    if contour_mask.get(cell) == Some(&true) {
+                        let idx = (x + y * frame_width) * 4;
+                        frame[idx..idx + 4].copy_from_slice(&CONTOUR_COLOR);
+                        continue;
+                    }
+                    let offset = if self.dither {
+                        (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0
+                    } else {
+                        0.0
+                    };
+                    match &self.palette {
+                        // Dithering against a coarse palette needs a much wider
+                        // nudge than breaking up single-level banding does.
+                        Some(palette) => nearest_color(
+                            self.cell_color(cell, 0.0),
+                            palette,
+                            if self.dither {
+                                (offset - 0.5) * PALETTE_DITHER_SPREAD
+                            } else {
+                                0.0
+                            },
+                        ),
+                        None => self.cell_color(cell, offset),
+                    }
+                } else {
+                    BACKGROUND
+                };
+
+                let idx = (x + y * frame_width) * 4;
+                frame[idx..idx + 4].copy_from_slice(&rgba);
+            }
+        }
+
+        self.draw_agents(frame, frame_width, rect, alpha);
+    }
+
+    /// Which cells a contour line runs through: those whose intensity is on
+    /// the other side of some contour level from their right or lower
+    /// neighbour's.
+    fn contour_mask(&self) -> Vec<bool> {
+        let intensity = |idx: usize| {
+            if self.mask[idx] {
+                self.tiles[idx].intensity()
+            } else {
+                0.0
+            }
+        };
+        let crosses = |a: f32, b: f32| self.contour_levels.iter().any(|&l| (a < l) != (b < l));
+        let mut lines = vec![false; self.tiles.len()];
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let idx = self.tiles.index(col, row);
+                let here = intensity(idx);
+                lines[idx] = (col + 1 < self.width && crosses(here, intensity(idx + 1)))
+                    || (row + 1 < self.height && crosses(here, intensity(idx + self.width)));
+            }
+        }
+        lines
+    }
+
+    /// Move the exposure level toward the brightest channel now on the grid.
+    fn update_exposure(&mut self) {
+        let brightest = self
+            .tiles
+            .iter()
+            .map(|tile| match *tile {
+                Cell::Empty => 0.0,
+                Cell::Heat(r, g, b) => r.max(g).max(b),
+            })
+            .fold(0.0, f32::max)
+            .min(MAX_HEAT);
+        self.exposure = Some(match self.exposure {
+            Some(level) => level + (brightest - level) * EXPOSURE_SMOOTHING,
+            None => brightest,
+        });
+    }
+
+    /// How much `auto_exposure` brightens channel values by, `1.0` when off.
+    fn exposure_gain(&self) -> f32 {
+        match self.exposure {
+            Some(level) if self.auto_exposure && level >= 1.0 => 255.0 / level,
+            _ => 1.0,
+        }
+    }
+
+    /// Lay the grid out for a `frame_width` x `frame_height` window frame:
+    /// centered in the default window's margins, scaled to the frame, and
+    /// as large as fits in them. Frames too small for one pixel a cell
+    /// shrink the grid below that rather than cut it off, down to
+    /// `MIN_DRAW_SCALE`. With the raw view on, the scale it goes back to
+    /// changes instead.
+    pub fn fit_to(&mut self, frame_width: usize, frame_height: usize) {
+        let area = grid_area(frame_width, frame_height);
+        let scale = (area.width as f32 / self.width as f32)
+            .min(area.height as f32 / self.height as f32)
+            .max(MIN_DRAW_SCALE);
+        match &mut self.scaled_view {
+            Some(scaled) => *scaled = scale,
+            None => self.draw_scale = scale,
+        }
+        let width = (self.width as f32 * scale) as usize;
+        let height = (self.height as f32 * scale) as usize;
+        self.origin = (
+            area.x + area.width.saturating_sub(width) / 2,
+            area.y + area.height.saturating_sub(height) / 2,
+        );
+        self.frame_size = (frame_width, frame_height);
+    }
+
+    /// Width and height of the window frame `draw` expects.
+    pub fn frame_size(&self) -> (usize, usize) {
+        self.frame_size
+    }
+
+    /// Switch between the configured `draw_scale` and drawing one cell per
+    /// screen pixel, for inspecting exact cell values.
+    pub fn toggle_raw_view(&mut self) {
+        match self.scaled_view.take() {
+            Some(scale) => self.draw_scale = scale,
+            None => self.scaled_view = Some(std::mem::replace(&mut self.draw_scale, 1.0)),
+        }
+    }
+
+    /// Overlay agent markers onto the `rect` of a frame already holding the
+    /// grid there, each `params.agent_scale` pixels across.
+    fn draw_agents(&self, frame: &mut [u8], frame_width: usize, rect: Rect, alpha: f32) {
+        let right = (rect.x + rect.width).min(frame_width);
+        let bottom = (rect.y + rect.height).min(frame.len() / 4 / frame_width);
+        let mut blend = |x: usize, y: usize, coverage: f32| {
+            if x < rect.x || x >= right || y < rect.y || y >= bottom {
+                return;
+            }
+            let idx = (x + y * frame_width) * 4;
+            for (channel, marker) in frame[idx..idx + 3].iter_mut().zip(MARKER_COLOR) {
+                *channel = (*channel as f32 + (marker as f32 - *channel as f32) * coverage) as u8;
+            }
+        };
+
+        let size = self.params.agent_scale.max(0.0);
+        let dot = (size.round() as usize).max(1);
+        for agent in &self.agents {
+            let (x, y) = agent.position_at(alpha);
+            let sx = rect.x as f32 + x * self.draw_scale;
+            let sy = rect.y as f32 + y * self.draw_scale;
+            if sx < 0.0 || sy < 0.0 {
+                continue;
+            }
+            let markers = match self.agent_markers {
+                AgentMarkers::Off if self.agents_only => AgentMarkers::Dot,
+                markers => markers,
+            };
+            match markers {
+                AgentMarkers::Off => return,
+                AgentMarkers::Dot => {
+                    let (x0, y0) = (sx.round() as usize, sy.round() as usize);
+                    let (x0, y0) = (
+                        x0.saturating_sub((dot - 1) / 2),
+                        y0.saturating_sub((dot - 1) / 2),
+                    );
+                    for y in y0..y0 + dot {
+                        for x in x0..x0 + dot {
+                            blend(x, y, 1.0);
+                        }
+                    }
+                }
+                AgentMarkers::Smooth => {
+                    // A `size` pixel square grown evenly around the unit
+                    // one, covering each pixel by the area it overlaps.
+                    let (left, top) = (sx - (size - 1.0) / 2.0, sy - (size - 1.0) / 2.0);
+                    let overlap = |start: f32, pixel: usize| {
+                        let pixel = pixel as f32;
+                        ((start + size).min(pixel + 1.0) - start.max(pixel)).max(0.0)
+                    };
+                    let (x0, y0) = (left.max(0.0) as usize, top.max(0.0) as usize);
+                    let (x1, y1) = ((left + size).ceil() as usize, (top + size).ceil() as usize);
+                    for y in y0..y1.max(y0) {
+                        for x in x0..x1.max(x0) {
+                            let coverage = overlap(left, x) * overlap(top, y);
+                            if coverage > 0.0 {
+                                blend(x, y, coverage);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Switch agent markers between off, dots and smooth markers.
+    pub fn cycle_agent_markers(&mut self) {
+        self.agent_markers = match self.agent_markers {
+            AgentMarkers::Off => AgentMarkers::Dot,
+            AgentMarkers::Dot => AgentMarkers::Smooth,
+            AgentMarkers::Smooth => AgentMarkers::Off,
+        };
+    }
+
+    /// Step `render_mode` on to the next mode, back to `Rgb` after the last.
+    pub fn cycle_render_mode(&mut self) {
+        self.render_mode = match self.render_mode {
+            RenderMode::Rgb => RenderMode::Grayscale,
+            RenderMode::Grayscale => RenderMode::Palette,
+            RenderMode::Palette => RenderMode::Rgb,
+        };
+    }
+
+    /// Write the grid, its heat, the agents and the parameters to `path` as
+    /// JSON, for `load` or `restore` to pick up again. Walls, sources and
+    /// the other maps aren't saved.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let state = SavedState {
+            width: self.width,
+            height: self.height,
+            seed: self.seed,
+            step: self.step,
+            params: self.params.clone(),
+            heat: self.tiles.iter().flat_map(|tile| tile.channels()).collect(),
+            ages: self.ages.clone(),
+            agents: self.agents.clone(),
+            last_agent_id: self.last_agent_id,
+        };
+        let file = io::BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer(file, &state).map_err(io::Error::from)
+    }
+
+    /// A world set up as `save` left one in `path`, drawn with the default
+    /// settings.
+    pub fn load(path: &Path) -> io::Result<World> {
+        let state = read_state(path)?;
+        let mut world = World::with_size(state.width, state.height, state.seed);
+        world.set_state(state);
+        Ok(world)
+    }
+
+    /// Replace this world's heat, agents and parameters with those saved in
+    /// `path`, keeping how it is drawn. The saved grid must be the same size.
+    pub fn restore(&mut self, path: &Path) -> io::Result<()> {
+        let state = read_state(path)?;
+        if (state.width, state.height) != (self.width, self.height) {
+            return Err(invalid_state(format!(
+                "saved grid is {}x{}, not {}x{}",
+                state.width, state.height, self.width, self.height
+            )));
+        }
+        self.set_state(state);
+        Ok(())
+    }
+
+    /// Take on a checked `state` of this world's size.
+    fn set_state(&mut self, state: SavedState) {
+        for (tile, heat) in self.tiles.iter_mut().zip(state.heat.chunks_exact(3)) {
+            *tile = match *heat {
+                [0.0, 0.0, 0.0] => Cell::Empty,
+                [r, g, b] => Cell::Heat(r, g, b),
+                _ => unreachable!(),
+            };
+        }
+        self.ages = state.ages;
+        self.clear_agents();
+        self.agents = state.agents;
+        for agent in &mut self.agents {
+            agent.remember_position();
+        }
+        self.last_agent_id = state.last_agent_id;
+        self.params = state.params;
+        self.step = state.step;
+        self.exposure = None;
+        self.rebuild_active();
+    }
+
+    /// Write every agent's recorded trajectory to `path` as an SVG `<path>`
+    /// in the agent's color, one unit per cell. Trajectories are only kept
+    /// while `params.trajectory_points` is above `0`.
+    pub fn export_svg(&self, path: &Path) -> io::Result<()> {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {w} {h}\" \
+             width=\"{w}\" height=\"{h}\">\n",
+            w = self.width,
+            h = self.height
+        );
+        for agent in &self.agents {
+            let points = agent.trajectory();
+            if points.len() < 2 {
+                continue;
+            }
+            let (r, g, b) = agent.rgb;
+            let _ = write!(svg, "<path id=\"agent-{}\" d=\"", agent.id());
+            for (i, (x, y)) in points.iter().enumerate() {
+                let _ = write!(svg, "{}{x:.2} {y:.2}", if i == 0 { "M" } else { " L" });
+            }
+            let _ = writeln!(
+                svg,
+                "\" fill=\"none\" stroke=\"#{r:02x}{g:02x}{b:02x}\" stroke-width=\"0.5\"/>"
+            );
+        }
+        svg.push_str("</svg>\n");
+        fs::write(path, svg)
+    }
+
+    /// The grid cells of the heat sources placed with `add_source`.
+    pub fn source_cells(&self) -> Vec<(usize, usize)> {
+        self.sources
+            .iter()
+            .map(|&(idx, _)| self.tiles.coords(idx))
+            .collect()
+    }
+
+    /// How well the trails connect `sources`, given as grid cells: trails
+    /// are the cells inside the shape at or above `threshold` intensity,
+    /// joined to their eight neighbours, with diagonal steps √2 (about
+    /// 1.41) cells long.
+    pub fn network_metrics(&self, sources: &[(usize, usize)], threshold: f32) -> NetworkMetrics {
+        let on_trail = |idx: usize| self.mask[idx] && self.tiles[idx].intensity() >= threshold;
+        let starts: Vec<Option<usize>> = sources
+            .iter()
+            .map(|&(col, row)| {
+                let idx = self
+                    .tiles
+                    .contains(col, row)
+                    .then(|| self.tiles.index(col, row));
+                idx.filter(|&idx| on_trail(idx))
+            })
+            .collect();
+        // Shortest trail distance between every pair of sources.
+        let distances: Vec<Vec<Option<f32>>> = starts
+            .iter()
+            .map(|start| {
+                let Some(start) = *start else {
+                    return vec![None; starts.len()];
+                };
+                let from_start = self.trail_distances(start, &on_trail);
+                starts
+                    .iter()
+                    .map(|end| end.and_then(|end| from_start[end]))
+                    .collect()
+            })
+            .collect();
+
+        let pairs = sources.len() * sources.len().saturating_sub(1) / 2;
+        let connected = (0..sources.len())
+            .flat_map(|i| (i + 1..sources.len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| distances[i][j].is_some())
+            .count();
+
+        // A minimum spanning forest over the pairwise distances: the least
+        // total path length that keeps every connected pair joined.
+        let mut in_tree = vec![false; sources.len()];
+        let mut total_length = 0.0;
+        for root in 0..sources.len() {
+            if in_tree[root] {
+                continue;
+            }
+            in_tree[root] = true;
+            let mut best: Vec<Option<f32>> = distances[root].clone();
+            while let Some((next, length)) = best
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| !in_tree[i])
+                .filter_map(|(i, d)| d.map(|d| (i, d)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+            {
+                in_tree[next] = true;
+                total_length += length;
+                for (i, d) in distances[next].iter().enumerate() {
+                    if let Some(d) = *d {
+                        if best[i].is_none_or(|b| d < b) {
+                            best[i] = Some(d);
+                        }
+                    }
+                }
+            }
+        }
+
+        NetworkMetrics {
+            connectivity: if pairs == 0 {
+                1.0
+            } else {
+                connected as f32 / pairs as f32
+            },
+            total_length,
+        }
+    }
+
+    /// Shortest distance from `start` to every cell along cells that are
+    /// `on_trail`, `None` for those it can't reach.
+    fn trail_distances(&self, start: usize, on_trail: &impl Fn(usize) -> bool) -> Vec<Option<f32>> {
+        let mut distances = vec![None; self.tiles.len()];
+        let mut queue = BinaryHeap::new();
+        distances[start] = Some(0.0);
+        queue.push(Reverse(PathStep(0.0, start)));
+        while let Some(Reverse(PathStep(distance, idx))) = queue.pop() {
+            if distances[idx].is_some_and(|best| distance > best) {
+                continue;
+            }
+            let (x, y) = self.tiles.coords(idx);
+            for ((nx, ny), (dx, dy)) in self.tiles.neighbours(x, y) {
+                let next = self.tiles.index(nx, ny);
+                let step = if dx != 0 && dy != 0 {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                let through = distance + step;
+                if on_trail(next) && distances[next].is_none_or(|best| through < best) {
+                    distances[next] = Some(through);
+                    queue.push(Reverse(PathStep(through, next)));
+                }
+            }
+        }
+        distances
+    }
+
+    /// Render the grid at one pixel per cell, row-major RGBA, for hosts that
+    /// show the simulation without a window of their own.
+    ///
+    /// `out` must be exactly `width() * height() * 4` bytes long.
+    pub fn render_to_buffer(&self, out: &mut [u8]) {
+        assert_eq!(
+            out.len(),
+            self.tiles.len() * 4,
+            "render buffer must hold width * height RGBA pixels"
+        );
+        for (idx, pixel) in out.chunks_exact_mut(4).enumerate() {
+            pixel.copy_from_slice(&self.cell_color(idx, 0.0));
+        }
+    }
+
+    /// Render this world and `other` at one pixel per cell, combined by
+    /// `mode`, into `out` as row-major RGBA.
+    ///
+    /// Both worlds must have the same dimensions, and `out` must be exactly
+    /// `width() * height() * 4` bytes long.
+    pub fn blend_into(&self, other: &World, mode: BlendMode, out: &mut [u8]) {
+        assert_eq!(
+            (self.width, self.height),
+            (other.width, other.height),
+            "blended worlds must share dimensions"
+        );
+        assert_eq!(
+            out.len(),
+            self.tiles.len() * 4,
+            "render buffer must hold width * height RGBA pixels"
+        );
+        for (idx, pixel) in out.chunks_exact_mut(4).enumerate() {
+            let (a, b) = (self.cell_color(idx, 0.0), other.cell_color(idx, 0.0));
+            for c in 0..3 {
+                pixel[c] = mode.blend(a[c], b[c]);
+            }
+            pixel[3] = 0xff;
+        }
+    }
+
+    /// RGBA color the cell at `idx` is drawn in, background outside the shape
+    /// and `WALL_COLOR` on walls.
+    /// `offset`, in `0.0..1.0`, is added to each channel before it's cut down
+    /// to 8 bits, for dithering.
+    fn cell_color(&self, idx: usize, offset: f32) -> [u8; 4] {
+        if self.age_colors
+            && self.render_mode == RenderMode::Rgb
+            && self.mask[idx]
+            && self.tiles[idx] != Cell::Empty
+        {
+            let t = (self.ages[idx] / AGE_SPAN).min(1.0);
+            let mix =
+                |hot: u8, cool: u8| (hot as f32 + (cool as f32 - hot as f32) * t + offset) as u8;
+            [
+                mix(self.hot_color.0, self.cool_color.0),
+                mix(self.hot_color.1, self.cool_color.1),
+                mix(self.hot_color.2, self.cool_color.2),
+                0xff,
+            ]
+        } else if self.mask[idx] {
+            self.tile_color(self.tiles[idx], offset)
+        } else if self.is_wall(idx) {
+            WALL_COLOR
+        } else {
+            BACKGROUND
+        }
+    }
+
+    /// RGBA color a cell is drawn in, with `offset` as for `cell_color`.
+    fn tile_color(&self, tile: Cell, offset: f32) -> [u8; 4] {
+        let gain = self.exposure_gain();
+        let curve = self.tone_curve;
+        let expose =
+            |c: f32| (curve.apply(c.clamp(0.0, MAX_HEAT) * gain) + offset).min(255.0) as u8;
+        match tile {
+            Cell::Empty => BACKGROUND,
+            Cell::Heat(..) if self.render_mode != RenderMode::Rgb => {
+                let v = (curve.apply(tile.intensity() * gain) + offset).min(255.0);
+                let (r, g, b) = match self.render_mode {
+                    RenderMode::Palette => gradient_color(&RENDER_GRADIENT, v / 255.0),
+                    _ => (v as u8, v as u8, v as u8),
+                };
+                [r, g, b, 0xff]
+            }
+            Cell::Heat(..) if self.fade_colors => {
+                let t = (curve.apply(tile.intensity() * gain) / 255.0).min(1.0);
+                let mix = |cool: u8, hot: u8| {
+                    (cool as f32 + (hot as f32 - cool as f32) * t + offset) as u8
+                };
+                [
+                    mix(self.cool_color.0, self.hot_color.0),
+                    mix(self.cool_color.1, self.hot_color.1),
+                    mix(self.cool_color.2, self.hot_color.2),
+                    0xff,
+                ]
+            }
+            Cell::Heat(r, g, b) => [expose(r), expose(g), expose(b), 0xff],
+        }
+    }
+}
+
+/// The part of a `frame_width` x `frame_height` frame `World::fit_to`
+/// fits the grid into: inside the default window's margins around it,
+/// scaled by how much bigger or smaller the frame is.
+fn grid_area(frame_width: usize, frame_height: usize) -> Rect {
+    let left = frame_width * CELLS_X / SCREEN_WIDTH as usize;
+    let top = frame_height * CELLS_Y / SCREEN_HEIGHT as usize;
+    let bottom = frame_height * CELLS_BOTTOM_MARGIN / SCREEN_HEIGHT as usize;
+    Rect {
+        x: left,
+        y: top,
+        width: frame_width.saturating_sub(2 * left),
+        height: frame_height.saturating_sub(top + bottom),
+    }
+}
+
+/// The color `t` of the way, from `0.0` to `1.0`, along the gradient
+/// through `stops`, blending linearly between neighbouring stops.
+pub fn gradient_color(stops: &[(u8, u8, u8)], t: f32) -> (u8, u8, u8) {
+    let Some(&last) = stops.last() else {
+        return (0, 0, 0);
+    };
+    let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = position as usize;
+    let Some(&next) = stops.get(i + 1) else {
+        return last;
+    };
+    let (from, f) = (stops[i], position.fract());
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * f).round() as u8;
+    (
+        mix(from.0, next.0),
+        mix(from.1, next.1),
+        mix(from.2, next.2),
+    )
+}
+
+/// The color in `palette` closest to `rgba` shifted by `nudge` on every
+/// channel, by squared distance. `rgba` itself when the palette is empty.
+fn nearest_color(rgba: [u8; 4], palette: &[(u8, u8, u8)], nudge: f32) -> [u8; 4] {
+    let target = [0, 1, 2].map(|c| rgba[c] as f32 + nudge);
+    let distance = |&(r, g, b): &(u8, u8, u8)| {
+        [r, g, b]
+            .iter()
+            .zip(target)
+            .map(|(&p, t)| (p as f32 - t).powi(2))
+            .sum::<f32>()
+    };
+    palette
+        .iter()
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .map_or(rgba, |&(r, g, b)| [r, g, b, rgba[3]])
+}
+
+/// Average `tiles` over `factor` x `factor` blocks, the last row and column
+/// of blocks covering whatever cells remain.
+fn downsample(tiles: &[Cell], width: usize, height: usize, factor: usize) -> Vec<Cell> {
+    let (out_width, out_height) = (width.div_ceil(factor), height.div_ceil(factor));
+    let mut out = Vec::with_capacity(out_width * out_height);
+    for by in 0..out_height {
+        for bx in 0..out_width {
+            let mut sum = [0.0; 3];
+            let mut count = 0;
+            for y in by * factor..((by + 1) * factor).min(height) {
+                for x in bx * factor..((bx + 1) * factor).min(width) {
+                    if let Cell::Heat(r, g, b) = tiles[x + y * width] {
+                        sum[0] += r;
+                        sum[1] += g;
+                        sum[2] += b;
+                    }
+                    count += 1;
+                }
+            }
+            out.push(match sum.map(|c| c / count as f32) {
+                [0.0, 0.0, 0.0] => Cell::Empty,
+                [r, g, b] => Cell::Heat(r, g, b),
+            });
+        }
+    }
+    out
+}
+
+/// A grid cell's heat, each channel from `0.0` up to `MAX_HEAT`. Channels
+/// stay fractional through every pass, so faint trails fade smoothly, and
+/// are only cut down to 8 bits when drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cell {
+    Empty,
+    Heat(f32, f32, f32),
+}
+
+impl Cell {
+    /// A cell at full heat in `rgb`.
+    pub fn from_rgb((r, g, b): (u8, u8, u8)) -> Self {
+        Cell::Heat(r as f32, g as f32, b as f32)
+    }
+
+    /// The channels, all `0.0` for empty cells.
+    pub fn channels(&self) -> [f32; 3] {
+        match *self {
+            Cell::Empty => [0.0; 3],
+            Cell::Heat(r, g, b) => [r, g, b],
+        }
+    }
+
+    /// The channels cut down to 8 bits, as they're drawn.
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        let [r, g, b] = self.channels().map(|c| c.clamp(0.0, MAX_HEAT) as u8);
+        (r, g, b)
+    }
+
+    /// Mean channel value, `0.0` for empty cells.
+    pub fn intensity(&self) -> f32 {
+        match *self {
+            Cell::Empty => 0.0,
+            Cell::Heat(r, g, b) => (r + g + b) / 3.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::Replay;
+
+    #[test]
+    fn normalization_conserves_mass() {
+        let mut world = World::new();
+        world.params.evaporation = 1.0;
+        world.params.evaporation_threshold = 0.0;
+        world.params.normalize_mass = true;
+        for (x, y) in [(50, 50), (150, 120), (200, 250)] {
+            world.set_tile(x, y, Cell::Heat(200.0, 100.0, 50.0));
+        }
+        let initial = world.total_intensity();
+
+        for _ in 0..5 {
+            world.update_tiles();
+            let total = world.total_intensity();
+            assert!(
+                (total - initial).abs() <= initial * 1e-4,
+                "mass drifted from {initial} to {total}"
+            );
+        }
+    }
+
+    #[test]
+    fn sparse_update_matches_dense() {
+        let mut dense = World::new();
+        for (x, y) in [(0, 0), (10, 20), (150, 150), (299, 299)] {
+            dense.set_tile(x, y, Cell::Heat(250.0, 120.0, 30.0));
+        }
+        let mut sparse = dense.clone();
+        sparse.params.grid_update = GridUpdate::Sparse {
+            max_active_fraction: 1.0,
+        };
+
+        for _ in 0..10 {
+            dense.update_tiles();
+            sparse.update_tiles();
+            assert!(dense.tiles == sparse.tiles);
+        }
+    }
+
+    #[test]
+    fn evaporated_cells_leave_the_active_set() {
+        for grid_update in [
+            GridUpdate::Dense,
+            GridUpdate::InPlace,
+            GridUpdate::Sparse {
+                max_active_fraction: 1.0,
+            },
+        ] {
+            let mut world = World::with_size(40, 30, 2);
+            world.params.grid_update = grid_update;
+            world.params.evaporation = 0.5;
+            world.set_tile(10, 10, Cell::Heat(40.0, 0.0, 0.0));
+            world.set_tile(30, 20, Cell::Heat(255.0, 255.0, 255.0));
+            for _ in 0..6 {
+                world.update_tiles();
+                let heated = world.tiles.iter().filter(|cell| **cell != Cell::Empty);
+                assert_eq!(world.sparse.active.len(), heated.count(), "{grid_update:?}");
+                assert!(world
+                    .sparse
+                    .active
+                    .iter()
+                    .all(|&idx| world.sparse.active_mask[idx] && world.tiles[idx] != Cell::Empty));
+            }
+        }
+    }
+
+    fn strong_trail_world(seed: u64) -> World {
+        let mut world = World::new_seeded(seed);
+        world.params.reproduction_chance = 0.5;
+        world.params.max_agents = 40;
+        for y in 0..world.height() {
+            for x in 0..world.width() {
+                world.set_tile(x, y, Cell::Heat(255.0, 255.0, 255.0));
+            }
+        }
+        world.spawn_agent(150.0, 150.0);
+        world
+    }
+
+    #[test]
+    fn reproduction_is_capped_and_seeded() {
+        let mut a = strong_trail_world(7);
+        let mut b = strong_trail_world(7);
+        for _ in 0..20 {
+            a.update_agents();
+            b.update_agents();
+        }
+
+        assert_eq!(a.agents().len(), a.params.max_agents);
+        let positions = |w: &World| w.agents().iter().map(|a| (a.x, a.y)).collect::<Vec<_>>();
+        assert_eq!(positions(&a), positions(&b));
+    }
+
+    #[test]
+    fn replaying_a_recording_reproduces_the_run() {
+        let inputs = [
+            (0, Command::Spawn { x: 150, y: 210 }),
+            (3, Command::ToggleOrbit),
+            (3, Command::Spawn { x: 200, y: 230 }),
+            (8, Command::Spawn { x: 120, y: 220 }),
+        ];
+
+        let mut live = World::new_seeded(99);
+        live.start_recording("unused.txt");
+        for step in 0..12 {
+            for (_, command) in inputs.iter().filter(|(at, _)| *at == step) {
+                live.apply(*command);
+            }
+            live.update();
+        }
+        let script: InputScript = live.take_recording().unwrap().to_string().parse().unwrap();
+
+        let mut replayed = World::new_seeded(script.seed);
+        let mut replay = Replay::new(script);
+        for _ in 0..12 {
+            for command in replay.due(replayed.step()).collect::<Vec<_>>() {
+                replayed.apply(command);
+            }
+            replayed.update();
+        }
+
+        assert!(replay.finished());
+        assert!(live.tiles == replayed.tiles);
+        let positions = |w: &World| w.agents().iter().map(|a| (a.x, a.y)).collect::<Vec<_>>();
+        assert_eq!(positions(&live), positions(&replayed));
+    }
+
+    #[test]
+    fn shutdown_writes_the_recording() {
+        let path =
+            std::env::temp_dir().join(format!("z_slime_shutdown_{}.txt", std::process::id()));
+        let mut world = World::new_seeded(5);
+        world.start_recording(&path);
+        world.apply(Command::ToggleOrbit);
+        world.shutdown();
+
+        let script = InputScript::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(script.seed, 5);
+        assert_eq!(script.events, vec![(0, Command::ToggleOrbit)]);
+    }
+
+    #[test]
+    fn shutdown_writes_the_autosave() {
+        let dir = std::env::temp_dir().join(format!("z_slime_autosave_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        let mut world = World::with_size(16, 12, 9);
+        world.spawn_random(5);
+        world.update();
+        world.autosave_to(&path);
+        world.shutdown();
+
+        let loaded = World::load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.step(), world.step());
+        assert_eq!(loaded.tiles(), world.tiles());
+        let place = |agent: &Agent| (agent.id(), agent.x, agent.y);
+        assert!(loaded
+            .agents()
+            .iter()
+            .map(place)
+            .eq(world.agents().iter().map(place)));
+
+        // A second shutdown has nothing left to write.
+        world.shutdown();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn fading_trails_shift_from_hot_to_cool() {
+        let mut world = World::new();
+        world.fade_colors = true;
+        let (hot, cool) = (world.hot_color, world.cool_color);
+
+        assert_eq!(
+            world.tile_color(Cell::Heat(255.0, 255.0, 255.0), 0.0),
+            [hot.0, hot.1, hot.2, 0xff]
+        );
+        assert_eq!(
+            world.tile_color(Cell::Heat(0.0, 0.0, 0.0), 0.0),
+            [cool.0, cool.1, cool.2, 0xff]
+        );
+        assert_eq!(world.tile_color(Cell::Empty, 0.0), BACKGROUND);
+    }
+
+    #[test]
+    fn render_modes_draw_intensity_in_gray_or_along_a_gradient() {
+        let mut world = World::new();
+        let tile = Cell::Heat(30.0, 60.0, 90.0);
+        assert_eq!(world.tile_color(tile, 0.0), [30, 60, 90, 0xff]);
+
+        world.cycle_render_mode();
+        assert_eq!(world.render_mode, RenderMode::Grayscale);
+        assert_eq!(world.tile_color(tile, 0.0), [60, 60, 60, 0xff]);
+        assert_eq!(world.tile_color(Cell::Empty, 0.0), BACKGROUND);
+
+        world.cycle_render_mode();
+        let half = Cell::Heat(127.5, 127.5, 127.5);
+        assert_eq!(world.tile_color(half, 0.0), [0, 0, 255, 0xff]);
+        let full = Cell::Heat(255.0, 255.0, 255.0);
+        assert_eq!(world.tile_color(full, 0.0), [255, 255, 255, 0xff]);
+        assert_eq!(world.tile_color(Cell::Empty, 0.0), BACKGROUND);
+
+        world.cycle_render_mode();
+        assert_eq!(world.render_mode, RenderMode::Rgb);
+    }
+
+    #[test]
+    fn render_to_buffer_writes_one_pixel_per_cell() {
+        let mut world = World::new();
+        world.set_tile(3, 2, Cell::Heat(10.0, 20.0, 30.0));
+        world.set_tile(0, 0, Cell::Heat(40.0, 50.0, 60.0));
+        let mut buffer = vec![0; world.width() * world.height() * 4];
+        world.render_to_buffer(&mut buffer);
+
+        let idx = (3 + 2 * world.width()) * 4;
+        assert_eq!(buffer[idx..idx + 4], [10, 20, 30, 0xff]);
+        // The first row and column are cells too, not background.
+        assert_eq!(buffer[..4], [40, 50, 60, 0xff]);
+    }
+
+    #[test]
+    fn spawns_use_the_selected_palette_color() {
+        let mut world = World::new_seeded(1);
+        world.apply(Command::CycleColor);
+        world.apply(Command::CycleColor);
+        world.spawn_agent(10.0, 10.0);
+
+        assert_eq!(world.agents()[0].rgb, SPAWN_PALETTE[1]);
+    }
+
+    #[test]
+    fn diffusion_counts_the_center_once() {
+        let mut world = World::new();
+        world.params.evaporation = 1.0;
+        world.set_tile(100, 100, Cell::Heat(180.0, 90.0, 45.0));
+        world.update_tiles();
+
+        // A lone source spreads evenly over its 3x3 box with weight 1/9 each.
+        for y in 99..=101 {
+            for x in 99..=101 {
+                assert_eq!(
+                    world.cell_at(x as f32, y as f32),
+                    Some(Cell::Heat(20.0, 10.0, 5.0))
+                );
+            }
+        }
+        assert_eq!(world.cell_at(102.0, 100.0), Some(Cell::Empty));
+    }
+
+    #[test]
+    fn off_grid_agents_are_culled() {
+        let mut world = World::new_seeded(3);
+        world.spawn_agent(150.0, 150.0);
+        world.spawn_agent(-50.0, 20.0);
+        world.spawn_agent(20.0, 1000.0);
+        world.update_agents();
+
+        assert_eq!(world.agents().len(), 1);
+        assert!(world.agents()[0].x > 100.0);
+    }
+
+    #[test]
+    fn smooth_markers_split_coverage_by_subpixel_position() {
+        let mut world = World::new_seeded(4);
+        world.agent_markers = AgentMarkers::Smooth;
+        // Lands a quarter of a pixel right of and half a pixel below (200, 260).
+        world.spawn_agent(50.125, 30.25);
+        let mut frame = vec![0xff; (SCREEN_WIDTH * SCREEN_HEIGHT * 4) as usize];
+        world.draw_agents(&mut frame, SCREEN_WIDTH as usize, world.view(), 1.0);
+
+        let red = |x: usize, y: usize| frame[(x + y * SCREEN_WIDTH as usize) * 4];
+        // Darkened by 255 * coverage, with coverage 0.375, 0.125, 0.375, 0.125.
+        assert_eq!(red(200, 260), 159);
+        assert_eq!(red(201, 260), 223);
+        assert_eq!(red(200, 261), 159);
+        assert_eq!(red(201, 261), 223);
+        assert_eq!(red(202, 260), 0xff);
+    }
+
+    #[test]
+    fn reset_restores_defaults_but_keeps_the_grid() {
+        let mut world = World::new_seeded(8);
+        world.set_tile(5, 5, Cell::Heat(9.0, 9.0, 9.0));
+        world.spawn_agent(20.0, 20.0);
+        world.apply(Command::ToggleSensing);
+        world.apply(Command::ToggleTurnMode);
+        world.params.reproduction_chance = 0.3;
+        world.apply(Command::ResetParams);
+
+        assert_eq!(world.params, world.defaults);
+        assert_eq!(world.cell_at(5.0, 5.0), Some(Cell::Heat(9.0, 9.0, 9.0)));
+        assert_eq!(world.agents().len(), 1);
+    }
+
+    #[test]
+    fn disc_mask_walls_off_the_corners() {
+        let mut world = World::new_seeded(2);
+        world.set_shape(Shape::Disc);
+        // Heat just inside the disc's top-left edge must not leak past it.
+        world.set_tile(60, 60, Cell::Heat(255.0, 255.0, 255.0));
+        world.set_tile(10, 10, Cell::Heat(255.0, 255.0, 255.0));
+        world.update_tiles();
+        world.update_tiles();
+
+        assert_eq!(world.cell_at(10.0, 10.0), Some(Cell::Empty));
+        assert!(world.cell_at(60.0, 60.0).unwrap() != Cell::Empty);
+
+        let mut buffer = vec![0; world.width() * world.height() * 4];
+        world.set_tile(0, 0, Cell::Heat(1.0, 2.0, 3.0));
+        world.render_to_buffer(&mut buffer);
+        assert_eq!(buffer[..4], BACKGROUND);
+    }
+
+    #[test]
+    fn agents_reflect_off_the_disc_edge() {
+        let mut world = World::new_seeded(6);
+        world.set_shape(Shape::Disc);
+        for i in 0..20 {
+            world.spawn_agent(150.0 + i as f32 * 7.0, 150.0);
+        }
+        for _ in 0..400 {
+            world.update_agents();
+        }
+
+        assert_eq!(world.agents().len(), 20);
+        for agent in world.agents() {
+            assert!(world.shape_contains(agent.x, agent.y));
+        }
+    }
+
+    #[test]
+    fn clicks_deposit_under_the_cursor() {
+        let mut world = World::new_seeded(12);
+        world.apply(Command::Spawn {
+            x: (CELLS_X + 2 * 120 + 1) as i16,
+            y: (CELLS_Y + 2 * 45 + 1) as i16,
+        });
+        let agent = &world.agents()[0];
+        assert_eq!((agent.x, agent.y), (120.5, 45.5));
+
+        world.update_agents();
+        let agent = &world.agents()[0];
+        let (col, row) = (agent.x as usize, agent.y as usize);
+        assert!(col.abs_diff(120) <= 1 && row.abs_diff(45) <= 1);
+        let heated: Vec<_> = (0..world.tiles().len())
+            .filter(|&idx| world.tiles()[idx] != Cell::Empty)
+            .collect();
+        assert_eq!(heated, [col + row * world.width()]);
+    }
+
+    #[test]
+    fn agents_steer_onto_trails_by_default() {
+        let run = |sensing| {
+            let mut world = World::with_size(60, 60, 0);
+            world.params.sensing = sensing;
+            for x in 0..60 {
+                world.set_tile(x, 30, Cell::Heat(255.0, 255.0, 255.0));
+            }
+            world.push_agent(Agent::with_heading(5.0, 27.0, (255, 255, 255), 0.0));
+            for _ in 0..10 {
+                world.update_agents();
+            }
+            world.agents()[0].y
+        };
+        assert_eq!(run(false), 27.0);
+        assert!((run(true) - 30.5).abs() < 1.0, "{}", run(true));
+    }
+
+    #[test]
+    fn walls_keep_agents_and_heat_on_their_side() {
+        let mut world = World::with_size(40, 40, 8);
+        world.set_tile(20, 5, Cell::Heat(255.0, 255.0, 255.0));
+        world
+            .set_walls(Some((0..1600).map(|idx| idx % 40 == 20).collect()))
+            .unwrap();
+        assert_eq!(world.cell_at(20.0, 5.0), Some(Cell::Empty));
+
+        for i in 0..20 {
+            world.spawn_agent(5.0 + i as f32 * 0.5, 2.0 + i as f32 * 1.5);
+        }
+        for _ in 0..300 {
+            world.update();
+        }
+        assert!(world.agents().iter().all(|agent| agent.x < 20.0));
+        for y in 0..40 {
+            assert_eq!(world.cell_at(20.0, y as f32), Some(Cell::Empty));
+            assert_eq!(world.cell_at(30.0, y as f32), Some(Cell::Empty));
+        }
+        assert!(world.set_walls(Some(vec![true; 3])).is_err());
+    }
+
+    #[test]
+    fn sharp_fronts_hold_heat_against_empty_cells() {
+        let run = |sharp_fronts| {
+            let mut world = World::with_size(40, 40, 0);
+            world.params.sharp_fronts = sharp_fronts;
+            world
+                .set_walls(Some((0..1600).map(|idx| idx % 40 == 20).collect()))
+                .unwrap();
+            for y in 10..30 {
+                world.set_tile(19, y, Cell::Heat(200.0, 200.0, 200.0));
+            }
+            world.set_tile(5, 5, Cell::Heat(240.0, 0.0, 0.0));
+            for _ in 0..4 {
+                world.update_tiles();
+            }
+            world
+        };
+        let (soft, sharp) = (run(None), run(Some(0.5)));
+
+        for world in [&soft, &sharp] {
+            for y in 0..40 {
+                assert_eq!(world.cell_at(20.0, y as f32), Some(Cell::Empty));
+                assert_eq!(world.cell_at(21.0, y as f32), Some(Cell::Empty));
+            }
+        }
+        let heat = |world: &World, x: f32, y: f32| world.cell_at(x, y).unwrap().intensity();
+        // Walls take no heat from either, so only the floor tells them apart.
+        assert!(heat(&sharp, 19.0, 20.0) >= heat(&soft, 19.0, 20.0));
+        // Each pass keeps at least half of the lone cell's heat.
+        assert!(heat(&sharp, 5.0, 5.0) >= 240.0 / 3.0 / 16.0);
+        assert!(heat(&sharp, 5.0, 5.0) > heat(&soft, 5.0, 5.0));
+    }
+
+    #[test]
+    fn walls_take_no_heat_without_sharp_fronts() {
+        let mut world = World::with_size(10, 10, 0);
+        world.params.evaporation = 1.0;
+        world
+            .set_walls(Some((0..100).map(|idx| idx % 10 == 5).collect()))
+            .unwrap();
+        for y in 0..10 {
+            for x in (0..10).filter(|&x| x != 5) {
+                world.set_tile(x, y, Cell::Heat(100.0, 100.0, 100.0));
+            }
+        }
+        world.params.kernel = Kernel::Gaussian { sigma: 1.0 };
+        world.update_tiles();
+        world.params.kernel = Kernel::Box;
+        world.update_tiles();
+
+        for y in 0..10 {
+            for x in (0..10).filter(|&x| x != 5) {
+                let heat = world.cell_at(x as f32, y as f32).unwrap().intensity();
+                assert!((heat - 100.0).abs() < 1e-3, "({x}, {y}) holds {heat}");
+            }
+        }
+    }
+
+    #[test]
+    fn corner_heat_spreads_evenly_over_the_cells_on_the_grid() {
+        let mut world = World::with_size(10, 10, 0);
+        world.params.evaporation = 1.0;
+        world.set_tile(0, 0, Cell::Heat(200.0, 200.0, 200.0));
+        world.update_tiles();
+        // The corner averages four cells, the edge cells beside it six, and
+        // the cell inside it all nine.
+        for (x, y, heat) in [
+            (0.0, 0.0, 50),
+            (1.0, 0.0, 33),
+            (0.0, 1.0, 33),
+            (1.0, 1.0, 22),
+        ] {
+            assert_eq!(
+                world.cell_at(x, y).map(|cell| cell.rgb()),
+                Some((heat, heat, heat))
+            );
+        }
+        assert_eq!(world.cell_at(2.0, 0.0), Some(Cell::Empty));
+
+        // Weighted kernels leave the off-grid weights out the same way.
+        let mut world = World::with_size(10, 10, 0);
+        world.params.evaporation = 1.0;
+        world.params.kernel = Kernel::Gaussian { sigma: 1.0 };
+        world.set_tile(9, 9, Cell::Heat(200.0, 200.0, 200.0));
+        world.update_tiles();
+        assert_eq!(world.cell_at(8.0, 9.0), world.cell_at(9.0, 8.0));
+        let corner = world.cell_at(9.0, 9.0).unwrap().intensity();
+        assert!(corner > world.cell_at(8.0, 8.0).unwrap().intensity());
+        assert!(corner > 200.0 / 9.0 * 3.0);
+    }
+
+    #[test]
+    fn channels_diffuse_at_their_own_rates() {
+        let mut world = World::new();
+        world.params.evaporation = 1.0;
+        world.params.diffusion_rate = [1.0, 0.5, 0.1];
+        world.set_tile(100, 100, Cell::Heat(255.0, 255.0, 255.0));
+        world.update_tiles();
+
+        assert_eq!(
+            world.cell_at(100.0, 100.0).map(|cell| cell.rgb()),
+            Some((28, 141, 232))
+        );
+        assert_eq!(
+            world.cell_at(101.0, 100.0).map(|cell| cell.rgb()),
+            Some((28, 14, 2))
+        );
+    }
+
+    #[test]
+    fn faint_heat_spreads_without_rounding_away() {
+        let mut world = World::with_size(20, 20, 0);
+        world.params.evaporation = 1.0;
+        world.params.evaporation_threshold = 0.0;
+        world.set_tile(10, 10, Cell::Heat(8.0, 0.0, 0.0));
+        for _ in 0..2 {
+            world.update_tiles();
+        }
+
+        // A ninth of 8 would have truncated to nothing on the first pass;
+        // now the heat reaches two cells out and none of it is lost.
+        assert!(world.cell_at(12.0, 12.0).unwrap().intensity() > 0.0);
+        let total: f32 = world.tiles().iter().map(|tile| tile.channels()[0]).sum();
+        assert!((total - 8.0).abs() < 1e-3, "{total}");
+        assert_eq!(world.diffusion_stats().underflowed, 0);
+    }
+
+    #[test]
+    fn downsampled_field_averages_blocks() {
+        let mut tiles = vec![Cell::Empty; 5 * 3];
+        tiles[0] = Cell::Heat(200.0, 0.0, 40.0);
+        tiles[1] = Cell::Heat(200.0, 0.0, 0.0);
+        tiles[4] = Cell::Heat(0.0, 90.0, 0.0);
+
+        let field = downsample(&tiles, 5, 3, 2);
+
+        // 3x2 blocks; the right column and bottom row cover leftover cells.
+        assert_eq!(field.len(), 6);
+        assert_eq!(field[0], Cell::Heat(100.0, 0.0, 10.0));
+        assert_eq!(field[1], Cell::Empty);
+        assert_eq!(field[2], Cell::Heat(0.0, 45.0, 0.0));
+    }
+
+    #[test]
+    fn seeded_runs_repeat_exactly() {
+        let run = |seed| {
+            let mut world = World::with_size(80, 60, seed);
+            world.spawn_random(40);
+            world.advance(30);
+            let positions: Vec<_> = world.agents().iter().map(|a| (a.x, a.y)).collect();
+            (world.tiles().to_vec(), positions)
+        };
+
+        let (tiles, positions) = run(21);
+        assert!(tiles.iter().any(|&tile| tile != Cell::Empty));
+        assert_eq!(run(21), (tiles.clone(), positions));
+        assert_ne!(run(22).0, tiles);
+    }
+
+    #[test]
+    fn scatters_over_a_grid_with_no_open_cells_place_nothing() {
+        let mut world = World::with_size(6, 4, 0);
+        world.set_walls(Some(vec![true; 24])).unwrap();
+        world.spawn_random(10);
+        assert!(world.agents().is_empty());
+
+        let mut world = World::with_size(0, 0, 0);
+        world.spawn_random(10);
+        assert!(world.agents().is_empty());
+    }
+
+    #[test]
+    fn random_spawns_are_seeded_and_capped() {
+        let scatter = |seed| {
+            let mut world = World::new_seeded(seed);
+            world.params.max_agents = 50;
+            world.spawn_random(80);
+            world
+        };
+
+        let world = scatter(9);
+        assert_eq!(world.agents().len(), 50);
+        assert!(world
+            .agents()
+            .iter()
+            .all(|agent| world.shape_contains(agent.x, agent.y)));
+
+        let positions = |world: &World| -> Vec<(f32, f32)> {
+            world.agents().iter().map(|a| (a.x, a.y)).collect()
+        };
+        assert_eq!(positions(&world), positions(&scatter(9)));
+        assert_ne!(positions(&world), positions(&scatter(10)));
+    }
+
+    #[test]
+    fn update_order_decides_whether_fresh_trails_are_blurred() {
+        let brightest = |order| {
+            let mut world = World::with_size(20, 20, 0);
+            world.params.evaporation = 1.0;
+            world.params.update_order = order;
+            world
+                .agents
+                .push(Agent::with_heading(10.5, 10.5, (210, 0, 0), 0.0));
+            world.update();
+            world.tiles.iter().map(|tile| tile.rgb().0).max()
+        };
+
+        assert_eq!(brightest(UpdateOrder::DepositThenDiffuse), Some(23));
+        assert_eq!(brightest(UpdateOrder::DiffuseThenDeposit), Some(210));
+    }
+
+    #[test]
+    fn bilateral_kernels_keep_ridges_sharp() {
+        let ridge = |kernel| {
+            let mut world = World::with_size(20, 20, 0);
+            world.params.evaporation = 1.0;
+            world.params.kernel = kernel;
+            for x in 0..20 {
+                world.set_tile(x, 10, Cell::Heat(255.0, 255.0, 255.0));
+            }
+            world.update_tiles();
+            let intensity = |y: f32| world.cell_at(10.0, y).map_or(0.0, |cell| cell.intensity());
+            (intensity(10.0), intensity(9.0))
+        };
+
+        assert_eq!(ridge(Kernel::Box), (85.0, 85.0));
+        let (peak, beside) = ridge(Kernel::Gaussian { sigma: 1.0 });
+        assert!(
+            peak > 85.0 && beside > 0.0 && beside < 85.0,
+            "{peak} {beside}"
+        );
+        let (peak, beside) = ridge(Kernel::Bilateral {
+            spatial_sigma: 1.0,
+            intensity_sigma: 20.0,
+        });
+        assert_eq!((peak, beside), (255.0, 0.0));
+    }
+
+    #[test]
+    fn anisotropy_keeps_stripes_from_spreading_sideways() {
+        let spread_beside_stripe = |anisotropy| {
+            let mut world = World::new_seeded(0);
+            world.params.evaporation = 1.0;
+            world.params.anisotropy = anisotropy;
+            for x in 0..world.width() {
+                world.set_tile(x, 150, Cell::Heat(255.0, 255.0, 255.0));
+            }
+            world.update_tiles();
+            world
+                .cell_at(150.0, 149.0)
+                .map_or(0.0, |cell| cell.intensity())
+        };
+
+        let isotropic = spread_beside_stripe(0.0);
+        let anisotropic = spread_beside_stripe(1.0);
+        assert_eq!(isotropic, 85.0);
+        assert!(anisotropic < isotropic, "{anisotropic} >= {isotropic}");
+    }
+
+    #[test]
+    fn spawns_and_culls_reach_the_event_callback() {
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut world = World::new_seeded(4);
+        let sink = events.clone();
+        world.on_event(move |event| sink.lock().unwrap().push(event));
+        world.color_strategy = ColorStrategy::Fixed;
+        // A hooked world still has to move to a simulation thread.
+        fn assert_send<T: Send>(_: &T) {}
+        assert_send(&world);
+
+        world.spawn_agent(150.0, 150.0);
+        world.agents[0].x = -10.0;
+        world.update_agents();
+
+        let events = events.lock().unwrap();
+        let rgb = SPAWN_PALETTE[0];
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0],
+            SimEvent::Spawn {
+                id: 1,
+                x: 150.0,
+                y: 150.0,
+                rgb
+            }
+        );
+        assert!(
+            matches!(events[1], SimEvent::Cull { id: 1, x, rgb: culled, .. } if x < 0.0 && culled == rgb)
+        );
+    }
+
+    #[test]
+    fn spawns_deaths_and_culls_are_all_counted() {
+        let counts = std::sync::Arc::new(std::sync::Mutex::new([0; 3]));
+        let mut world = World::with_size(20, 20, 6);
+        let sink = counts.clone();
+        world.on_event(move |event| {
+            let kind = match event {
+                SimEvent::Spawn { .. } => 0,
+                SimEvent::Death { .. } => 1,
+                SimEvent::Cull { .. } => 2,
+            };
+            sink.lock().unwrap()[kind] += 1;
+        });
+        let path = std::env::temp_dir().join(format!("z_slime_deaths_{}.json", std::process::id()));
+        world.spawn_random(4);
+        world.save(&path).unwrap();
+
+        world.agents[0].x = -10.0;
+        world.update_agents();
+        world.reset();
+        world.spawn_random(2);
+        world.restore(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        world.resize(0, 0);
+
+        // Four scattered and two more, one culled, three lost to the reset,
+        // two to the restore and the four restored ones to the resize.
+        assert_eq!(*counts.lock().unwrap(), [6, 9, 1]);
+    }
+
+    #[test]
+    fn species_mixes_split_scatters_and_steer_clicks() {
+        let mut world = World::new_seeded(9);
+        world.species = Some("ff0000=70,0000ff=30".parse().unwrap());
+        world.spawn_random(200);
+        let red = world
+            .agents()
+            .iter()
+            .filter(|agent| agent.rgb == (255, 0, 0))
+            .count();
+        assert_eq!(red, 140);
+        assert!(world.agents()[..140]
+            .iter()
+            .any(|agent| agent.rgb != (255, 0, 0)));
+
+        world.apply(Command::SelectSpecies { index: 1 });
+        for _ in 0..5 {
+            world.spawn_agent(150.0, 150.0);
+        }
+        assert!(world.agents()[200..]
+            .iter()
+            .all(|agent| agent.rgb == (0, 0, 255)));
+        world.apply(Command::SelectSpecies { index: 5 });
+        assert_eq!(world.selected_species, Some(1));
+    }
+
+    #[test]
+    fn species_blind_to_each_other_form_disjoint_networks() {
+        // The share of trail cells heavy in both red and blue.
+        let overlap = |cross_channel_weight: f32| {
+            let mut world = World::with_size(64, 64, 21);
+            world.species = Some("ff0000=50,0000ff=50".parse().unwrap());
+            world.params.sensors.cross_channel_weight = cross_channel_weight;
+            world.spawn_random(600);
+            world.advance(300);
+            let heavy = |c: f32| c >= 32.0;
+            let (mut either, mut both) = (0, 0);
+            for [r, _, b] in world.tiles().iter().map(Cell::channels) {
+                either += (heavy(r) || heavy(b)) as u32;
+                both += (heavy(r) && heavy(b)) as u32;
+            }
+            both as f32 / either.max(1) as f32
+        };
+        assert!(overlap(0.0) < 0.25);
+        // Sensing each other's trails, the species share one network.
+        assert!(overlap(1.0) > 0.6);
+    }
+
+    #[test]
+    fn agents_wait_out_the_burn_in() {
+        let mut world = World::new_seeded(4);
+        world.params.burn_in = 3;
+        world.spawn_agent(150.0, 150.0);
+        world.set_tile(10, 10, Cell::Heat(255.0, 255.0, 255.0));
+        world.advance(3);
+        assert_eq!((world.agents[0].x, world.agents[0].y), (150.0, 150.0));
+        assert!(world.cell_at(10.0, 10.0).unwrap().intensity() < 255.0);
+        assert!(world.cell_at(12.0, 10.0).unwrap().intensity() > 0.0);
+
+        world.update();
+        assert_ne!((world.agents[0].x, world.agents[0].y), (150.0, 150.0));
+    }
+
+    #[test]
+    fn agent_ids_are_never_reused() {
+        let mut world = World::new_seeded(4);
+        world.spawn_random(3);
+        world.agents[0].x = -10.0;
+        world.update_agents();
+        world.spawn_random(2);
+        let ids: Vec<u64> = world.agents().iter().map(Agent::id).collect();
+        assert_eq!(ids, [2, 3, 4, 5]);
+
+        let clone = world.clone();
+        world.spawn_random(1);
+        assert_eq!(world.agents()[4].id(), 6);
+        assert_eq!(clone.agents()[0].id(), 2);
+    }
+
+    #[test]
+    fn draw_maps_cells_of_a_resized_grid() {
+        let mut world = World::with_size(50, 40, 0);
+        world.set_tile(10, 20, Cell::Heat(9.0, 8.0, 7.0));
+        world.set_tile(0, 0, Cell::Heat(6.0, 5.0, 4.0));
+        let mut frame = vec![0; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4];
+        world.draw(&mut frame);
+
+        let pixel = |x: f32, y: f32| {
+            let (x, y) = (x as usize + CELLS_X, y as usize + CELLS_Y);
+            let idx = (x + y * SCREEN_WIDTH as usize) * 4;
+            frame[idx..idx + 4].to_vec()
+        };
+        let scale = world.draw_scale;
+        assert_eq!(
+            pixel(10.0 * scale + 1.0, 20.0 * scale + 1.0),
+            [9, 8, 7, 0xff]
+        );
+        assert_eq!(pixel(0.0, 0.0), [6, 5, 4, 0xff]);
+        assert_eq!(pixel(50.0 * scale + 1.0, 20.0 * scale + 1.0), BACKGROUND);
+        assert!(!world.mouse_inside_world((CELLS_X as f32 + 50.0 * scale + 1.0) as i16, 250));
+    }
+
+    #[test]
+    fn trail_age_counts_updates_and_blends_by_intensity() {
+        let mut world = World::new_seeded(0);
+        world.set_tile(50, 50, Cell::Heat(255.0, 255.0, 255.0));
+        for _ in 0..3 {
+            world.update_tiles();
+        }
+        assert_eq!(world.age_at(50.0, 50.0), Some(3.0));
+        assert_eq!(world.age_at(51.0, 50.0), Some(3.0));
+
+        // Fresh heat beside the old patch pulls the ages between them down.
+        world.set_tile(53, 50, Cell::Heat(255.0, 255.0, 255.0));
+        world.update_tiles();
+        let blended = world.age_at(52.0, 50.0).unwrap();
+        assert!(blended > 1.0 && blended < 4.0, "{blended}");
+    }
+
+    #[test]
+    fn reset_empties_the_grid_in_place_but_keeps_sources() {
+        let mut world = World::with_size(30, 30, 1);
+        world.params.motion.speed = 2.0;
+        world.spawn_random(20);
+        world.add_source(5, 5, (0, 200, 0));
+        world.advance(10);
+        let tiles = world.tiles().as_ptr();
+
+        world.apply(Command::Reset);
+        assert!(world.agents().is_empty());
+        assert_eq!(world.tiles().as_ptr(), tiles);
+        for (idx, tile) in world.tiles().iter().enumerate() {
+            let expected = match idx {
+                155 => Cell::Heat(0.0, 200.0, 0.0),
+                _ => Cell::Empty,
+            };
+            assert_eq!(*tile, expected);
+        }
+        assert_eq!(world.params.motion.speed, 2.0);
+    }
+
+    #[test]
+    fn brushes_scatter_agents_around_the_cursor_on_the_grid_only() {
+        let mut world = World::with_size(50, 40, 0);
+        world.params.brush_count = 10;
+        world.apply(Command::Brush {
+            x: (CELLS_X + 50) as i16,
+            y: (CELLS_Y + 40) as i16,
+        });
+        assert_eq!(world.agents().len(), 10);
+        assert!(world
+            .agents()
+            .iter()
+            .all(|agent| { (agent.x - 25.0).hypot(agent.y - 20.0) <= world.params.brush_radius }));
+
+        // Past the grid's edge the brush only keeps the agents that land on it.
+        world.spawn_brush(0.5, 39.5, 4.0, 200);
+        assert!(world.agents().len() < 210);
+        assert!(world
+            .agents()
+            .iter()
+            .all(|agent| agent.x >= 0.0 && agent.y < 40.0));
+        let count = world.agents().len();
+        world.apply(Command::Brush { x: 10, y: 10 });
+        assert_eq!(world.agents().len(), count);
+    }
+
+    #[test]
+    fn fitting_a_frame_centers_the_grid_and_keeps_clicks_on_their_cells() {
+        let mut world = World::new_seeded(1);
+        world.fit_to(SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize);
+        let default_view = Rect {
+            x: CELLS_X,
+            y: CELLS_Y,
+            width: 600,
+            height: 600,
+        };
+        assert_eq!((world.draw_scale, world.view()), (SCALE, default_view));
+
+        // A wide window is limited by its height and centers the grid.
+        world.fit_to(1600, 900);
+        assert_eq!(world.draw_scale, 2.0);
+        assert_eq!((world.view().x, world.view().y), (500, 200));
+
+        // One too small for a pixel a cell shrinks the grid to fit.
+        world.fit_to(200, 180);
+        let view = world.view();
+        assert_eq!(world.draw_scale, 0.4);
+        assert!(view.x + view.width <= 200 && view.y + view.height <= 180);
+        world.set_tile(102, 52, Cell::Heat(0.0, 0.0, 200.0));
+        let mut frame = vec![0; 200 * 180 * 4];
+        world.draw(&mut frame);
+        let (x, y) = (view.x + 41, view.y + 21);
+        let idx = (x + y * 200) * 4;
+        assert_eq!(frame[idx..idx + 4], [0, 0, 200, 0xff]);
+        assert_eq!(world.screen_to_grid(x, y), Some((102, 52)));
+        world.apply(Command::Spawn {
+            x: x as i16,
+            y: y as i16,
+        });
+        let agent = &world.agents()[0];
+        assert_eq!((agent.x as usize, agent.y as usize), (102, 52));
+    }
+
+    #[test]
+    fn screen_to_grid_matches_the_drawn_cells() {
+        let mut world = World::with_size(50, 40, 0);
+        world.set_tile(10, 20, Cell::Heat(9.0, 8.0, 7.0));
+        let scale = world.draw_scale;
+        let (x, y) = (
+            CELLS_X + (10.0 * scale) as usize + 1,
+            CELLS_Y + (20.0 * scale) as usize + 1,
+        );
+
+        assert_eq!(world.screen_to_grid(x, y), Some((10, 20)));
+        assert_eq!(world.screen_to_grid(CELLS_X, y), None);
+        assert_eq!(world.screen_to_grid(CELLS_X + 200, y), None);
+    }
+
+    #[test]
+    fn picked_colors_come_from_heat_cells() {
+        let mut world = World::with_size(50, 40, 0);
+        world.set_tile(10, 20, Cell::Heat(9.0, 8.0, 7.0));
+        let scale = world.draw_scale;
+        let at = |col: f32, row: f32| Command::PickColor {
+            x: (CELLS_X as f32 + col * scale) as i16 + 1,
+            y: (CELLS_Y as f32 + row * scale) as i16 + 1,
+        };
+
+        world.apply(at(10.0, 20.0));
+        assert_eq!(world.color_strategy, ColorStrategy::Fixed);
+        assert_eq!(world.spawn_color, (9, 8, 7));
+
+        // Empty cells and clicks off the grid keep the color.
+        world.apply(at(11.0, 20.0));
+        world.apply(at(60.0, 20.0));
+        assert_eq!(world.spawn_color, (9, 8, 7));
+    }
+
+    #[test]
+    fn speed_trail_and_deposit_steps_stay_in_range() {
+        let mut world = World::with_size(20, 20, 0);
+        world.params.sensing = false;
+        world.apply(Command::Faster);
+        world.push_agent(Agent::with_heading(5.0, 5.0, (200, 100, 0), 0.0));
+        world.update_agents();
+        assert_eq!(world.agents()[0].x, 6.25);
+        for _ in 0..20 {
+            world.apply(Command::Slower);
+        }
+        assert_eq!(world.params.motion.speed, MIN_SPEED);
+
+        world.apply(Command::LongerTrails);
+        world.apply(Command::LongerTrails);
+        world.apply(Command::LongerTrails);
+        assert_eq!(world.params.evaporation, 1.0);
+        world.apply(Command::ShorterTrails);
+        assert_eq!(world.params.evaporation, 0.99);
+
+        // Single-cell deposits scale too, saturating at full heat.
+        world.apply(Command::StrongerDeposits);
+        world.apply(Command::StrongerDeposits);
+        world.update_agents();
+        let (x, y) = (world.agents()[0].x, world.agents()[0].y);
+        assert_eq!(
+            world.cell_at(x, y).map(|cell| cell.rgb()),
+            Some((255, 156, 0))
+        );
+        for _ in 0..20 {
+            world.apply(Command::WeakerDeposits);
+        }
+        assert_eq!(world.params.deposit_strength, MIN_DEPOSIT_STRENGTH);
+    }
+
+    #[test]
+    fn agent_scale_grows_markers_and_deposits() {
+        let mut world = World::new_seeded(4);
+        world.agent_markers = AgentMarkers::Dot;
+        world.apply(Command::GrowAgents);
+        world.apply(Command::GrowAgents);
+        world.apply(Command::GrowAgents);
+        world.apply(Command::ShrinkAgents);
+        assert_eq!(world.params.agent_scale, 1.25 * 1.25);
+        world.params.agent_scale = 3.0;
+        world.spawn_agent(50.0, 30.0);
+        let mut frame = vec![0xff; (SCREEN_WIDTH * SCREEN_HEIGHT * 4) as usize];
+        world.draw_agents(&mut frame, SCREEN_WIDTH as usize, world.view(), 1.0);
+        let red = |x: usize, y: usize| frame[(x + y * SCREEN_WIDTH as usize) * 4];
+        let (x, y) = (200, 260);
+        assert!([(x - 1, y - 1), (x + 1, y + 1)]
+            .iter()
+            .all(|&(x, y)| red(x, y) != 0xff));
+        assert_eq!(red(x + 2, y), 0xff);
+
+        world.params.deposit_radius = 1.0;
+        world.stamp(100.5, 100.5, (200, 200, 200));
+        assert!(world.cell_at(102.5, 100.5).unwrap().intensity() > 0.0);
+        assert_eq!(world.cell_at(104.5, 100.5), Some(Cell::Empty));
+    }
+
+    #[test]
+    fn dense_passes_swap_between_two_buffers() {
+        let mut world = World::with_size(30, 20, 0);
+        world.set_tile(5, 5, Cell::Heat(200.0, 100.0, 50.0));
+        let front = world.tiles().as_ptr();
+        world.update_tiles();
+        let back = world.tiles().as_ptr();
+        assert_ne!(front, back);
+        world.update_tiles();
+        assert_eq!(world.tiles().as_ptr(), front);
+        world.update_tiles();
+        assert_eq!(world.tiles().as_ptr(), back);
+    }
+
+    #[test]
+    fn scan_order_only_matters_in_place() {
+        let run = |grid_update, scan_order| {
+            let mut world = World::new_seeded(3);
+            world.params.grid_update = grid_update;
+            world.params.scan_order = scan_order;
+            world.set_tile(150, 150, Cell::Heat(255.0, 255.0, 255.0));
+            world.update_tiles();
+            world
+        };
+        let intensity = |world: &World, x: f32| world.cell_at(x, 150.0).unwrap().intensity();
+
+        let raster = run(GridUpdate::Dense, ScanOrder::Raster);
+        for order in [ScanOrder::Boustrophedon, ScanOrder::Random] {
+            assert!(raster.tiles() == run(GridUpdate::Dense, order).tiles());
+        }
+        assert_eq!(intensity(&raster, 149.0), intensity(&raster, 151.0));
+
+        // In place, cells scanned before the hot spot see it at full heat,
+        // cells after it only see its already spread-out value.
+        let in_place = run(GridUpdate::InPlace, ScanOrder::Raster);
+        assert!(intensity(&in_place, 149.0) > intensity(&in_place, 151.0));
+    }
+
+    #[test]
+    fn blend_modes_combine_matching_cells() {
+        let mut a = World::with_size(2, 1, 0);
+        let mut b = World::with_size(2, 1, 0);
+        a.set_tile(0, 0, Cell::Heat(100.0, 200.0, 50.0));
+        b.set_tile(0, 0, Cell::Heat(100.0, 100.0, 250.0));
+        let blended = |mode| {
+            let mut out = [0; 8];
+            a.blend_into(&b, mode, &mut out);
+            out
+        };
+
+        // The second cell is background in both, which every mode keeps.
+        assert_eq!(
+            blended(BlendMode::Add),
+            [200, 255, 255, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(
+            blended(BlendMode::Max),
+            [100, 200, 250, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(
+            blended(BlendMode::Screen),
+            [161, 222, 251, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "share dimensions")]
+    fn blending_different_sizes_panics() {
+        let mut out = [0; 8];
+        World::with_size(2, 1, 0).blend_into(&World::with_size(1, 2, 0), BlendMode::Max, &mut out);
+    }
+
+    #[test]
+    fn decay_maps_fade_cells_at_their_own_rate() {
+        let mut world = World::with_size(4, 3, 0);
+        assert!(world.set_decay_map(Some(vec![0.5; 11])).is_err());
+        assert!(world.set_decay_map(Some(vec![0.0; 12])).is_err());
+        assert!(world.set_decay_map(Some(vec![1.5; 12])).is_err());
+
+        let mut map = vec![1.0; 12];
+        map[0] = 0.5;
+        map[3] = 0.004;
+        world.set_decay_map(Some(map)).unwrap();
+        world.params.diffusion_rate = [0.0; 3];
+        for x in [0, 1, 3] {
+            world.set_tile(x, 0, Cell::Heat(200.0, 100.0, 50.0));
+        }
+        world.update_tiles();
+        assert_eq!(world.cell_at(0.0, 0.0), Some(Cell::Heat(100.0, 50.0, 25.0)));
+        assert_eq!(
+            world.cell_at(1.0, 0.0),
+            Some(Cell::Heat(200.0, 100.0, 50.0))
+        );
+        assert_eq!(world.cell_at(3.0, 0.0), Some(Cell::Empty));
+
+        // Without a map every cell fades by the scalar.
+        world.set_decay_map(None).unwrap();
+        world.params.evaporation = 0.5;
+        world.update_tiles();
+        assert_eq!(world.cell_at(1.0, 0.0), Some(Cell::Heat(100.0, 50.0, 25.0)));
+    }
+
+    #[test]
+    fn trails_linger_a_few_passes_then_evaporate() {
+        let mut world = World::with_size(20, 20, 0);
+        world.set_tile(10, 10, Cell::Heat(255.0, 255.0, 255.0));
+        for _ in 0..3 {
+            world.update_tiles();
+        }
+        assert!(world.cell_at(10.0, 10.0).unwrap().intensity() > 0.0);
+        for _ in 0..50 {
+            world.update_tiles();
+        }
+        assert!(world.tiles().iter().all(|&tile| tile == Cell::Empty));
+
+        // Heat fading under the threshold goes at once, whatever is left.
+        world.params.diffusion_rate = [0.0; 3];
+        world.params.evaporation_threshold = 10.0;
+        world.set_tile(0, 0, Cell::Heat(10.0, 3.0, 0.0));
+        world.set_tile(1, 0, Cell::Heat(11.0, 3.0, 0.0));
+        world.update_tiles();
+        assert_eq!(world.cell_at(0.0, 0.0), Some(Cell::Empty));
+        assert_eq!(
+            world.cell_at(1.0, 0.0).map(|cell| cell.rgb()),
+            Some((10, 2, 0))
+        );
+    }
+
+    #[test]
+    fn attractor_field_must_cover_the_grid() {
+        let mut world = World::with_size(4, 3, 0);
+        assert!(world.set_attractor_field(Some(vec![0.5; 11])).is_err());
+        assert!(world.set_attractor_field(Some(vec![0.5; 12])).is_ok());
+        assert!(world.set_attractor_field(None).is_ok());
+    }
+
+    #[test]
+    fn blended_draws_place_agents_between_updates() {
+        let mut world = World::new_seeded(4);
+        world.agent_markers = AgentMarkers::Dot;
+        world
+            .agents
+            .push(Agent::with_heading(50.0, 30.0, (1, 2, 3), 0.0));
+        world.update_agents();
+        assert_eq!((world.agents[0].x, world.agents[0].y), (51.0, 30.0));
+
+        let marked = |alpha| {
+            let mut frame = vec![0xff; (SCREEN_WIDTH * SCREEN_HEIGHT * 4) as usize];
+            world.draw_agents(&mut frame, SCREEN_WIDTH as usize, world.view(), alpha);
+            let row = (CELLS_Y + 60) * SCREEN_WIDTH as usize;
+            (200..203).find(|&x| frame[(x + row) * 4] == 0)
+        };
+        assert_eq!(marked(0.0), Some(200));
+        assert_eq!(marked(0.5), Some(201));
+        assert_eq!(marked(1.0), Some(202));
+    }
+
+    #[test]
+    fn auto_exposure_stretches_dim_fields_smoothly() {
+        let mut world = World::with_size(2, 1, 0);
+        world.auto_exposure = true;
+        world.set_tile(0, 0, Cell::Heat(51.0, 17.0, 0.0));
+        world.set_tile(1, 0, Cell::Heat(0.0, 0.0, 17.0));
+        let mut frame = vec![0; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4];
+        world.draw(&mut frame);
+        assert_eq!(
+            world.tile_color(Cell::Heat(51.0, 17.0, 0.0), 0.0),
+            [255, 85, 0, 0xff]
+        );
+
+        // A sudden bright cell only pulls the exposure part of the way.
+        world.set_tile(1, 0, Cell::Heat(0.0, 0.0, 255.0));
+        world.draw(&mut frame);
+        assert_eq!(
+            world.exposure,
+            Some(51.0 + (255.0 - 51.0) * EXPOSURE_SMOOTHING)
+        );
+
+        world.auto_exposure = false;
+        assert_eq!(
+            world.tile_color(Cell::Heat(51.0, 17.0, 0.0), 0.0),
+            [51, 17, 0, 0xff]
+        );
+    }
+
+    #[test]
+    fn tone_curves_lift_faint_trails() {
+        let mut world = World::with_size(2, 1, 0);
+        let faint = Cell::Heat(64.0, 0.0, 255.0);
+        assert_eq!(world.tile_color(faint, 0.0), [64, 0, 255, 0xff]);
+
+        world.tone_curve = ToneCurve::Gamma(0.5);
+        assert_eq!(world.tile_color(faint, 0.0), [127, 0, 255, 0xff]);
+        world.tone_curve = ToneCurve::Log(100.0);
+        let [r, g, b, _] = world.tile_color(faint, 0.0);
+        assert!(r > 170 && g == 0 && b >= 254);
+        // The trails themselves are untouched.
+        assert_eq!(world.cell_at(0.0, 0.0), Some(Cell::Empty));
+    }
+
+    #[test]
+    fn flood_spawns_fill_only_the_clicked_compartment() {
+        let mut world = World::with_size(10, 10, 6);
+        for y in 0..10 {
+            world.set_tile(5, y, Cell::Heat(255.0, 255.0, 255.0));
+        }
+        world.params.flood_density = 1.0;
+        world.flood_spawn(2, 2);
+
+        assert_eq!(world.agents().len(), 50);
+        assert!(world.agents().iter().all(|agent| agent.x < 5.0));
+
+        world.params.max_agents = 60;
+        world.flood_spawn(8, 8);
+        assert_eq!(world.agents().len(), 60);
+        world.flood_spawn(5, 5);
+        assert_eq!(world.agents().len(), 60);
+    }
+
+    #[test]
+    fn deposit_interval_leaves_dashed_trails() {
+        let heated_after_six_steps = |interval| {
+            let mut world = World::new_seeded(2);
+            world.params.deposit_interval = interval;
+            world
+                .agents
+                .push(Agent::with_heading(50.0, 1.0, (9, 9, 9), 0.0));
+            for _ in 0..6 {
+                world.update_agents();
+            }
+            world
+                .tiles
+                .iter()
+                .filter(|cell| matches!(cell, Cell::Heat(..)))
+                .count()
+        };
+        assert_eq!(heated_after_six_steps(1), 6);
+        assert_eq!(heated_after_six_steps(3), 2);
+    }
+
+    #[test]
+    fn shrinking_keeps_the_overlap_and_agents_in_range() {
+        let mut world = World::with_size(20, 10, 3);
+        world.set_tile(2, 3, Cell::Heat(1.0, 2.0, 3.0));
+        world.set_tile(15, 3, Cell::Heat(4.0, 5.0, 6.0));
+        world.spawn_random(50);
+        world.resize(8, 6);
+
+        assert_eq!(world.tiles().len(), 48);
+        assert_eq!(world.cell_at(2.0, 3.0), Some(Cell::Heat(1.0, 2.0, 3.0)));
+        assert_eq!(world.sparse.active, vec![2 + 3 * 8]);
+        assert_eq!(world.agents().len(), 50);
+        assert!(world
+            .agents()
+            .iter()
+            .all(|agent| agent.x >= 0.0 && agent.x <= 7.0 && agent.y >= 0.0 && agent.y <= 5.0));
+        world.advance(10);
+
+        world.resize(12, 12);
+        assert_eq!(world.cell_at(11.0, 11.0), Some(Cell::Empty));
+        world.advance(10);
+
+        world.resize(0, 0);
+        assert!(world.agents().is_empty());
+    }
+
+    #[test]
+    fn diffuse_every_skips_the_updates_in_between() {
+        let mut world = World::with_size(20, 20, 0);
+        world.params.diffuse_every = 3;
+        world.set_tile(10, 10, Cell::Heat(255.0, 255.0, 255.0));
+        let mut heated = Vec::new();
+        for _ in 0..4 {
+            world.update();
+            heated.push(world.tiles().iter().filter(|&&c| c != Cell::Empty).count());
+        }
+        assert_eq!(heated, [9, 9, 9, 25]);
+    }
+
+    #[test]
+    fn skipped_diffusions_still_evaporate_unless_told_not_to() {
+        let total_after = |diffuse_every, decay_between_diffusions| {
+            let mut world = World::with_size(10, 10, 0);
+            world.params.diffusion_rate = [0.0; 3];
+            world.params.evaporation = 0.9;
+            world.params.evaporation_threshold = 0.0;
+            world.params.diffuse_every = diffuse_every;
+            world.params.decay_between_diffusions = decay_between_diffusions;
+            world.set_tile(5, 5, Cell::Heat(200.0, 100.0, 0.0));
+            world.add_source(1, 1, (0, 50, 0));
+            // The last of these updates is one that skips diffusing.
+            world.advance(6);
+            assert_eq!(world.cell_at(1.0, 1.0), Some(Cell::Heat(0.0, 50.0, 0.0)));
+            world.set_tile(1, 1, Cell::Empty);
+            world.total_intensity()
+        };
+
+        let every = total_after(1, true);
+        assert!((total_after(2, true) - every).abs() < 1e-3);
+        // Without the decay in between, trails fade half as often.
+        assert!(total_after(2, false) > every + 1.0);
+    }
+
+    #[test]
+    fn dithering_mixes_neighbouring_levels_across_a_cell() {
+        let mut world = World::with_size(1, 1, 0);
+        world.fade_colors = true;
+        world.cool_color = (0, 0, 0);
+        world.hot_color = (2, 2, 2);
+        let red_levels = |world: &mut World| {
+            let mut frame = vec![0; (SCREEN_WIDTH * SCREEN_HEIGHT * 4) as usize];
+            world.draw_scale = 8.0;
+            world.draw(&mut frame);
+            let mut levels: Vec<u8> = (CELLS_Y + 1..CELLS_Y + 8)
+                .flat_map(|y| (CELLS_X + 1..CELLS_X + 8).map(move |x| (x, y)))
+                .map(|(x, y)| frame[(x + y * SCREEN_WIDTH as usize) * 4])
+                .collect();
+            levels.sort();
+            levels.dedup();
+            levels
+        };
+        // Three quarters of the way along the gradient, level 1.49.
+        world.set_tile(0, 0, Cell::Heat(190.0, 190.0, 190.0));
+        assert_eq!(red_levels(&mut world), [1]);
+        world.dither = true;
+        assert_eq!(red_levels(&mut world), [1, 2]);
+    }
+
+    #[test]
+    fn sources_stay_at_full_heat_through_diffusion() {
+        let mut world = World::with_size(9, 9, 0);
+        world.params.evaporation = 1.0;
+        world.add_source(4, 4, (200, 0, 90));
+        for _ in 0..20 {
+            world.update_tiles();
+        }
+        assert_eq!(world.cell_at(4.0, 4.0), Some(Cell::Heat(200.0, 0.0, 90.0)));
+        assert!(world.cell_at(0.0, 0.0).unwrap().intensity() > 0.0);
+
+        world.add_source(4, 4, (1, 1, 1));
+        assert_eq!(world.sources.len(), 1);
+        world.resize(4, 4);
+        assert!(world.sources.is_empty());
+    }
+
+    #[test]
+    fn saved_worlds_load_back_whole_and_reject_bad_files() {
+        let mut world = World::with_size(30, 20, 3);
+        world.params.deposit_strength = 0.5;
+        world.spawn_random(25);
+        world.advance(10);
+        let path = std::env::temp_dir().join(format!("z_slime_state_{}.json", std::process::id()));
+        world.save(&path).unwrap();
+
+        let mut loaded = World::load(&path).unwrap();
+        assert_eq!(
+            (loaded.width(), loaded.height(), loaded.step()),
+            (30, 20, 10)
+        );
+        assert_eq!(loaded.tiles(), world.tiles());
+        assert_eq!(loaded.params, world.params);
+        let place = |agent: &Agent| (agent.id(), agent.x, agent.y, agent.rgb);
+        assert!(loaded
+            .agents()
+            .iter()
+            .map(place)
+            .eq(world.agents().iter().map(place)));
+        loaded.spawn_agent(1.0, 1.0);
+        assert_eq!(loaded.agents().last().unwrap().id(), 26);
+
+        assert!(World::with_size(20, 30, 0).restore(&path).is_err());
+        let mut text: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        text["width"] = 31.into();
+        fs::write(&path, text.to_string()).unwrap();
+        let err = World::load(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(World::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn trajectories_export_as_capped_svg_paths() {
+        let mut world = World::with_size(50, 50, 0);
+        world.params.trajectory_points = 3;
+        world.push_agent(Agent::with_heading(10.0, 20.0, (255, 0, 16), 0.0));
+        world.push_agent(Agent::with_heading(30.0, 20.0, (0, 0, 0), 0.0));
+        for _ in 0..5 {
+            world.update_agents();
+        }
+        world.agents[1].record_position(0);
+
+        let path = std::env::temp_dir().join(format!("z_slime_paths_{}.svg", std::process::id()));
+        world.export_svg(&path).unwrap();
+        let svg = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(
+            "<path id=\"agent-1\" d=\"M13.00 20.00 L14.00 20.00 L15.00 20.00\" fill=\"none\" stroke=\"#ff0010\""
+        ));
+        assert_eq!(svg.matches("<path").count(), 1);
+    }
+
+    #[test]
+    fn diffusion_stats_count_edge_cases_per_update_or_cumulatively() {
+        let mut world = World::with_size(5, 5, 0);
+        world.set_tile(0, 0, Cell::Heat(90.0, 0.0, 0.0));
+        world.set_tile(3, 3, Cell::Heat(0.1, 0.0, 0.0));
+        world.update_tiles();
+        assert_eq!(
+            world.diffusion_stats(),
+            DiffusionStats {
+                // Three edge cells around the corner heat, five along the
+                // far edges next to the weak cell.
+                edge_clamped: 8,
+                saturated: 0,
+                underflowed: 1,
+            }
+        );
+
+        // Overshooting toward a hot ring pushes its empty middle past 255.
+        world.params.diffusion_rate = [2.0; 3];
+        for (x, y) in [
+            (1, 1),
+            (2, 1),
+            (3, 1),
+            (1, 2),
+            (3, 2),
+            (1, 3),
+            (2, 3),
+            (3, 3),
+        ] {
+            world.set_tile(x, y, Cell::Heat(250.0, 250.0, 250.0));
+        }
+        world.set_tile(2, 2, Cell::Empty);
+        world.update_tiles();
+        assert!(world.diffusion_stats().saturated > 0);
+
+        world.cumulative_stats = true;
+        let before = world.diffusion_stats();
+        world.update_tiles();
+        assert!(world.diffusion_stats().edge_clamped > before.edge_clamped);
+    }
+
+    #[test]
+    fn agents_only_draws_markers_on_a_bare_background() {
+        let mut world = World::new_seeded(1);
+        world.set_tile(10, 10, Cell::Heat(200.0, 0.0, 0.0));
+        world
+            .agents
+            .push(Agent::with_heading(50.0, 30.0, (1, 2, 3), 0.0));
+        world.agents_only = true;
+        let mut frame = vec![0; (SCREEN_WIDTH * SCREEN_HEIGHT * 4) as usize];
+        world.draw(&mut frame);
+
+        let pixel = |x: usize, y: usize| {
+            let idx = (x + y * SCREEN_WIDTH as usize) * 4;
+            [frame[idx], frame[idx + 1], frame[idx + 2]]
+        };
+        assert_eq!(pixel(CELLS_X + 21, CELLS_Y + 21), [0xff; 3]);
+        assert_eq!(pixel(CELLS_X + 100, CELLS_Y + 60), MARKER_COLOR);
+    }
+
+    #[test]
+    fn gradient_colors_come_from_the_stops_and_the_seed() {
+        let stops = [(0, 0, 0), (200, 100, 0), (200, 200, 200)];
+        assert_eq!(gradient_color(&stops, 0.0), (0, 0, 0));
+        assert_eq!(gradient_color(&stops, 0.25), (100, 50, 0));
+        assert_eq!(gradient_color(&stops, 1.0), (200, 200, 200));
+        assert_eq!(gradient_color(&stops[..1], 0.7), (0, 0, 0));
+
+        let colors = |seed| {
+            let mut world = World::new_seeded(seed);
+            world.color_strategy = ColorStrategy::PaletteGradient(vec![(0, 0, 0), (200, 100, 0)]);
+            world.spawn_random(20);
+            world
+                .agents
+                .iter()
+                .map(|agent| agent.rgb)
+                .collect::<Vec<_>>()
+        };
+        let run = colors(5);
+        assert_eq!(run, colors(5));
+        // Every color lies on the line between the two stops.
+        assert!(run
+            .iter()
+            .all(|&(r, g, b)| b == 0 && g.abs_diff(r / 2) <= 1));
+        assert!(run.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn network_metrics_follow_trails_between_sources() {
+        let mut world = World::with_size(20, 20, 0);
+        // A straight trail from (2, 5) to (12, 5), then a diagonal down to
+        // (15, 8), and a separate patch around (3, 15).
+        for col in 2..=12 {
+            world.set_tile(col, 5, Cell::Heat(200.0, 200.0, 200.0));
+        }
+        for step in 1..=3 {
+            world.set_tile(12 + step, 5 + step, Cell::Heat(200.0, 200.0, 200.0));
+        }
+        world.set_tile(3, 15, Cell::Heat(200.0, 200.0, 200.0));
+
+        let metrics = world.network_metrics(&[(2, 5), (12, 5), (15, 8)], 100.0);
+        assert_eq!(metrics.connectivity, 1.0);
+        let expected = 10.0 + 3.0 * std::f32::consts::SQRT_2;
+        assert!((metrics.total_length - expected).abs() < 1e-4);
+
+        let metrics = world.network_metrics(&[(2, 5), (15, 8), (3, 15)], 100.0);
+        assert!((metrics.connectivity - 1.0 / 3.0).abs() < 1e-6);
+        assert!((metrics.total_length - expected).abs() < 1e-4);
+        // Above the trail's intensity nothing is connected.
+        let metrics = world.network_metrics(&[(2, 5), (12, 5)], 250.0);
+        assert_eq!(metrics.connectivity, 0.0);
+        assert_eq!(metrics.total_length, 0.0);
+    }
+
+    #[test]
+    fn contours_trace_level_crossings() {
+        let mut world = World::with_size(20, 20, 0);
+        for row in 0..20 {
+            for col in 10..20 {
+                world.set_tile(col, row, Cell::Heat(150.0, 150.0, 150.0));
+            }
+        }
+        world.contours = true;
+        world.contour_levels = vec![100.0];
+        let lines = world.contour_mask();
+        assert!((0..20).all(|row| lines[row * 20 + 9]));
+        assert_eq!(lines.iter().filter(|&&on| on).count(), 20);
+
+        let mut frame = vec![0; (SCREEN_WIDTH * SCREEN_HEIGHT * 4) as usize];
+        world.draw(&mut frame);
+        let scale = world.draw_scale as usize;
+        let pixel = |col: usize, row: usize| {
+            let (x, y) = (CELLS_X + col * scale + 1, CELLS_Y + row * scale + 1);
+            let idx = (x + y * SCREEN_WIDTH as usize) * 4;
+            [frame[idx], frame[idx + 1], frame[idx + 2], frame[idx + 3]]
+        };
+        assert_eq!(pixel(9, 5), CONTOUR_COLOR);
+        assert_ne!(pixel(10, 5), CONTOUR_COLOR);
+        assert_ne!(pixel(8, 5), CONTOUR_COLOR);
+    }
+
+    #[test]
+    fn soft_deposits_stamp_a_falloff_around_the_agent() {
+        let mut world = World::with_size(20, 20, 0);
+        world.params.deposit_radius = 2.0;
+        world.params.deposit_strength = 0.8;
+        world.stamp(10.5, 10.5, (200, 100, 0));
+
+        assert_eq!(
+            world.cell_at(10.0, 10.0),
+            Some(Cell::Heat(160.0, 80.0, 0.0))
+        );
+        let Some(Cell::Heat(r, ..)) = world.cell_at(11.0, 10.0) else {
+            panic!("neighbour got no heat");
+        };
+        assert!(r < 160.0 && r > 0.0);
+        assert_eq!(world.cell_at(13.0, 10.0), Some(Cell::Empty));
+        assert_eq!(world.cell_at(12.0, 12.0), Some(Cell::Empty));
+
+        // A fainter stamp doesn't cool what's already there.
+        world.params.deposit_strength = 0.1;
+        world.stamp(10.5, 10.5, (200, 100, 0));
+        assert_eq!(
+            world.cell_at(10.0, 10.0),
+            Some(Cell::Heat(160.0, 80.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn palettes_snap_drawn_cells_to_the_nearest_color() {
+        let palette = [(0, 0, 0), (255, 255, 255), (200, 0, 0)];
+        assert_eq!(
+            nearest_color([180, 30, 20, 0xff], &palette, 0.0),
+            [200, 0, 0, 0xff]
+        );
+        assert_eq!(
+            nearest_color([100, 100, 100, 0xff], &palette, 0.0),
+            [0, 0, 0, 0xff]
+        );
+        assert_eq!(
+            nearest_color([100, 100, 100, 0xff], &palette, 40.0),
+            [255, 255, 255, 0xff]
+        );
+
+        let mut world = World::with_size(1, 1, 0);
+        world.set_tile(0, 0, Cell::Heat(180.0, 30.0, 20.0));
+        world.palette = Some(palette.to_vec());
+        let mut frame = vec![0; (SCREEN_WIDTH * SCREEN_HEIGHT * 4) as usize];
+        world.draw(&mut frame);
+        let idx = (CELLS_X + 1 + (CELLS_Y + 1) * SCREEN_WIDTH as usize) * 4;
+        assert_eq!(frame[idx..idx + 4], [200, 0, 0, 0xff]);
+        assert_eq!(world.tiles()[0], Cell::Heat(180.0, 30.0, 20.0));
+    }
+}